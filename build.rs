@@ -11,6 +11,7 @@ use strfmt::strfmt;
 
 #[derive(Debug)]
 struct Error {
+    #[allow(dead_code)]
     message: String,
 }
 
@@ -30,7 +31,7 @@ impl From<env::VarError> for Error {
     }
 }
 
-type Matcher = fn(&PathBuf) -> bool;
+type Matcher = fn(&Path) -> bool;
 
 fn generate_tests(destination: &str, module: &str, path: &str, template: &str, matcher: Matcher) -> Result<(), Error> {
     let out_dir = env::var("OUT_DIR")?;
@@ -42,14 +43,9 @@ fn generate_tests(destination: &str, module: &str, path: &str, template: &str, m
     Ok(())
 }
 
-fn generate_tests_module(
-    mut test_file: &mut File,
-    dir: &PathBuf,
-    template: &str,
-    matcher: Matcher,
-) -> Result<(), Error> {
+fn generate_tests_module(test_file: &mut File, dir: &Path, template: &str, matcher: Matcher) -> Result<(), Error> {
     let module_name = normalize_file_stem(dir)?;
-    start_module(&mut test_file, &module_name)?;
+    start_module(test_file, &module_name)?;
 
     for entry in read_dir(dir)? {
         let entry = entry?;
@@ -57,20 +53,18 @@ fn generate_tests_module(
 
         if path.is_dir() {
             generate_tests_module(test_file, &path, template, matcher)?;
-        } else {
-            if matcher(&path) {
-                generate_test(test_file, &path, template)?;
-            }
+        } else if matcher(&path) {
+            generate_test(test_file, &path, template)?;
         }
     }
 
-    end_module(&mut test_file)?;
+    end_module(test_file)?;
     Ok(())
 }
 
-fn generate_test(test_file: &mut File, path: &PathBuf, template: &str) -> Result<(), Error> {
+fn generate_test(test_file: &mut File, path: &Path, template: &str) -> Result<(), Error> {
     let mut vars = HashMap::new();
-    vars.insert("name".to_string(), format!("{}", normalize_file_stem(&path)?));
+    vars.insert("name".to_string(), normalize_file_stem(path)?);
     vars.insert(
         "path".to_string(),
         path.to_str().expect("unable to format path as a string").to_string(),
@@ -115,7 +109,7 @@ fn end_module(test_file: &mut File) -> Result<(), Error> {
     Ok(())
 }
 
-fn normalize_file_stem(path: &PathBuf) -> Result<String, Error> {
+fn normalize_file_stem(path: &Path) -> Result<String, Error> {
     let result = path
         .file_stem()
         .ok_or(Error {
@@ -126,14 +120,14 @@ fn normalize_file_stem(path: &PathBuf) -> Result<String, Error> {
     Ok(result.to_string())
 }
 
-fn validator_tests_matcher(path: &PathBuf) -> bool {
+fn validator_tests_matcher(path: &Path) -> bool {
     match path.extension() {
         Some(ext) => ext == "yaml",
         _ => false,
     }
 }
 
-fn generator_tests_matcher(path: &PathBuf) -> bool {
+fn generator_tests_matcher(path: &Path) -> bool {
     match path.file_name() {
         Some(name) => name == "input-schema.yaml",
         _ => false,
@@ -169,5 +163,12 @@ fn main() -> Result<(), Error> {
         "./tests/generator/valid-test-template",
         generator_tests_matcher,
     )?;
+    generate_tests(
+        "generator_round_trip_tests.rs",
+        "rt",
+        "./tests/generator/valid",
+        "./tests/generator/round-trip-test-template",
+        generator_tests_matcher,
+    )?;
     Ok(())
 }
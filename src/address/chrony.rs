@@ -0,0 +1,310 @@
+// https://github.com/balena-os/meta-balena/blob/v2.29.2/meta-resin-common/recipes-connectivity/resin-ntp-config/resin-ntp-config/resin-ntp-config#L19
+//
+// chrony server address (`chronyc add server`):
+//
+//   <host> [<option> [<value>]]...
+//
+// Host is a hostname, IPv4 or IPv6 address. Supported options are `port`, `minpoll`,
+// `maxpoll`, `presend`, `maxdelayratio`, `maxdelay`, `key` and the `iburst` flag.
+//
+//   foo.example.net minpoll 6 maxpoll 10 key 25 iburst
+use std::fmt;
+use std::net::IpAddr;
+use std::str::FromStr;
+
+use serde_derive::Serialize;
+
+use crate::{
+    address::{is_domain, parse_port},
+    error::Error,
+};
+
+/// Minimum poll interval (log2 of seconds, 1/64 s)
+pub const MIN_POLL: i8 = -6;
+/// Maximum poll interval (log2 of seconds, ~ 6 months)
+pub const MAX_POLL: i8 = 24;
+
+/// Parsed `chrony-address` value
+///
+/// Serializes into an object with the `host` and the present options, the `iburst` flag
+/// is omitted if it's not set.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ChronyAddress {
+    host: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    port: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    minpoll: Option<i8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    maxpoll: Option<i8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    presend: Option<i8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    maxdelayratio: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    maxdelay: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    key: Option<u32>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    iburst: bool,
+}
+
+impl ChronyAddress {
+    /// Hostname, IPv4 or IPv6 address
+    pub fn host(&self) -> &str {
+        &self.host
+    }
+
+    pub fn port(&self) -> Option<u16> {
+        self.port
+    }
+
+    /// Minimum poll interval (log2 of seconds)
+    pub fn minpoll(&self) -> Option<i8> {
+        self.minpoll
+    }
+
+    /// Maximum poll interval (log2 of seconds)
+    pub fn maxpoll(&self) -> Option<i8> {
+        self.maxpoll
+    }
+
+    /// Poll interval (log2 of seconds) from which an extra request is sent ahead of the real one
+    pub fn presend(&self) -> Option<i8> {
+        self.presend
+    }
+
+    pub fn maxdelayratio(&self) -> Option<f64> {
+        self.maxdelayratio
+    }
+
+    /// Maximum delay in seconds
+    pub fn maxdelay(&self) -> Option<f64> {
+        self.maxdelay
+    }
+
+    pub fn key(&self) -> Option<u32> {
+        self.key
+    }
+
+    pub fn iburst(&self) -> bool {
+        self.iburst
+    }
+}
+
+fn parse_poll(option: &str, value: &str) -> Result<i8, Error> {
+    match value.parse::<i8>() {
+        Ok(poll) if (MIN_POLL..=MAX_POLL).contains(&poll) => Ok(poll),
+        _ => Err(Error::message(format!(
+            "invalid value of the '{}' option: '{}', expected an integer between {} and {}",
+            option, value, MIN_POLL, MAX_POLL
+        ))),
+    }
+}
+
+fn parse_positive_number(option: &str, value: &str) -> Result<f64, Error> {
+    match value.parse::<f64>() {
+        Ok(number) if number.is_finite() && number > 0.0 => Ok(number),
+        _ => Err(Error::message(format!(
+            "invalid value of the '{}' option: '{}', expected a positive number",
+            option, value
+        ))),
+    }
+}
+
+impl FromStr for ChronyAddress {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<ChronyAddress, Error> {
+        let mut tokens = s.split_whitespace();
+
+        let host = tokens.next().ok_or_else(|| Error::message("missing host"))?;
+        if host.parse::<IpAddr>().is_err() && !is_domain(host) {
+            return Err(Error::message(format!("invalid host '{}'", host)));
+        }
+
+        let mut address = ChronyAddress {
+            host: host.to_string(),
+            ..Default::default()
+        };
+        let mut options: Vec<&str> = vec![];
+
+        while let Some(option) = tokens.next() {
+            if options.contains(&option) {
+                return Err(Error::message(format!("duplicate option '{}'", option)));
+            }
+            options.push(option);
+
+            if option == "iburst" {
+                address.iburst = true;
+                continue;
+            }
+
+            let mut value = || {
+                tokens
+                    .next()
+                    .ok_or_else(|| Error::message(format!("missing value of the '{}' option", option)))
+            };
+
+            match option {
+                "port" => {
+                    let value = value()?;
+                    address.port = Some(
+                        parse_port(value)
+                            .map_err(|_| Error::message(format!("invalid value of the 'port' option: '{}'", value)))?,
+                    );
+                }
+                "minpoll" => address.minpoll = Some(parse_poll(option, value()?)?),
+                "maxpoll" => address.maxpoll = Some(parse_poll(option, value()?)?),
+                "presend" => address.presend = Some(parse_poll(option, value()?)?),
+                "maxdelayratio" => address.maxdelayratio = Some(parse_positive_number(option, value()?)?),
+                "maxdelay" => address.maxdelay = Some(parse_positive_number(option, value()?)?),
+                "key" => {
+                    let value = value()?;
+                    address.key = match value.parse::<u32>() {
+                        Ok(key) if key > 0 => Some(key),
+                        _ => {
+                            return Err(Error::message(format!(
+                                "invalid value of the 'key' option: '{}', expected a positive integer",
+                                value
+                            )));
+                        }
+                    };
+                }
+                _ => return Err(Error::message(format!("unknown option '{}'", option))),
+            };
+        }
+
+        if let (Some(minpoll), Some(maxpoll)) = (address.minpoll, address.maxpoll) {
+            if minpoll > maxpoll {
+                return Err(Error::message(format!(
+                    "minpoll ({}) must not be greater than maxpoll ({})",
+                    minpoll, maxpoll
+                )));
+            }
+        }
+
+        Ok(address)
+    }
+}
+
+impl fmt::Display for ChronyAddress {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.host)?;
+
+        if let Some(port) = self.port {
+            write!(f, " port {}", port)?;
+        }
+        if let Some(minpoll) = self.minpoll {
+            write!(f, " minpoll {}", minpoll)?;
+        }
+        if let Some(maxpoll) = self.maxpoll {
+            write!(f, " maxpoll {}", maxpoll)?;
+        }
+        if let Some(presend) = self.presend {
+            write!(f, " presend {}", presend)?;
+        }
+        if let Some(maxdelayratio) = self.maxdelayratio {
+            write!(f, " maxdelayratio {}", maxdelayratio)?;
+        }
+        if let Some(maxdelay) = self.maxdelay {
+            write!(f, " maxdelay {}", maxdelay)?;
+        }
+        if let Some(key) = self.key {
+            write!(f, " key {}", key)?;
+        }
+        if self.iburst {
+            write!(f, " iburst")?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn parse(s: &str) -> ChronyAddress {
+        s.parse().unwrap()
+    }
+
+    fn error(s: &str) -> String {
+        s.parse::<ChronyAddress>().unwrap_err().msg().to_string()
+    }
+
+    #[test]
+    fn host_only() {
+        assert_eq!(parse("foo.example.net").host(), "foo.example.net");
+        assert_eq!(parse("10.0.0.1").host(), "10.0.0.1");
+        assert_eq!(parse("2001:db8::1").host(), "2001:db8::1");
+        assert_eq!(parse("  pool.ntp.org ").host(), "pool.ntp.org");
+    }
+
+    #[test]
+    fn options() {
+        let address = parse(
+            "foo.example.net port 1123 minpoll -2 maxpoll 10 presend 9 maxdelayratio 1.5 maxdelay 0.3 key 25 iburst",
+        );
+        assert_eq!(address.port(), Some(1123));
+        assert_eq!(address.minpoll(), Some(-2));
+        assert_eq!(address.maxpoll(), Some(10));
+        assert_eq!(address.presend(), Some(9));
+        assert_eq!(address.maxdelayratio(), Some(1.5));
+        assert_eq!(address.maxdelay(), Some(0.3));
+        assert_eq!(address.key(), Some(25));
+        assert!(address.iburst());
+    }
+
+    #[test]
+    fn structured_form() {
+        assert_eq!(
+            serde_json::to_value(parse("10.0.0.1 iburst maxpoll 8")).unwrap(),
+            json!({"host": "10.0.0.1", "maxpoll": 8, "iburst": true})
+        );
+        assert_eq!(
+            serde_json::to_value(parse("10.0.0.1")).unwrap(),
+            json!({"host": "10.0.0.1"})
+        );
+    }
+
+    #[test]
+    fn display() {
+        assert_eq!(
+            parse("foo iburst  key 1 minpoll 4").to_string(),
+            "foo minpoll 4 key 1 iburst"
+        );
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(error(""), "missing host");
+        assert_eq!(error("foo$@"), "invalid host 'foo$@'");
+        assert_eq!(error("foo prefer"), "unknown option 'prefer'");
+        assert_eq!(error("foo iburst iburst"), "duplicate option 'iburst'");
+        assert_eq!(error("foo minpoll"), "missing value of the 'minpoll' option");
+        assert_eq!(
+            error("foo minpoll 25"),
+            "invalid value of the 'minpoll' option: '25', expected an integer between -6 and 24"
+        );
+        assert_eq!(
+            error("foo maxpoll -7"),
+            "invalid value of the 'maxpoll' option: '-7', expected an integer between -6 and 24"
+        );
+        assert_eq!(error("foo port 0"), "invalid value of the 'port' option: '0'");
+        assert_eq!(
+            error("foo maxdelay -1"),
+            "invalid value of the 'maxdelay' option: '-1', expected a positive number"
+        );
+        assert_eq!(
+            error("foo key 0"),
+            "invalid value of the 'key' option: '0', expected a positive integer"
+        );
+        assert_eq!(
+            error("foo minpoll 10 maxpoll 6"),
+            "minpoll (10) must not be greater than maxpoll (6)"
+        );
+    }
+}
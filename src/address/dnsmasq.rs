@@ -0,0 +1,312 @@
+// https://github.com/balena-os/meta-balena/blob/v2.29.2/meta-resin-common/recipes-connectivity/resin-net-config/resin-net-config/resin-net-config#L34-L39
+//
+// dnsmasq server address (`dnsmasq --server`):
+//
+//   [/[<domain>]/[domain/]][<ipaddr>[#<port>][@<source-ip>|<interface>[#<port>]]]
+//
+// * `/example.com/10.0.0.1` - queries for the domain are forwarded to the server
+// * `//10.0.0.1` - empty domain matches unqualified names
+// * `/#/10.0.0.1` - `#` domain matches any domain
+// * `/example.com/` - queries for the domain are answered from local data only
+// * `/example.com/#` - queries for the domain are forwarded to the standard servers
+// * `10.0.0.1@10.0.0.2#1025@eth0` - source address, port & interface of the queries
+use std::fmt;
+use std::net::IpAddr;
+use std::str::FromStr;
+
+use crate::{
+    address::{is_domain, parse_port},
+    error::Error,
+};
+
+const MAX_INTERFACE_NAME_LENGTH: usize = 15;
+
+/// Where the queries are sent to
+#[derive(Debug, Clone, PartialEq)]
+pub enum DnsmasqTarget {
+    /// Queries are forwarded to the name server
+    Server(IpAddr),
+    /// Queries for the domains are answered from local data only (`/example.com/`)
+    Local,
+    /// Queries for the domains are forwarded to the standard servers (`/example.com/#`)
+    Default,
+}
+
+/// Source of the queries sent to the name server (`@10.0.0.2#1025@eth0`)
+#[derive(Debug, Clone, PartialEq)]
+pub struct DnsmasqSource {
+    address: Option<IpAddr>,
+    port: Option<u16>,
+    interface: Option<String>,
+}
+
+impl DnsmasqSource {
+    pub fn address(&self) -> Option<&IpAddr> {
+        self.address.as_ref()
+    }
+
+    pub fn port(&self) -> Option<u16> {
+        self.port
+    }
+
+    pub fn interface(&self) -> Option<&str> {
+        self.interface.as_deref()
+    }
+}
+
+/// Parsed `dnsmasq-address` value
+#[derive(Debug, Clone, PartialEq)]
+pub struct DnsmasqAddress {
+    domains: Option<Vec<String>>,
+    target: DnsmasqTarget,
+    port: Option<u16>,
+    source: Option<DnsmasqSource>,
+}
+
+impl DnsmasqAddress {
+    /// Domains the server is used for, `None` if it's used for all queries
+    ///
+    /// Empty domain matches unqualified names and `#` matches any domain.
+    pub fn domains(&self) -> Option<&[String]> {
+        self.domains.as_deref()
+    }
+
+    pub fn target(&self) -> &DnsmasqTarget {
+        &self.target
+    }
+
+    /// Name server address, `None` for the local & default targets
+    pub fn address(&self) -> Option<&IpAddr> {
+        match &self.target {
+            DnsmasqTarget::Server(address) => Some(address),
+            _ => None,
+        }
+    }
+
+    /// Name server port
+    pub fn port(&self) -> Option<u16> {
+        self.port
+    }
+
+    pub fn source(&self) -> Option<&DnsmasqSource> {
+        self.source.as_ref()
+    }
+}
+
+fn parse_domains(s: &str) -> Result<Vec<String>, Error> {
+    s.split('/')
+        .map(|domain| {
+            if domain.is_empty() || domain == "#" || is_domain(domain) {
+                Ok(domain.to_string())
+            } else {
+                Err(Error::message(format!("invalid domain '{}'", domain)))
+            }
+        })
+        .collect()
+}
+
+// `<source-ip>[#<port>][@<interface>]` or `<interface>`
+fn parse_source(s: &str) -> Result<DnsmasqSource, Error> {
+    let (address, interface) = match s.find('@') {
+        Some(index) => (&s[..index], Some(&s[index + 1..])),
+        None => (s, None),
+    };
+
+    let (address, port) = match address.find('#') {
+        Some(index) => (&address[..index], Some(parse_port(&address[index + 1..])?)),
+        None => (address, None),
+    };
+
+    let (address, interface) = match (address.parse::<IpAddr>(), interface) {
+        (Ok(ip), interface) => (Some(ip), interface),
+        (Err(_), None) if port.is_none() => (None, Some(address)),
+        (Err(_), _) => return Err(Error::message(format!("invalid source address '{}'", address))),
+    };
+
+    if let Some(interface) = interface {
+        let is_valid = !interface.is_empty()
+            && interface.len() <= MAX_INTERFACE_NAME_LENGTH
+            && !interface.contains(|c: char| c == '/' || c == '#' || c == '@' || c.is_whitespace());
+
+        if !is_valid {
+            return Err(Error::message(format!("invalid interface name '{}'", interface)));
+        }
+    }
+
+    Ok(DnsmasqSource {
+        address,
+        port,
+        interface: interface.map(str::to_string),
+    })
+}
+
+impl FromStr for DnsmasqAddress {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<DnsmasqAddress, Error> {
+        let (domains, rest) = match s.strip_prefix('/') {
+            Some(rest) => {
+                let end = rest
+                    .rfind('/')
+                    .ok_or_else(|| Error::message("missing '/' after the domains"))?;
+                (Some(parse_domains(&rest[..end])?), &rest[end + 1..])
+            }
+            None => (None, s),
+        };
+
+        let (server, source) = match rest.find('@') {
+            Some(index) => (&rest[..index], Some(parse_source(&rest[index + 1..])?)),
+            None => (rest, None),
+        };
+
+        let (address, port) = match server.find('#') {
+            Some(0) if server == "#" => (server, None),
+            Some(index) => (&server[..index], Some(parse_port(&server[index + 1..])?)),
+            None => (server, None),
+        };
+
+        let target = match address {
+            "" if domains.is_some() => DnsmasqTarget::Local,
+            "#" if domains.is_some() => DnsmasqTarget::Default,
+            "" => return Err(Error::message("missing server address")),
+            _ => DnsmasqTarget::Server(
+                address
+                    .parse()
+                    .map_err(|_| Error::message(format!("invalid server address '{}'", address)))?,
+            ),
+        };
+
+        if (port.is_some() || source.is_some()) && !matches!(target, DnsmasqTarget::Server(_)) {
+            return Err(Error::message("port & source require a server address"));
+        }
+
+        Ok(DnsmasqAddress {
+            domains,
+            target,
+            port,
+            source,
+        })
+    }
+}
+
+impl fmt::Display for DnsmasqAddress {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(domains) = &self.domains {
+            write!(f, "/{}/", domains.join("/"))?;
+        }
+
+        match &self.target {
+            DnsmasqTarget::Server(address) => write!(f, "{}", address)?,
+            DnsmasqTarget::Local => {}
+            DnsmasqTarget::Default => write!(f, "#")?,
+        };
+
+        if let Some(port) = self.port {
+            write!(f, "#{}", port)?;
+        }
+
+        if let Some(source) = &self.source {
+            write!(f, "@")?;
+            if let Some(address) = source.address {
+                write!(f, "{}", address)?;
+                if let Some(port) = source.port {
+                    write!(f, "#{}", port)?;
+                }
+                if source.interface.is_some() {
+                    write!(f, "@")?;
+                }
+            }
+            if let Some(interface) = &source.interface {
+                write!(f, "{}", interface)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(s: &str) -> DnsmasqAddress {
+        s.parse().unwrap()
+    }
+
+    fn error(s: &str) -> String {
+        s.parse::<DnsmasqAddress>().unwrap_err().msg().to_string()
+    }
+
+    #[test]
+    fn plain_address() {
+        let address = parse("10.0.0.1");
+        assert_eq!(address.domains(), None);
+        assert_eq!(address.address(), Some(&"10.0.0.1".parse().unwrap()));
+        assert_eq!(address.port(), None);
+        assert_eq!(address.source(), None);
+
+        let address = parse("2001:db8::1#5353");
+        assert_eq!(address.address(), Some(&"2001:db8::1".parse().unwrap()));
+        assert_eq!(address.port(), Some(5353));
+    }
+
+    #[test]
+    fn domains() {
+        let address = parse("/example.com/_ldap._tcp.local//#/10.0.0.1");
+        assert_eq!(
+            address.domains().unwrap(),
+            &["example.com", "_ldap._tcp.local", "", "#"]
+        );
+
+        let address = parse("/example.com/");
+        assert_eq!(address.target(), &DnsmasqTarget::Local);
+        assert_eq!(address.address(), None);
+
+        let address = parse("/example.com/#");
+        assert_eq!(address.target(), &DnsmasqTarget::Default);
+    }
+
+    #[test]
+    fn source() {
+        let source = parse("10.0.0.1@eth0").source().cloned().unwrap();
+        assert_eq!(source.address(), None);
+        assert_eq!(source.interface(), Some("eth0"));
+
+        let source = parse("10.0.0.1@10.0.0.2#1025@wlan0").source().cloned().unwrap();
+        assert_eq!(source.address(), Some(&"10.0.0.2".parse().unwrap()));
+        assert_eq!(source.port(), Some(1025));
+        assert_eq!(source.interface(), Some("wlan0"));
+    }
+
+    #[test]
+    fn display() {
+        for s in &[
+            "10.0.0.1",
+            "/example.com//10.0.0.1#53@10.0.0.2#1025@eth0",
+            "/example.com/",
+            "/#/#",
+            "::1@eth0",
+        ] {
+            assert_eq!(&parse(s).to_string(), s);
+        }
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(error(""), "missing server address");
+        assert_eq!(error("foo.bar.com"), "invalid server address 'foo.bar.com'");
+        assert_eq!(error("/example.com"), "missing '/' after the domains");
+        assert_eq!(error("/exa$mple.com/10.0.0.1"), "invalid domain 'exa$mple.com'");
+        assert_eq!(error("10.0.0.1#0"), "invalid port '0'");
+        assert_eq!(error("10.0.0.1#foo"), "invalid port 'foo'");
+        assert_eq!(error("10.0.0.1@foo#53"), "invalid source address 'foo'");
+        assert_eq!(error("10.0.0.1@10.0.0.2@"), "invalid interface name ''");
+        assert_eq!(
+            error("10.0.0.1@a-very-long-interface"),
+            "invalid interface name 'a-very-long-interface'"
+        );
+        assert_eq!(error("/example.com/#53"), "port & source require a server address");
+        assert_eq!(error("#"), "invalid server address '#'");
+        assert_eq!(error("/example.com/@eth0"), "port & source require a server address");
+    }
+}
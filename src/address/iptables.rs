@@ -0,0 +1,284 @@
+// https://github.com/balena-os/meta-balena/blob/v2.29.2/meta-resin-common/recipes-connectivity/resin-proxy-config/resin-proxy-config/resin-proxy-config#L66-L73
+//
+//   -d, --destination address[/mask][,...]
+//          Destination specification.  See the description of the -s (source) flag for a detailed description
+//          of the syntax.  The flag --dst is an alias for this option.
+//
+// iptables address is `[!] address[/mask][,address[/mask]...]`:
+//
+// * address is an IPv4 or IPv6 address, all addresses must be of the same family
+// * mask is a prefix length (`/8`) or a netmask (`/255.0.0.0`, `/ffff:ffff::`)
+// * `!` negates the match, it's not allowed with multiple addresses
+use std::fmt;
+use std::net::IpAddr;
+use std::str::FromStr;
+
+use crate::error::Error;
+
+/// Single address with the optional mask
+#[derive(Debug, Clone, PartialEq)]
+pub struct IptablesNetwork {
+    address: IpAddr,
+    prefix: Option<u8>,
+}
+
+// Address as an integer & the number of bits
+fn bits(address: &IpAddr) -> (u128, u8) {
+    match address {
+        IpAddr::V4(address) => (u128::from(u32::from(*address)), 32),
+        IpAddr::V6(address) => (u128::from(*address), 128),
+    }
+}
+
+// Mask with `prefix` leading ones in the `width` bits
+fn mask(prefix: u8, width: u8) -> u128 {
+    if prefix == 0 {
+        0
+    } else {
+        (u128::MAX << (128 - u32::from(prefix))) >> (128 - u32::from(width))
+    }
+}
+
+impl IptablesNetwork {
+    pub fn address(&self) -> &IpAddr {
+        &self.address
+    }
+
+    /// Prefix length, `None` if the mask wasn't provided
+    pub fn prefix(&self) -> Option<u8> {
+        self.prefix
+    }
+
+    /// Prefix length, the whole address length if the mask wasn't provided
+    pub fn prefix_len(&self) -> u8 {
+        self.prefix.unwrap_or_else(|| bits(&self.address).1)
+    }
+
+    /// `true` if the address has bits set outside of the mask (`10.0.0.1/8`)
+    pub fn has_host_bits(&self) -> bool {
+        let (address, width) = bits(&self.address);
+        address & !mask(self.prefix_len(), width) & mask(width, width) != 0
+    }
+}
+
+impl FromStr for IptablesNetwork {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<IptablesNetwork, Error> {
+        let (address, netmask) = match s.find('/') {
+            Some(index) => (&s[..index], Some(&s[index + 1..])),
+            None => (s, None),
+        };
+
+        if address.is_empty() {
+            return Err(Error::message("missing address"));
+        }
+
+        let address: IpAddr = address
+            .parse()
+            .map_err(|_| Error::message(format!("invalid address '{}'", address)))?;
+        let width = bits(&address).1;
+
+        let prefix = match netmask {
+            None => None,
+            Some(netmask) if !netmask.is_empty() && netmask.chars().all(|c| c.is_ascii_digit()) => {
+                match netmask.parse::<u8>() {
+                    Ok(prefix) if prefix <= width => Some(prefix),
+                    _ => {
+                        return Err(Error::message(format!(
+                            "invalid prefix length '{}', expected 0-{}",
+                            netmask, width
+                        )));
+                    }
+                }
+            }
+            Some(netmask) => {
+                let (value, mask_width) = netmask
+                    .parse::<IpAddr>()
+                    .map(|x| bits(&x))
+                    .map_err(|_| Error::message(format!("invalid netmask '{}'", netmask)))?;
+
+                if mask_width != width {
+                    return Err(Error::message(format!(
+                        "netmask '{}' does not match the address family",
+                        netmask
+                    )));
+                }
+
+                let prefix = (value << (128 - u32::from(width))).leading_ones() as u8;
+                if mask(prefix, width) != value {
+                    return Err(Error::message(format!("non-contiguous netmask '{}'", netmask)));
+                }
+                Some(prefix)
+            }
+        };
+
+        Ok(IptablesNetwork { address, prefix })
+    }
+}
+
+impl fmt::Display for IptablesNetwork {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.prefix {
+            Some(prefix) => write!(f, "{}/{}", self.address, prefix),
+            None => write!(f, "{}", self.address),
+        }
+    }
+}
+
+/// Parsed `iptables-address` value
+#[derive(Debug, Clone, PartialEq)]
+pub struct IptablesAddress {
+    negated: bool,
+    networks: Vec<IptablesNetwork>,
+}
+
+impl IptablesAddress {
+    /// `true` if the match is negated (`! 10.0.0.0/8`)
+    pub fn is_negated(&self) -> bool {
+        self.negated
+    }
+
+    pub fn networks(&self) -> &[IptablesNetwork] {
+        &self.networks
+    }
+
+    /// Parses the address and rejects addresses with host bits set (`10.0.0.1/8`)
+    pub fn parse_strict(s: &str) -> Result<IptablesAddress, Error> {
+        let address: IptablesAddress = s.parse()?;
+
+        if let Some(network) = address.networks.iter().find(|x| x.has_host_bits()) {
+            return Err(Error::message(format!("host bits set in '{}'", network)));
+        }
+
+        Ok(address)
+    }
+}
+
+impl FromStr for IptablesAddress {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<IptablesAddress, Error> {
+        let (negated, list) = match s.strip_prefix('!') {
+            Some(list) => (true, list.trim_start()),
+            None => (false, s),
+        };
+
+        let networks = list
+            .split(',')
+            .map(str::parse)
+            .collect::<Result<Vec<IptablesNetwork>, Error>>()?;
+
+        if negated && networks.len() > 1 {
+            return Err(Error::message("negation is not allowed with multiple addresses"));
+        }
+
+        if networks
+            .windows(2)
+            .any(|x| x[0].address.is_ipv4() != x[1].address.is_ipv4())
+        {
+            return Err(Error::message("mixed IPv4 and IPv6 addresses"));
+        }
+
+        Ok(IptablesAddress { negated, networks })
+    }
+}
+
+impl fmt::Display for IptablesAddress {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.negated {
+            write!(f, "! ")?;
+        }
+
+        for (index, network) in self.networks.iter().enumerate() {
+            if index > 0 {
+                write!(f, ",")?;
+            }
+            write!(f, "{}", network)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(s: &str) -> IptablesAddress {
+        s.parse().unwrap()
+    }
+
+    fn error(s: &str) -> String {
+        s.parse::<IptablesAddress>().unwrap_err().msg().to_string()
+    }
+
+    #[test]
+    fn masks() {
+        let network = parse("10.0.0.0/8").networks()[0].clone();
+        assert_eq!(network.address(), &"10.0.0.0".parse::<IpAddr>().unwrap());
+        assert_eq!(network.prefix(), Some(8));
+
+        assert_eq!(parse("10.0.0.0/255.255.0.0").networks()[0].prefix(), Some(16));
+        assert_eq!(parse("0.0.0.0/0.0.0.0").networks()[0].prefix(), Some(0));
+        assert_eq!(parse("2001:db8::/32").networks()[0].prefix(), Some(32));
+        assert_eq!(parse("2001:db8::/ffff:ffff::").networks()[0].prefix(), Some(32));
+        assert_eq!(parse("::1/128").networks()[0].prefix(), Some(128));
+
+        let network = parse("10.0.0.1").networks()[0].clone();
+        assert_eq!(network.prefix(), None);
+        assert_eq!(network.prefix_len(), 32);
+    }
+
+    #[test]
+    fn host_bits() {
+        assert!(!parse("10.0.0.0/8").networks()[0].has_host_bits());
+        assert!(parse("10.0.0.1/8").networks()[0].has_host_bits());
+        assert!(!parse("10.0.0.1").networks()[0].has_host_bits());
+        assert!(parse("10.0.0.1/0.0.0.0").networks()[0].has_host_bits());
+        assert!(parse("2001:db8::1/64").networks()[0].has_host_bits());
+        assert!(!parse("2001:db8::/64").networks()[0].has_host_bits());
+
+        assert!(IptablesAddress::parse_strict("10.0.0.0/8,192.168.0.0/16").is_ok());
+        assert_eq!(
+            IptablesAddress::parse_strict("10.0.0.0/8,192.168.1.1/16")
+                .unwrap_err()
+                .msg(),
+            "host bits set in '192.168.1.1/16'"
+        );
+    }
+
+    #[test]
+    fn lists_and_negation() {
+        let address = parse("10.0.0.0/8,192.168.0.0/255.255.0.0");
+        assert!(!address.is_negated());
+        assert_eq!(address.networks().len(), 2);
+        assert_eq!(address.to_string(), "10.0.0.0/8,192.168.0.0/16");
+
+        let address = parse("! 10.0.0.0/8");
+        assert!(address.is_negated());
+        assert_eq!(address.to_string(), "! 10.0.0.0/8");
+        assert!(parse("!10.0.0.1").is_negated());
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(error(""), "missing address");
+        assert_eq!(error("10.0.0.1,"), "missing address");
+        assert_eq!(error("foo"), "invalid address 'foo'");
+        assert_eq!(error("10.0.0.0/33"), "invalid prefix length '33', expected 0-32");
+        assert_eq!(error("::/129"), "invalid prefix length '129', expected 0-128");
+        assert_eq!(error("10.0.0.0/"), "invalid netmask ''");
+        assert_eq!(error("10.0.0.0/foo"), "invalid netmask 'foo'");
+        assert_eq!(error("10.0.0.0/255.0.255.0"), "non-contiguous netmask '255.0.255.0'");
+        assert_eq!(
+            error("10.0.0.0/ffff::"),
+            "netmask 'ffff::' does not match the address family"
+        );
+        assert_eq!(
+            error("! 10.0.0.1,10.0.0.2"),
+            "negation is not allowed with multiple addresses"
+        );
+        assert_eq!(error("10.0.0.1,::1"), "mixed IPv4 and IPv6 addresses");
+    }
+}
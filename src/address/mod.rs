@@ -0,0 +1,36 @@
+//! A module containing parsers of the network service address types.
+//!
+//! The `*-address` types are passed to the system services as they are. The parsers
+//! check the whole service syntax and expose the parsed components, the structured forms
+//! can be serialized for the mapping targets.
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use crate::error::Error;
+
+pub use chrony::{ChronyAddress, MAX_POLL, MIN_POLL};
+pub use dnsmasq::{DnsmasqAddress, DnsmasqSource, DnsmasqTarget};
+pub use iptables::{IptablesAddress, IptablesNetwork};
+
+mod chrony;
+mod dnsmasq;
+mod iptables;
+
+lazy_static! {
+    // Hostname labels, underscores are allowed, because they're used in service names (`_ldap._tcp`)
+    static ref DOMAIN_REGEX: Regex =
+        Regex::new(r"^(?i)[a-z0-9_](?:[a-z0-9_-]{0,61}[a-z0-9_])?(?:\.[a-z0-9_](?:[a-z0-9_-]{0,61}[a-z0-9_])?)*$")
+            .unwrap();
+}
+
+fn is_domain(s: &str) -> bool {
+    s.len() <= 253 && DOMAIN_REGEX.is_match(s)
+}
+
+// Port number, `0` is not a valid port
+fn parse_port(s: &str) -> Result<u16, Error> {
+    match s.parse::<u16>() {
+        Ok(port) if port > 0 => Ok(port),
+        _ => Err(Error::message(format!("invalid port '{}'", s))),
+    }
+}
@@ -0,0 +1,331 @@
+//! Command-line tool for schema authors
+//!
+//! Run `jellyschema --help` for usage.
+use std::{
+    env, fs,
+    io::{self, Read, Write},
+    path::Path,
+    process,
+};
+
+use serde_json::{json, Value};
+
+use jellyschema::{
+    error::Error,
+    filler::fill_default_values,
+    generator::generate_json_ui_schema,
+    lint::{lint, Severity},
+    mapper::{remove_targets, render_updated_targets, stale_targets, write_targets, DirectoryFileSystem},
+    schema::{
+        loader::{load_schema, FileResolver},
+        Schema,
+    },
+    validator::{validate, ValidationError},
+};
+
+const USAGE: &str = "Usage: jellyschema [--format human|json] <command> [arguments]
+
+Commands:
+  generate <schema>                           Prints the JSON Schema & UI Schema
+  validate <schema> <data>                    Validates the data against the schema
+  fill <schema> [data] [--include-optional]   Fills missing default values
+  lint <schema> [--deny-warnings]             Reports schema mistakes
+  render <schema> <data> <directory>          Renders mapping targets into the directory,
+                                              every partition is a subdirectory
+
+Data can be JSON or YAML, `-` reads them from the standard input. `generate` and `fill`
+always print JSON, `--format` changes the output of the other commands and of errors.
+
+Exit codes:
+  0  success
+  1  data are not valid, schema has lint errors (warnings with --deny-warnings)
+  2  invalid arguments, schema or data can't be read or parsed";
+
+// Broken pipe (`jellyschema generate schema.yaml | head`) is not an error
+macro_rules! out {
+    ($($arg:tt)*) => {
+        let _ = writeln!(io::stdout(), $($arg)*);
+    };
+}
+
+const EXIT_SUCCESS: i32 = 0;
+const EXIT_FAILURE: i32 = 1;
+const EXIT_ERROR: i32 = 2;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Format {
+    Human,
+    Json,
+}
+
+struct Arguments {
+    format: Format,
+    command: String,
+    positional: Vec<String>,
+    flags: Vec<String>,
+}
+
+impl Arguments {
+    fn parse<I>(args: I) -> Result<Arguments, Error>
+    where
+        I: IntoIterator<Item = String>,
+    {
+        let mut args = args.into_iter();
+        let mut format = Format::Human;
+        let mut command = None;
+        let mut positional = vec![];
+        let mut flags = vec![];
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--format" => {
+                    format = match args.next().as_deref() {
+                        Some("human") => Format::Human,
+                        Some("json") => Format::Json,
+                        _ => return Err(Error::message("--format must be 'human' or 'json'")),
+                    }
+                }
+                "-" => positional.push(arg),
+                x if x.starts_with('-') => flags.push(arg),
+                _ if command.is_none() => command = Some(arg),
+                _ => positional.push(arg),
+            };
+        }
+
+        Ok(Arguments {
+            format,
+            command: command.ok_or_else(|| Error::message("missing command"))?,
+            positional,
+            flags,
+        })
+    }
+
+    fn has_flag(&self, flag: &str) -> bool {
+        self.flags.iter().any(|x| x == flag)
+    }
+
+    // Checks the number of positional arguments and flags
+    fn expect(&self, required: usize, optional: usize, flags: &[&str]) -> Result<(), Error> {
+        if let Some(flag) = self.flags.iter().find(|x| !flags.contains(&x.as_str())) {
+            return Err(Error::message(format!("unknown option '{}'", flag)));
+        }
+
+        if self.positional.len() < required || self.positional.len() > required + optional {
+            return Err(Error::message(format!(
+                "invalid number of arguments for '{}'",
+                self.command
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+fn load(path: &str) -> Result<Schema, Error> {
+    let path = Path::new(path);
+    let directory = path.parent().unwrap_or_else(|| Path::new(""));
+    let name = path
+        .file_name()
+        .ok_or_else(|| Error::message(format!("invalid schema file name '{}'", path.display())))?;
+
+    load_schema(&FileResolver::new(directory), &name.to_string_lossy())
+}
+
+fn read_data(path: &str) -> Result<Value, Error> {
+    let content = if path == "-" {
+        let mut content = String::new();
+        io::stdin()
+            .read_to_string(&mut content)
+            .map_err(|e| Error::message(format!("unable to read standard input: {}", e)))?;
+        content
+    } else {
+        fs::read_to_string(path).map_err(|e| Error::message(format!("unable to read '{}': {}", path, e)))?
+    };
+
+    serde_yaml::from_str(&content).map_err(|e| Error::message(format!("unable to parse data '{}': {}", path, e)))
+}
+
+fn print_json(value: &Value) {
+    out!(
+        "{}",
+        serde_json::to_string_pretty(value).expect("unable to serialize JSON")
+    );
+}
+
+fn print_validation_errors(format: Format, errors: &[ValidationError]) {
+    match format {
+        Format::Human => {
+            for error in errors {
+                let data_path = if error.data_path().is_empty() {
+                    "(root)"
+                } else {
+                    error.data_path()
+                };
+                out!("{}: {} (keyword: {})", data_path, error.message(), error.keyword());
+            }
+        }
+        Format::Json => print_json(&json!({ "valid": errors.is_empty(), "errors": errors })),
+    };
+}
+
+// Errors go to the standard error output, JSON errors are objects with the `error` key
+fn print_error(format: Format, error: &Error) {
+    match format {
+        Format::Human => eprintln!("error: {:#}", error),
+        Format::Json => eprintln!(
+            "{}",
+            json!({
+                "error": {
+                    "message": error.msg(),
+                    "file": error.file(),
+                    "line": error.line(),
+                    "column": error.column(),
+                    "path": error.path(),
+                }
+            })
+        ),
+    };
+}
+
+fn generate(args: &Arguments) -> Result<i32, Error> {
+    args.expect(1, 0, &[])?;
+    let schema = load(&args.positional[0])?;
+
+    let (json_schema, ui_schema) = generate_json_ui_schema(&schema);
+    print_json(&json!({ "jsonSchema": json_schema, "uiSchema": ui_schema }));
+    Ok(EXIT_SUCCESS)
+}
+
+fn validate_data(args: &Arguments) -> Result<i32, Error> {
+    args.expect(2, 0, &[])?;
+    let schema = load(&args.positional[0])?;
+    let data = read_data(&args.positional[1])?;
+
+    let state = validate(&schema, &data);
+    if state.is_valid() && args.format == Format::Human {
+        out!("valid");
+    } else {
+        print_validation_errors(args.format, state.errors());
+    }
+
+    Ok(if state.is_valid() { EXIT_SUCCESS } else { EXIT_FAILURE })
+}
+
+fn fill(args: &Arguments) -> Result<i32, Error> {
+    args.expect(1, 1, &["--include-optional"])?;
+    let schema = load(&args.positional[0])?;
+    let mut data = match args.positional.get(1) {
+        Some(path) => read_data(path)?,
+        None => Value::Null,
+    };
+
+    fill_default_values(&schema, &mut data, args.has_flag("--include-optional"));
+    print_json(&data);
+    Ok(EXIT_SUCCESS)
+}
+
+fn lint_schema(args: &Arguments) -> Result<i32, Error> {
+    args.expect(1, 0, &["--deny-warnings"])?;
+    let schema = load(&args.positional[0])?;
+
+    let diagnostics = lint(&schema);
+    match args.format {
+        Format::Human => {
+            for diagnostic in &diagnostics {
+                out!("{}", diagnostic);
+            }
+        }
+        Format::Json => print_json(&json!(diagnostics)),
+    };
+
+    let threshold = if args.has_flag("--deny-warnings") {
+        Severity::Warning
+    } else {
+        Severity::Error
+    };
+
+    if diagnostics.iter().any(|x| x.severity() >= threshold) {
+        Ok(EXIT_FAILURE)
+    } else {
+        Ok(EXIT_SUCCESS)
+    }
+}
+
+fn render(args: &Arguments) -> Result<i32, Error> {
+    args.expect(3, 0, &[])?;
+    let schema = load(&args.positional[0])?;
+    let data = read_data(&args.positional[1])?;
+
+    let state = validate(&schema, &data);
+    if !state.is_valid() {
+        print_validation_errors(args.format, state.errors());
+        return Ok(EXIT_FAILURE);
+    }
+
+    let mut fs = DirectoryFileSystem::new(&args.positional[2]);
+    let targets = render_updated_targets(&schema, &data, &fs)?;
+    let stale = stale_targets(&schema, &targets, &fs)?;
+    write_targets(&mut fs, &targets)?;
+    remove_targets(&mut fs, &stale)?;
+
+    let written = targets
+        .iter()
+        .map(|x| fs.path(x.location()).map(|x| x.display().to_string()))
+        .collect::<Result<Vec<String>, Error>>()?;
+    let removed = stale
+        .iter()
+        .map(|x| fs.path(x).map(|x| x.display().to_string()))
+        .collect::<Result<Vec<String>, Error>>()?;
+
+    match args.format {
+        Format::Human => {
+            for path in &written {
+                out!("written {}", path);
+            }
+            for path in &removed {
+                out!("removed {}", path);
+            }
+        }
+        Format::Json => print_json(&json!({ "written": written, "removed": removed })),
+    };
+
+    Ok(EXIT_SUCCESS)
+}
+
+fn run(args: &Arguments) -> Result<i32, Error> {
+    match args.command.as_str() {
+        "generate" => generate(args),
+        "validate" => validate_data(args),
+        "fill" => fill(args),
+        "lint" => lint_schema(args),
+        "render" => render(args),
+        command => Err(Error::message(format!("unknown command '{}'", command))),
+    }
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    if args.iter().any(|x| x == "--help" || x == "-h") {
+        out!("{}", USAGE);
+        process::exit(EXIT_SUCCESS);
+    }
+
+    let code = match Arguments::parse(args.iter().cloned()) {
+        Ok(args) => run(&args).unwrap_or_else(|e| {
+            print_error(args.format, &e);
+            EXIT_ERROR
+        }),
+        // Arguments are not parsed, look for the format only
+        Err(e) if args.windows(2).any(|x| x == ["--format", "json"]) => {
+            print_error(Format::Json, &e);
+            EXIT_ERROR
+        }
+        Err(e) => {
+            eprintln!("error: {}\n\n{}", e, USAGE);
+            EXIT_ERROR
+        }
+    };
+
+    process::exit(code);
+}
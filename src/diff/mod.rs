@@ -0,0 +1,845 @@
+//! A module containing the schema diff.
+//!
+//! Compares two versions of the schema and reports changes which can affect existing
+//! data documents or rendered configuration files. Every change is classified as
+//! backwards-compatible (data valid against the old schema are valid against the new
+//! one as well) or breaking.
+//!
+//! # Examples
+//!
+//! ```
+//! use jellyschema::diff::{diff, Compatibility, Kind};
+//! use jellyschema::schema::Schema;
+//!
+//! let old: Schema = r#"
+//!   version: 1
+//!   properties:
+//!     - hostname:
+//!         type: string
+//!         maxLength: 64
+//! "#.parse().unwrap();
+//!
+//! let new: Schema = r#"
+//!   version: 1
+//!   properties:
+//!     - hostname:
+//!         type: string
+//!         maxLength: 32
+//! "#.parse().unwrap();
+//!
+//! let changes = diff(&old, &new);
+//! assert_eq!(changes[0].kind(), Kind::ConstraintTightened);
+//! assert_eq!(changes[0].compatibility(), Compatibility::Breaking);
+//! assert_eq!(changes[0].schema_path(), "properties[0].hostname.maxLength");
+//! ```
+use std::collections::HashSet;
+use std::fmt;
+
+use serde::Serialize;
+use serde_derive::Serialize;
+use serde_json::{Number, Value};
+
+use crate::schema::{mapping::Mapping, PrimitiveType, Property, Schema};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Compatibility {
+    /// Existing data stay valid
+    Compatible,
+    /// Existing data can become invalid (or rendered files can change)
+    Breaking,
+}
+
+impl AsRef<str> for Compatibility {
+    fn as_ref(&self) -> &str {
+        match self {
+            Compatibility::Compatible => "compatible",
+            Compatibility::Breaking => "breaking",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Kind {
+    PropertyAdded,
+    PropertyRemoved,
+    /// Property removed and another one with the same schema added
+    PropertyRenamed,
+    TypeChanged,
+    /// Optional type changed to the required one
+    BecameRequired,
+    /// Required type changed to the optional one
+    BecameOptional,
+    /// Constraint added or narrowed (`min` increased, `enum` value removed, ...)
+    ConstraintTightened,
+    /// Constraint removed or widened (`maxLength` increased, `enum` value added, ...)
+    ConstraintRelaxed,
+    MappingTargetAdded,
+    MappingTargetRemoved,
+    MappingTargetChanged,
+    /// Any other `mapping` keyword (`target`, `filename`, ...) changed
+    MappingChanged,
+}
+
+impl AsRef<str> for Kind {
+    fn as_ref(&self) -> &str {
+        match self {
+            Kind::PropertyAdded => "property-added",
+            Kind::PropertyRemoved => "property-removed",
+            Kind::PropertyRenamed => "property-renamed",
+            Kind::TypeChanged => "type-changed",
+            Kind::BecameRequired => "became-required",
+            Kind::BecameOptional => "became-optional",
+            Kind::ConstraintTightened => "constraint-tightened",
+            Kind::ConstraintRelaxed => "constraint-relaxed",
+            Kind::MappingTargetAdded => "mapping-target-added",
+            Kind::MappingTargetRemoved => "mapping-target-removed",
+            Kind::MappingTargetChanged => "mapping-target-changed",
+            Kind::MappingChanged => "mapping-changed",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Change {
+    compatibility: Compatibility,
+    kind: Kind,
+    #[serde(rename = "schemaPath")]
+    schema_path: String,
+    message: String,
+}
+
+impl Change {
+    pub fn compatibility(&self) -> Compatibility {
+        self.compatibility
+    }
+
+    pub fn kind(&self) -> Kind {
+        self.kind
+    }
+
+    /// Path in the new schema (in the old one for removed values)
+    pub fn schema_path(&self) -> &str {
+        &self.schema_path
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    pub fn is_breaking(&self) -> bool {
+        self.compatibility == Compatibility::Breaking
+    }
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}[{}]: schema path: '{}', message: '{}'",
+            self.compatibility.as_ref(),
+            self.kind.as_ref(),
+            self.schema_path,
+            self.message
+        )
+    }
+}
+
+fn join(path: &str, component: &str) -> String {
+    if path.is_empty() {
+        component.to_string()
+    } else {
+        format!("{}.{}", path, component)
+    }
+}
+
+fn number(value: Option<&Number>) -> Option<f64> {
+    value.and_then(Number::as_f64)
+}
+
+fn usize_number(value: Option<usize>) -> Option<f64> {
+    value.map(|x| x as f64)
+}
+
+fn json<T: Serialize>(value: &T) -> Value {
+    serde_json::to_value(value).unwrap_or(Value::Null)
+}
+
+// Integer is a subset of the number, anything else is considered as an incompatible change
+fn is_widening(old: &PrimitiveType, new: &PrimitiveType) -> bool {
+    matches!((old, new), (PrimitiveType::Integer, PrimitiveType::Number))
+}
+
+struct Differ<'a> {
+    old_root: &'a Schema,
+    new_root: &'a Schema,
+    // Already compared (old, new) schema pairs, recursive definitions are compared once
+    visited: HashSet<(*const Schema, *const Schema)>,
+    changes: Vec<Change>,
+}
+
+impl<'a> Differ<'a> {
+    fn push<S>(&mut self, compatibility: Compatibility, kind: Kind, schema_path: String, message: S)
+    where
+        S: Into<String>,
+    {
+        self.changes.push(Change {
+            compatibility,
+            kind,
+            schema_path,
+            message: message.into(),
+        });
+    }
+
+    fn tightened<S: Into<String>>(&mut self, schema_path: String, message: S) {
+        self.push(Compatibility::Breaking, Kind::ConstraintTightened, schema_path, message);
+    }
+
+    fn relaxed<S: Into<String>>(&mut self, schema_path: String, message: S) {
+        self.push(Compatibility::Compatible, Kind::ConstraintRelaxed, schema_path, message);
+    }
+
+    fn diff(&mut self, old: &'a Schema, new: &'a Schema, path: &str) {
+        let old = self.old_root.resolve(old);
+        let new = self.new_root.resolve(new);
+
+        if !self.visited.insert((old as *const Schema, new as *const Schema)) {
+            return;
+        }
+
+        self.diff_mapping(old.mapping(), new.mapping(), path);
+
+        if !self.diff_type(old, new, path) {
+            return;
+        }
+
+        self.diff_constraints(old, new, path);
+        self.diff_properties(old, new, path);
+        self.diff_optional_schemas(old.keys(), new.keys(), &join(path, "keys"));
+        self.diff_optional_schemas(old.values(), new.values(), &join(path, "values"));
+
+        match (old.items(), new.items()) {
+            ([old], [new]) => self.diff(old, new, &join(path, "items")),
+            (old, new) => self.diff_schemas(old, new, &join(path, "items"), false),
+        };
+
+        self.diff_schemas(old.one_of(), new.one_of(), &join(path, "oneOf"), true);
+        self.diff_schemas(old.any_of(), new.any_of(), &join(path, "anyOf"), true);
+        self.diff_schemas(old.all_of(), new.all_of(), &join(path, "allOf"), false);
+    }
+
+    // Returns `false` if the type changed and other keywords shouldn't be compared
+    fn diff_type(&mut self, old: &Schema, new: &Schema, path: &str) -> bool {
+        let (old, new) = (old.r#type(), new.r#type());
+        let path = join(path, "type");
+
+        if old.primitive_type() != new.primitive_type() {
+            let message = format!(
+                "type changed from '{}' to '{}'",
+                old.primitive_type(),
+                new.primitive_type()
+            );
+            if !is_widening(old.primitive_type(), new.primitive_type()) {
+                self.push(Compatibility::Breaking, Kind::TypeChanged, path, message);
+                return false;
+            }
+            self.push(Compatibility::Compatible, Kind::TypeChanged, path.clone(), message);
+        }
+
+        if old.is_optional() && new.is_required() {
+            self.push(
+                Compatibility::Breaking,
+                Kind::BecameRequired,
+                path,
+                "value became required",
+            );
+        } else if old.is_required() && new.is_optional() {
+            self.push(
+                Compatibility::Compatible,
+                Kind::BecameOptional,
+                path,
+                "value became optional",
+            );
+        }
+
+        true
+    }
+
+    // Lower bound - greater value is tighter
+    fn diff_lower_bound(&mut self, path: &str, keyword: &str, old: Option<f64>, new: Option<f64>) {
+        let path = join(path, keyword);
+        match (old, new) {
+            (None, Some(new)) => self.tightened(path, format!("{} {} added", keyword, new)),
+            (Some(old), None) => self.relaxed(path, format!("{} {} removed", keyword, old)),
+            (Some(old), Some(new)) if new > old => {
+                self.tightened(path, format!("{} increased from {} to {}", keyword, old, new))
+            }
+            (Some(old), Some(new)) if new < old => {
+                self.relaxed(path, format!("{} decreased from {} to {}", keyword, old, new))
+            }
+            _ => {}
+        };
+    }
+
+    // Upper bound - lower value is tighter
+    fn diff_upper_bound(&mut self, path: &str, keyword: &str, old: Option<f64>, new: Option<f64>) {
+        let path = join(path, keyword);
+        match (old, new) {
+            (None, Some(new)) => self.tightened(path, format!("{} {} added", keyword, new)),
+            (Some(old), None) => self.relaxed(path, format!("{} {} removed", keyword, old)),
+            (Some(old), Some(new)) if new < old => {
+                self.tightened(path, format!("{} decreased from {} to {}", keyword, old, new))
+            }
+            (Some(old), Some(new)) if new > old => {
+                self.relaxed(path, format!("{} increased from {} to {}", keyword, old, new))
+            }
+            _ => {}
+        };
+    }
+
+    // Any change of the exact constraint (`pattern`, `const`, ...) is considered as tighter
+    fn diff_exact(&mut self, path: &str, keyword: &str, old: Option<String>, new: Option<String>) {
+        let path = join(path, keyword);
+        match (old, new) {
+            (None, Some(new)) => self.tightened(path, format!("{} {} added", keyword, new)),
+            (Some(old), None) => self.relaxed(path, format!("{} {} removed", keyword, old)),
+            (Some(old), Some(new)) if old != new => {
+                self.tightened(path, format!("{} changed from {} to {}", keyword, old, new))
+            }
+            _ => {}
+        };
+    }
+
+    fn diff_constraints(&mut self, old: &Schema, new: &Schema, path: &str) {
+        self.diff_lower_bound(path, "min", number(old.min()), number(new.min()));
+        self.diff_lower_bound(
+            path,
+            "exclusiveMin",
+            number(old.exclusive_min()),
+            number(new.exclusive_min()),
+        );
+        self.diff_upper_bound(path, "max", number(old.max()), number(new.max()));
+        self.diff_upper_bound(
+            path,
+            "exclusiveMax",
+            number(old.exclusive_max()),
+            number(new.exclusive_max()),
+        );
+        self.diff_lower_bound(
+            path,
+            "minLength",
+            usize_number(old.min_length()),
+            usize_number(new.min_length()),
+        );
+        self.diff_upper_bound(
+            path,
+            "maxLength",
+            usize_number(old.max_length()),
+            usize_number(new.max_length()),
+        );
+        self.diff_lower_bound(
+            path,
+            "minItems",
+            usize_number(old.min_items()),
+            usize_number(new.min_items()),
+        );
+        self.diff_upper_bound(
+            path,
+            "maxItems",
+            usize_number(old.max_items()),
+            usize_number(new.max_items()),
+        );
+
+        let multiple_of = |schema: &Schema| schema.multiple_of().map(Number::to_string);
+        self.diff_exact(path, "multipleOf", multiple_of(old), multiple_of(new));
+
+        let pattern = |schema: &Schema| schema.pattern().map(|x| format!("'{}'", x.as_str()));
+        self.diff_exact(path, "pattern", pattern(old), pattern(new));
+
+        let r#const = |schema: &Schema| schema.r#const().map(Value::to_string);
+        self.diff_exact(path, "const", r#const(old), r#const(new));
+
+        let unique_items = |schema: &Schema| match schema.unique_items().is_unique() {
+            Some(false) => None,
+            _ => Some(json(schema.unique_items()).to_string()),
+        };
+        self.diff_exact(path, "uniqueItems", unique_items(old), unique_items(new));
+
+        match (old.strict_mask(), new.strict_mask()) {
+            (false, true) => self.tightened(join(path, "strictMask"), "host bits disallowed"),
+            (true, false) => self.relaxed(join(path, "strictMask"), "host bits allowed"),
+            _ => {}
+        };
+
+        self.diff_enum(old, new, path);
+
+        match (old.additional_properties(), new.additional_properties()) {
+            (true, false) => self.tightened(join(path, "additionalProperties"), "additional properties disallowed"),
+            (false, true) => self.relaxed(join(path, "additionalProperties"), "additional properties allowed"),
+            _ => {}
+        };
+    }
+
+    fn diff_enum(&mut self, old: &Schema, new: &Schema, path: &str) {
+        let old: Vec<&Value> = old.r#enum().iter().map(|x| x.value()).collect();
+        let new: Vec<&Value> = new.r#enum().iter().map(|x| x.value()).collect();
+        let path = join(path, "enum");
+        let list = |values: &[&Value]| values.iter().map(|x| x.to_string()).collect::<Vec<_>>().join(", ");
+
+        if old.is_empty() && !new.is_empty() {
+            self.tightened(path, format!("enum added: {}", list(&new)));
+            return;
+        }
+
+        if !old.is_empty() && new.is_empty() {
+            self.relaxed(path, "enum removed");
+            return;
+        }
+
+        let removed: Vec<&Value> = old.iter().filter(|x| !new.contains(x)).cloned().collect();
+        let added: Vec<&Value> = new.iter().filter(|x| !old.contains(x)).cloned().collect();
+
+        if !removed.is_empty() {
+            self.tightened(path.clone(), format!("enum values removed: {}", list(&removed)));
+        }
+        if !added.is_empty() {
+            self.relaxed(path, format!("enum values added: {}", list(&added)));
+        }
+    }
+
+    fn diff_properties(&mut self, old_schema: &'a Schema, new_schema: &'a Schema, path: &str) {
+        let (old, new) = (old_schema.properties(), new_schema.properties());
+        let property_path =
+            |index: usize, property: &Property| join(path, &format!("properties[{}].{}", index, property.name()));
+        let find = |properties: &'a [Property], name: &str| properties.iter().position(|x| x.name() == name);
+
+        let mut removed: Vec<(usize, &Property)> = vec![];
+
+        for (index, property) in old.iter().enumerate() {
+            match find(new, property.name()) {
+                Some(new_index) => self.diff(
+                    property.schema(),
+                    new[new_index].schema(),
+                    &property_path(new_index, &new[new_index]),
+                ),
+                None => removed.push((index, property)),
+            };
+        }
+
+        let mut added: Vec<(usize, &Property)> = new
+            .iter()
+            .enumerate()
+            .filter(|(_, x)| find(old, x.name()).is_none())
+            .collect();
+
+        for (index, property) in removed {
+            let schema = json(self.old_root.resolve(property.schema()));
+            let renamed = added
+                .iter()
+                .position(|(_, x)| json(self.new_root.resolve(x.schema())) == schema);
+
+            match renamed {
+                Some(position) => {
+                    let (new_index, new_property) = added.remove(position);
+                    self.push(
+                        Compatibility::Breaking,
+                        Kind::PropertyRenamed,
+                        property_path(new_index, new_property),
+                        format!("property '{}' renamed to '{}'", property.name(), new_property.name()),
+                    );
+                }
+                None => {
+                    // Removed property value is an additional property now
+                    let compatibility = if new_schema.additional_properties() {
+                        Compatibility::Compatible
+                    } else {
+                        Compatibility::Breaking
+                    };
+                    self.push(
+                        compatibility,
+                        Kind::PropertyRemoved,
+                        property_path(index, property),
+                        format!("property '{}' removed", property.name()),
+                    );
+                }
+            };
+        }
+
+        for (index, property) in added {
+            let schema = self.new_root.resolve(property.schema());
+            let (compatibility, message) = if schema.r#type().is_required() {
+                (
+                    Compatibility::Breaking,
+                    format!("required property '{}' added", property.name()),
+                )
+            } else {
+                (
+                    Compatibility::Compatible,
+                    format!("optional property '{}' added", property.name()),
+                )
+            };
+            self.push(
+                compatibility,
+                Kind::PropertyAdded,
+                property_path(index, property),
+                message,
+            );
+        }
+    }
+
+    fn diff_optional_schemas(&mut self, old: Option<&'a Schema>, new: Option<&'a Schema>, path: &str) {
+        match (old, new) {
+            (Some(old), Some(new)) => self.diff(old, new, path),
+            (None, Some(_)) => self.tightened(path.to_string(), "schema added"),
+            (Some(_), None) => self.relaxed(path.to_string(), "schema removed"),
+            (None, None) => {}
+        };
+    }
+
+    // Schema lists are compared by index, `alternatives` is `true` if added schemas relax
+    // the constraints (`oneOf`, `anyOf`)
+    fn diff_schemas(&mut self, old: &'a [Schema], new: &'a [Schema], path: &str, alternatives: bool) {
+        for (index, (old, new)) in old.iter().zip(new.iter()).enumerate() {
+            self.diff(old, new, &format!("{}[{}]", path, index));
+        }
+
+        for index in new.len()..old.len() {
+            let path = format!("{}[{}]", path, index);
+            if alternatives {
+                self.tightened(path, "schema removed");
+            } else {
+                self.relaxed(path, "schema removed");
+            }
+        }
+
+        for index in old.len()..new.len() {
+            let path = format!("{}[{}]", path, index);
+            if alternatives {
+                self.relaxed(path, "schema added");
+            } else {
+                self.tightened(path, "schema added");
+            }
+        }
+    }
+
+    fn diff_mapping(&mut self, old: Option<&Mapping>, new: Option<&Mapping>, path: &str) {
+        let path = join(path, "mapping");
+        let empty = Mapping::new();
+        let (old, new) = (old.unwrap_or(&empty), new.unwrap_or(&empty));
+
+        let mut names: Vec<&String> = old.targets().keys().chain(new.targets().keys()).collect();
+        names.sort();
+        names.dedup();
+
+        for name in names {
+            let target_path = join(&path, &format!("targets.{}", name));
+            match (old.targets().get(name), new.targets().get(name)) {
+                (None, Some(_)) => self.push(
+                    Compatibility::Compatible,
+                    Kind::MappingTargetAdded,
+                    target_path,
+                    format!("mapping target '{}' added", name),
+                ),
+                (Some(_), None) => self.push(
+                    Compatibility::Breaking,
+                    Kind::MappingTargetRemoved,
+                    target_path,
+                    format!("mapping target '{}' removed", name),
+                ),
+                (Some(old), Some(new)) if json(old) != json(new) => self.push(
+                    Compatibility::Breaking,
+                    Kind::MappingTargetChanged,
+                    target_path,
+                    format!("mapping target '{}' changed", name),
+                ),
+                _ => {}
+            };
+        }
+
+        for (keyword, old, new) in &[
+            ("target", json(&old.target()), json(&new.target())),
+            ("filename", json(&old.filename()), json(&new.filename())),
+            ("path", json(&old.path()), json(&new.path())),
+            ("template", json(&old.template()), json(&new.template())),
+        ] {
+            if old != new {
+                self.push(
+                    Compatibility::Breaking,
+                    Kind::MappingChanged,
+                    join(&path, keyword),
+                    format!("mapping {} changed", keyword),
+                );
+            }
+        }
+    }
+}
+
+/// Compares two versions of the schema
+///
+/// Properties are matched by name, schema lists (`items`, `oneOf`, ...) by index.
+/// References are resolved, definitions are compared where they are used.
+///
+/// # Arguments
+///
+/// * `old` - Released schema
+/// * `new` - Schema to release
+pub fn diff(old: &Schema, new: &Schema) -> Vec<Change> {
+    let mut differ = Differ {
+        old_root: old,
+        new_root: new,
+        visited: HashSet::new(),
+        changes: vec![],
+    };
+    differ.diff(old, new, "");
+    differ.changes
+}
+
+/// Returns `true` if none of the changes is breaking
+pub fn is_compatible(changes: &[Change]) -> bool {
+    !changes.iter().any(Change::is_breaking)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn changes(old: &str, new: &str) -> Vec<(Compatibility, Kind, String)> {
+        let old: Schema = old.parse().unwrap();
+        let new: Schema = new.parse().unwrap();
+        diff(&old, &new)
+            .into_iter()
+            .map(|x| (x.compatibility, x.kind, x.schema_path))
+            .collect()
+    }
+
+    fn change(compatibility: Compatibility, kind: Kind, path: &str) -> (Compatibility, Kind, String) {
+        (compatibility, kind, path.to_string())
+    }
+
+    #[test]
+    fn identical_schemas() {
+        let schema = "properties:\n  - a:\n      type: string\n      enum: [a, b]";
+        assert!(changes(schema, schema).is_empty());
+    }
+
+    #[test]
+    fn added_and_removed_properties() {
+        let old = "properties:\n  - a:\n      type: string\n  - b:\n      type: integer";
+        let new = "properties:\n  - a:\n      type: string\n  - c:\n      type: boolean\n  - d:\n      type: port?";
+        assert_eq!(
+            changes(old, new),
+            vec![
+                change(Compatibility::Breaking, Kind::PropertyRemoved, "properties[1].b"),
+                change(Compatibility::Breaking, Kind::PropertyAdded, "properties[1].c"),
+                change(Compatibility::Compatible, Kind::PropertyAdded, "properties[2].d"),
+            ]
+        );
+
+        let new = "additionalProperties: true\nproperties:\n  - a:\n      type: string";
+        assert!(changes(old, new).contains(&change(
+            Compatibility::Compatible,
+            Kind::PropertyRemoved,
+            "properties[1].b"
+        )));
+    }
+
+    #[test]
+    fn renamed_property() {
+        let old = "properties:\n  - ssid:\n      type: string\n      minLength: 1";
+        let new = "properties:\n  - networkName:\n      type: string\n      minLength: 1";
+        let old_schema: Schema = old.parse().unwrap();
+        let new_schema: Schema = new.parse().unwrap();
+        let changes = diff(&old_schema, &new_schema);
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].kind(), Kind::PropertyRenamed);
+        assert_eq!(changes[0].message(), "property 'ssid' renamed to 'networkName'");
+        assert!(!is_compatible(&changes));
+    }
+
+    #[test]
+    fn tightened_and_relaxed_constraints() {
+        let old = r#"
+            properties:
+              - retries:
+                  type: integer
+                  min: 1
+                  max: 10
+              - name:
+                  type: string?
+                  maxLength: 32
+                  pattern: "^[a-z]+$"
+              - mode:
+                  type: string
+                  enum: [a, b]
+        "#;
+        let new = r#"
+            properties:
+              - retries:
+                  type: number
+                  min: 2
+                  max: 20
+              - name:
+                  type: string
+                  maxLength: 64
+              - mode:
+                  type: string
+                  enum: [b, c]
+        "#;
+        assert_eq!(
+            changes(old, new),
+            vec![
+                change(
+                    Compatibility::Compatible,
+                    Kind::TypeChanged,
+                    "properties[0].retries.type"
+                ),
+                change(
+                    Compatibility::Breaking,
+                    Kind::ConstraintTightened,
+                    "properties[0].retries.min"
+                ),
+                change(
+                    Compatibility::Compatible,
+                    Kind::ConstraintRelaxed,
+                    "properties[0].retries.max"
+                ),
+                change(Compatibility::Breaking, Kind::BecameRequired, "properties[1].name.type"),
+                change(
+                    Compatibility::Compatible,
+                    Kind::ConstraintRelaxed,
+                    "properties[1].name.maxLength"
+                ),
+                change(
+                    Compatibility::Compatible,
+                    Kind::ConstraintRelaxed,
+                    "properties[1].name.pattern"
+                ),
+                change(
+                    Compatibility::Breaking,
+                    Kind::ConstraintTightened,
+                    "properties[2].mode.enum"
+                ),
+                change(
+                    Compatibility::Compatible,
+                    Kind::ConstraintRelaxed,
+                    "properties[2].mode.enum"
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn incompatible_type_change_skips_constraints() {
+        let old = "properties:\n  - a:\n      type: string\n      maxLength: 3";
+        let new = "properties:\n  - a:\n      type: integer\n      max: 3";
+        assert_eq!(
+            changes(old, new),
+            vec![change(
+                Compatibility::Breaking,
+                Kind::TypeChanged,
+                "properties[0].a.type"
+            )]
+        );
+    }
+
+    #[test]
+    fn resolve_references() {
+        let old = r##"
+            definitions:
+              port:
+                type: port
+                min: 1024
+            properties:
+              - http:
+                  $ref: "#/definitions/port"
+        "##;
+        let new = r##"
+            definitions:
+              port:
+                type: port
+            properties:
+              - http:
+                  $ref: "#/definitions/port"
+        "##;
+        assert_eq!(
+            changes(old, new),
+            vec![change(
+                Compatibility::Compatible,
+                Kind::ConstraintRelaxed,
+                "properties[0].http.min"
+            )]
+        );
+    }
+
+    #[test]
+    fn mapping_targets() {
+        let old = r#"
+            mapping:
+              targets:
+                config:
+                  type: file
+                  format: json
+                  location:
+                    path: config.json
+                    partition: 1
+                legacy:
+                  type: file
+                  format: ini
+                  location:
+                    path: legacy.ini
+                    partition: 1
+        "#;
+        let new = r#"
+            mapping:
+              targets:
+                config:
+                  type: file
+                  format: json
+                  location:
+                    path: /boot/config.json
+                    partition: 1
+                extra:
+                  type: file
+                  format: ini
+                  location:
+                    path: extra.ini
+                    partition: 1
+        "#;
+        assert_eq!(
+            changes(old, new),
+            vec![
+                change(
+                    Compatibility::Breaking,
+                    Kind::MappingTargetChanged,
+                    "mapping.targets.config"
+                ),
+                change(
+                    Compatibility::Compatible,
+                    Kind::MappingTargetAdded,
+                    "mapping.targets.extra"
+                ),
+                change(
+                    Compatibility::Breaking,
+                    Kind::MappingTargetRemoved,
+                    "mapping.targets.legacy"
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn serialize_changes() {
+        let old: Schema = "properties:\n  - a:\n      type: string?".parse().unwrap();
+        let new: Schema = "properties:\n  - a:\n      type: string".parse().unwrap();
+        assert_eq!(
+            serde_json::to_value(diff(&old, &new)).unwrap(),
+            serde_json::json!([{
+                "compatibility": "breaking",
+                "kind": "became-required",
+                "schemaPath": "properties[0].a.type",
+                "message": "value became required"
+            }])
+        );
+    }
+}
@@ -1,10 +1,29 @@
 use std::{error, fmt};
 
 use serde;
+use yaml_rust::{
+    parser::{Event, Parser},
+    scanner::Marker,
+};
+
+/// Location of the error in the schema source
+#[derive(Debug, Clone, PartialEq)]
+struct Location {
+    // 1-based line
+    line: usize,
+    // 1-based column
+    column: usize,
+    // Path to the value, like `properties[3].advanced.properties[1].hostname.type`
+    path: String,
+    snippet: String,
+}
 
 #[derive(Debug)]
 pub struct Error {
     msg: String,
+    // Schema file the error is located in, multi-file schemas only
+    file: Option<String>,
+    location: Option<Location>,
 }
 
 impl Error {
@@ -12,20 +31,332 @@ impl Error {
     where
         S: Into<String>,
     {
-        Error { msg: msg.into() }
+        Error {
+            msg: msg.into(),
+            file: None,
+            location: None,
+        }
+    }
+
+    /// Creates an error with the location of the failure in the YAML source
+    pub(crate) fn from_yaml(error: serde_yaml::Error, source: &str) -> Error {
+        let yaml_location = match error.location() {
+            Some(x) => x,
+            None => return Error::message(error.to_string()),
+        };
+
+        let path = value_path(source, yaml_location.index()).unwrap_or_default();
+
+        Error {
+            msg: strip_yaml_location(&error.to_string(), &path, yaml_location.line(), yaml_location.column()),
+            file: None,
+            location: Some(Location {
+                line: yaml_location.line(),
+                column: yaml_location.column(),
+                snippet: snippet(source, yaml_location.line(), yaml_location.column()),
+                path,
+            }),
+        }
+    }
+
+    /// Creates an error located at the value with the `path` in the YAML source
+    ///
+    /// The error has no location if there's no such value.
+    pub(crate) fn at_path<S>(msg: S, source: &str, path: &str) -> Error
+    where
+        S: Into<String>,
+    {
+        let mut error = Error::message(msg);
+
+        if let Some(marker) = value_marker(source, path) {
+            let column = marker.col() + 1;
+
+            error.location = Some(Location {
+                line: marker.line(),
+                column,
+                path: path.to_string(),
+                snippet: snippet(source, marker.line(), column),
+            });
+        }
+
+        error
+    }
+
+    /// Sets the schema file the error is located in
+    pub(crate) fn in_file<S>(mut self, file: S) -> Error
+    where
+        S: Into<String>,
+    {
+        self.file = Some(file.into());
+        self
+    }
+
+    /// Error message without the location
+    pub fn msg(&self) -> &str {
+        &self.msg
+    }
+
+    /// Schema file of the failure, multi-file schemas loaded with the `loader` only
+    pub fn file(&self) -> Option<&str> {
+        self.file.as_deref()
+    }
+
+    /// 1-based line of the failure in the schema source
+    pub fn line(&self) -> Option<usize> {
+        self.location.as_ref().map(|x| x.line)
+    }
+
+    /// 1-based column of the failure in the schema source
+    pub fn column(&self) -> Option<usize> {
+        self.location.as_ref().map(|x| x.column)
+    }
+
+    /// Path to the failing value (`properties[3].advanced.properties[1].hostname.type`)
+    pub fn path(&self) -> Option<&str> {
+        self.location.as_ref().map(|x| x.path.as_str())
+    }
+
+    /// Source line of the failure with a caret pointing to the failing column
+    pub fn snippet(&self) -> Option<&str> {
+        self.location.as_ref().map(|x| x.snippet.as_str())
     }
 }
 
 impl error::Error for Error {}
 
+/// Alternate form (`{:#}`) includes the snippet
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", self.msg)
+        if let Some(file) = &self.file {
+            write!(f, "{}: ", file)?;
+        }
+
+        match &self.location {
+            Some(location) => {
+                if !location.path.is_empty() {
+                    write!(f, "{}: ", location.path)?;
+                }
+                write!(f, "{} at line {} column {}", self.msg, location.line, location.column)?;
+                if f.alternate() {
+                    write!(f, "\n{}", location.snippet)?;
+                }
+                Ok(())
+            }
+            None => write!(f, "{}", self.msg),
+        }
     }
 }
 
 impl<T: serde::de::Error + 'static> From<T> for Error {
     fn from(e: T) -> Error {
-        Error { msg: format!("{}", e) }
+        Error::message(format!("{}", e))
+    }
+}
+
+// serde_yaml errors are formatted as `path: message at line L column C`, where the
+// path is an ancestor of the value path (or missing at all)
+fn strip_yaml_location(message: &str, path: &str, line: usize, column: usize) -> String {
+    let suffix = format!(" at line {} column {}", line, column);
+    let message = message.strip_suffix(&suffix).unwrap_or(message);
+
+    if let Some(index) = message.find(": ") {
+        let prefix = &message[..index];
+        let is_ancestor = path == prefix || (path.starts_with(prefix) && path[prefix.len()..].starts_with(['.', '[']));
+
+        if is_ancestor {
+            return message[index + 2..].to_string();
+        }
+    }
+
+    message.to_string()
+}
+
+fn snippet(source: &str, line: usize, column: usize) -> String {
+    let content = source.lines().nth(line.saturating_sub(1)).unwrap_or_default();
+    let number = line.to_string();
+    let indent = " ".repeat(number.len());
+    let offset: String = content
+        .chars()
+        .take(column.saturating_sub(1))
+        .map(|x| if x == '\t' { '\t' } else { ' ' })
+        .collect();
+
+    format!("{} | {}\n{} | {}^", number, content, indent, offset)
+}
+
+enum Frame {
+    Sequence(usize),
+    // Last mapping key, `None` if the key is expected
+    Mapping(Option<String>),
+}
+
+fn format_path(frames: &[Frame]) -> String {
+    let mut path = String::new();
+
+    for frame in frames {
+        match frame {
+            Frame::Sequence(index) => path.push_str(&format!("[{}]", index)),
+            Frame::Mapping(Some(key)) => {
+                if !path.is_empty() {
+                    path.push('.');
+                }
+                path.push_str(key);
+            }
+            Frame::Mapping(None) => {}
+        };
+    }
+
+    path
+}
+
+// Finds the path of the value starting at the `index` (char index) of the source
+fn value_path(source: &str, index: usize) -> Option<String> {
+    find_value(source, |_, marker| marker.index() == index).map(|(path, _)| path)
+}
+
+// Finds the start of the value with the `path`
+fn value_marker(source: &str, path: &str) -> Option<Marker> {
+    find_value(source, |x, _| x == path).map(|(_, marker)| marker)
+}
+
+// Finds the first value matching the predicate, returns its path & start
+fn find_value<F>(source: &str, mut predicate: F) -> Option<(String, Marker)>
+where
+    F: FnMut(&str, &Marker) -> bool,
+{
+    let mut parser = Parser::new(source.chars());
+    let mut frames: Vec<Frame> = vec![];
+
+    loop {
+        let (event, marker): (Event, Marker) = parser.next().ok()?;
+
+        let is_node = match event {
+            Event::StreamEnd => return None,
+            Event::Scalar(..) | Event::Alias(..) | Event::SequenceStart(..) | Event::MappingStart(..) => true,
+            _ => false,
+        };
+
+        let is_key = matches!(frames.last(), Some(Frame::Mapping(None)));
+
+        if is_node && !is_key {
+            let path = format_path(&frames);
+            if predicate(&path, &marker) {
+                return Some((path, marker));
+            }
+        }
+
+        match &event {
+            Event::Scalar(value, ..) if is_key => {
+                frames.pop();
+                frames.push(Frame::Mapping(Some(value.clone())));
+            }
+            Event::SequenceStart(..) => frames.push(Frame::Sequence(0)),
+            Event::MappingStart(..) => frames.push(Frame::Mapping(None)),
+            Event::SequenceEnd | Event::MappingEnd => {
+                frames.pop();
+            }
+            _ => {}
+        };
+
+        // Move to the next item if the value is complete
+        let is_complete = match event {
+            Event::Scalar(..) | Event::Alias(..) => !is_key,
+            Event::SequenceEnd | Event::MappingEnd => true,
+            _ => false,
+        };
+
+        if is_complete {
+            match frames.last_mut() {
+                Some(Frame::Sequence(index)) => *index += 1,
+                Some(Frame::Mapping(key)) => *key = None,
+                None => {}
+            };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::Schema;
+
+    const SCHEMA: &str = r#"version: 1
+properties:
+  - name:
+      type: string
+  - advanced:
+      properties:
+        - persistentLogging:
+            type: boolean
+        - hostname:
+            type: strng
+"#;
+
+    #[test]
+    fn invalid_type_location() {
+        let error = SCHEMA.parse::<Schema>().unwrap_err();
+        assert_eq!(error.msg(), "invalid primitive type: \"strng\"");
+        assert_eq!(error.line(), Some(10));
+        assert_eq!(error.column(), Some(19));
+        assert_eq!(error.path(), Some("properties[1].advanced.properties[1].hostname.type"));
+        assert_eq!(
+            error.snippet(),
+            Some("10 |             type: strng\n   |                   ^")
+        );
+        assert_eq!(
+            error.to_string(),
+            "properties[1].advanced.properties[1].hostname.type: invalid primitive type: \"strng\" at line 10 column 19"
+        );
+    }
+
+    #[test]
+    fn root_keyword_location() {
+        let error = "version: 7".parse::<Schema>().unwrap_err();
+        assert_eq!(error.path(), Some("version"));
+        assert_eq!(error.line(), Some(1));
+        assert_eq!(error.column(), Some(10));
+    }
+
+    #[test]
+    fn nested_keyword_locations() {
+        let path = |schema: &str| schema.parse::<Schema>().unwrap_err().path().map(str::to_string);
+
+        assert_eq!(
+            path("version: 1\nproperties:\n  - a:\n      type: string\n      enum:\n        - [1, 2]"),
+            Some("properties[0].a.enum[0]".to_string())
+        );
+        assert_eq!(
+            path("version: 1\nproperties:\n  - a:\n      type: array\n      uniqueItems: 3"),
+            Some("properties[0].a.uniqueItems".to_string())
+        );
+        assert_eq!(
+            path("version: 1\nmapping:\n  targets:\n    a:\n      type: fil"),
+            Some("mapping.targets.a.type".to_string())
+        );
+        assert_eq!(
+            path("version: 1\nproperties:\n  - a:\n      minLength: foo"),
+            Some("properties[0].a.minLength".to_string())
+        );
+    }
+
+    #[test]
+    fn syntax_error_location() {
+        let error = "version: 1\nproperties: ]\ntitle: foo".parse::<Schema>().unwrap_err();
+        assert_eq!(error.line(), Some(2));
+        assert!(error.snippet().is_some());
+    }
+
+    #[test]
+    fn alternate_format_contains_snippet() {
+        let error = SCHEMA.parse::<Schema>().unwrap_err();
+        assert!(format!("{:#}", error).ends_with("\n10 |             type: strng\n   |                   ^"));
+    }
+
+    #[test]
+    fn message_without_location() {
+        let error = Error::message("foo");
+        assert_eq!(error.to_string(), "foo");
+        assert_eq!(error.line(), None);
+        assert_eq!(error.path(), None);
     }
 }
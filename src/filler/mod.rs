@@ -1,68 +1,107 @@
 //! A module containing default values filler.
 use serde_json::{json, Value};
 
-use crate::schema::{PrimitiveType, Schema};
+use crate::{
+    formula::evaluate_condition,
+    schema::{PrimitiveType, Schema},
+};
 
 // Recursively check if the object is empty
 //
 // If the child object is empty, parent object is considered as empty.
 fn is_empty_object(value: &Value) -> bool {
     match value {
-        Value::Object(map) => map.values().all(|x| is_empty_object(x)),
+        Value::Object(map) => map.values().all(is_empty_object),
         _ => false,
     }
 }
 
-fn fill_object_defaults(schema: &Schema, data: &mut Value, include_optional: bool) {
-    if data.is_null() {
-        std::mem::replace(data, json!({}));
-    }
+struct Filler<'a> {
+    // Root schema, used to resolve references
+    root: &'a Schema,
+    include_optional: bool,
+    // References being expanded for missing values, recursive references
+    // would never end otherwise
+    expanding: Vec<&'a str>,
+}
+
+impl<'a> Filler<'a> {
+    fn fill_object_defaults(&mut self, schema: &'a Schema, data: &mut Value) {
+        if data.is_null() {
+            *data = json!({});
+        }
 
-    if let Some(data) = data.as_object_mut() {
-        for property in schema.properties() {
-            let name = property.name();
+        if let Some(data) = data.as_object_mut() {
+            for property in schema.properties() {
+                let name = property.name();
 
-            if let Some(mut value) = data.get_mut(name) {
-                fill_defaults(property.schema(), &mut value, include_optional);
-            } else {
-                // Fill defaults, but if the resulting object is empty, do not include it
-                let mut value = Value::Null;
-                fill_defaults(property.schema(), &mut value, include_optional);
-                if !value.is_null() && !is_empty_object(&value) {
-                    data.insert(name.to_string(), value);
+                if let Some(condition) = property.schema().when() {
+                    if !evaluate_condition(condition, &Value::Object(data.clone())) {
+                        continue;
+                    }
+                }
+
+                if let Some(value) = data.get_mut(name) {
+                    self.fill_defaults(property.schema(), value);
+                } else {
+                    // Fill defaults, but if the resulting object is empty, do not include it
+                    let mut value = Value::Null;
+                    self.fill_defaults(property.schema(), &mut value);
+                    if !value.is_null() && !is_empty_object(&value) {
+                        data.insert(name.to_string(), value);
+                    }
                 }
             }
         }
     }
-}
 
-fn fill_array_defaults(schema: &Schema, data: &mut Value, include_optional: bool) {
-    if data.is_array() && schema.items().len() == 1 {
-        // What we should do in case of multiple schemas? Partial object match?
-        let schema = schema.items().first().unwrap();
+    fn fill_array_defaults(&mut self, schema: &'a Schema, data: &mut Value) {
+        if data.is_array() && schema.items().len() == 1 {
+            // What we should do in case of multiple schemas? Partial object match?
+            let schema = schema.items().first().unwrap();
 
-        for mut item in data.as_array_mut().unwrap() {
-            fill_defaults(&schema, &mut item, include_optional);
+            for item in data.as_array_mut().unwrap() {
+                self.fill_defaults(schema, item);
+            }
         }
     }
-}
 
-fn fill_primitive_defaults(schema: &Schema, data: &mut Value, include_optional: bool) {
-    let required = schema.r#type().is_required();
+    fn fill_primitive_defaults(&self, schema: &Schema, data: &mut Value) {
+        let required = schema.r#type().is_required();
 
-    if let Some(default_value) = schema.r#default() {
-        if data.is_null() && (include_optional || required) {
-            std::mem::replace(data, default_value.clone());
+        if let Some(default_value) = schema.r#default() {
+            if data.is_null() && (self.include_optional || required) {
+                *data = default_value.clone();
+            }
         }
     }
-}
 
-fn fill_defaults(schema: &Schema, data: &mut Value, include_optional: bool) {
-    match (schema.r#type().primitive_type(), schema.r#type().is_required()) {
-        (PrimitiveType::Object, _) => fill_object_defaults(schema, data, include_optional),
-        (PrimitiveType::Array, _) => fill_array_defaults(schema, data, include_optional),
-        _ => fill_primitive_defaults(schema, data, include_optional),
-    };
+    fn fill_defaults(&mut self, schema: &'a Schema, data: &mut Value) {
+        let reference = match schema.r#ref() {
+            Some(reference) if data.is_null() && self.expanding.contains(&reference) => return,
+            Some(reference) if data.is_null() => Some(reference),
+            _ => None,
+        };
+
+        if let Some(reference) = reference {
+            self.expanding.push(reference);
+        }
+
+        let schema = self.root.resolve(schema);
+
+        match (
+            schema.r#type().primitive_type().base_type(),
+            schema.r#type().is_required(),
+        ) {
+            (PrimitiveType::Object, _) => self.fill_object_defaults(schema, data),
+            (PrimitiveType::Array, _) => self.fill_array_defaults(schema, data),
+            _ => self.fill_primitive_defaults(schema, data),
+        };
+
+        if reference.is_some() {
+            self.expanding.pop();
+        }
+    }
 }
 
 /// Fill default values from the schema
@@ -73,14 +112,20 @@ fn fill_defaults(schema: &Schema, data: &mut Value, include_optional: bool) {
 /// * `data` - JSON value to start with
 /// * `include_optional` - if `false` only required properties are filled
 pub fn fill_default_values(schema: &Schema, data: &mut Value, include_optional: bool) {
-    fill_defaults(schema, data, include_optional);
+    let mut filler = Filler {
+        root: schema,
+        include_optional,
+        expanding: vec![],
+    };
+    filler.fill_defaults(schema, data);
+
     if data.is_null() {
-        match schema.r#type().primitive_type() {
+        match schema.resolve(schema).r#type().primitive_type().base_type() {
             PrimitiveType::Object => {
-                std::mem::replace(data, json!({}));
+                *data = json!({});
             }
             PrimitiveType::Array => {
-                std::mem::replace(data, json!([]));
+                *data = json!([]);
             }
             _ => {}
         }
@@ -214,6 +259,54 @@ mod tests {
         assert_eq!(fill_required(schema, input), result);
     }
 
+    #[test]
+    fn fill_referenced_definitions() {
+        let schema = r##"
+            definitions:
+                node:
+                    properties:
+                        - name:
+                            type: string
+                            default: node
+                        - children:
+                            type: array?
+                            items:
+                                $ref: "#/definitions/node"
+            properties:
+                - root:
+                    $ref: "#/definitions/node"
+        "##;
+        let input = json!({"root": {"children": [{}, {"name": "leaf"}]}});
+        let result = json!({"root": {"name": "node", "children": [{"name": "node"}, {"name": "leaf"}]}});
+        assert_eq!(fill_required(schema, input), result);
+    }
+
+    #[test]
+    fn fill_conditional_properties() {
+        let schema = r##"
+            properties:
+                - proxyType:
+                    type: string
+                    default: socks5
+                - port:
+                    type: port
+                    default: 1080
+                    when: proxyType == "socks5"
+                - url:
+                    type: string
+                    default: http://proxy.local
+                    when: proxyType == "http-connect"
+        "##;
+        assert_eq!(
+            fill_required(schema, json!({})),
+            json!({"proxyType": "socks5", "port": 1080})
+        );
+        assert_eq!(
+            fill_required(schema, json!({"proxyType": "http-connect"})),
+            json!({"proxyType": "http-connect", "url": "http://proxy.local"})
+        );
+    }
+
     #[test]
     fn object_emptiness() {
         assert!(!is_empty_object(&json!("foo")));
@@ -0,0 +1,545 @@
+//! A module containing the formula evaluation engine.
+//!
+//! Every `formula` keyword in the schema is evaluated against the data document
+//! and the result is stored at the data path of the schema. Formulas can reference
+//! any other value in the data document, either with an absolute path (`foo.bar`)
+//! or with a path relative to the computed value (`this`, `super`).
+//!
+//! ```yaml
+//! properties:
+//!   - networks:
+//!       type: array
+//!       items:
+//!         properties:
+//!           - id:
+//!               type: string
+//!               formula: super.ssid | slugify
+//!           - ssid:
+//!               type: string
+//! ```
+//!
+//! Formulas referencing other computed values are evaluated after them, dependency
+//! cycles are reported as errors.
+use std::{error, fmt};
+
+use balena_temen::{
+    ast::{Expression, ExpressionValue, FunctionCall, Identifier, IdentifierValue},
+    Context, Engine,
+};
+use serde_json::{Map, Value};
+
+use crate::schema::{PrimitiveType, Schema};
+
+/// Formula evaluation error
+#[derive(Debug, Clone, PartialEq)]
+pub struct FormulaError {
+    data_path: String,
+    message: String,
+}
+
+impl FormulaError {
+    fn new<S1, S2>(data_path: S1, message: S2) -> FormulaError
+    where
+        S1: Into<String>,
+        S2: Into<String>,
+    {
+        FormulaError {
+            data_path: data_path.into(),
+            message: message.into(),
+        }
+    }
+
+    pub fn data_path(&self) -> &str {
+        &self.data_path
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl fmt::Display for FormulaError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "data path: '{}', message: '{}'", self.data_path, self.message)
+    }
+}
+
+impl error::Error for FormulaError {}
+
+// Formula with the position of the computed value
+struct Site<'a> {
+    formula: &'a str,
+    position: Identifier,
+    dependencies: Vec<Identifier>,
+}
+
+impl<'a> Site<'a> {
+    fn data_path(&self) -> String {
+        data_path(&self.position)
+    }
+}
+
+fn data_path(identifier: &Identifier) -> String {
+    let mut result = String::new();
+
+    for value in &identifier.values {
+        match value {
+            IdentifierValue::Name(name) if result.is_empty() => result.push_str(name),
+            IdentifierValue::Name(name) => {
+                result.push('.');
+                result.push_str(name);
+            }
+            IdentifierValue::Index(index) => result.push_str(&format!("[{}]", index)),
+            _ => {}
+        }
+    }
+
+    result
+}
+
+// Collects all formulas for values which can be stored in the data document
+//
+// Object properties are visited even if they are missing, array items only
+// if they exist.
+fn collect_sites<'a>(
+    root: &'a Schema,
+    schema: &'a Schema,
+    data: Option<&Value>,
+    position: Identifier,
+    sites: &mut Vec<Site<'a>>,
+) {
+    let schema = root.resolve(schema);
+
+    if let Some(formula) = schema.formula() {
+        sites.push(Site {
+            formula,
+            position,
+            dependencies: vec![],
+        });
+        return;
+    }
+
+    match (schema.r#type().primitive_type().base_type(), data) {
+        (PrimitiveType::Object, Some(Value::Object(object))) => {
+            for property in schema.properties() {
+                collect_sites(
+                    root,
+                    property.schema(),
+                    object.get(property.name()),
+                    position.clone().name(property.name()),
+                    sites,
+                );
+            }
+        }
+        (PrimitiveType::Array, Some(Value::Array(items))) if schema.items().len() == 1 => {
+            let item_schema = &schema.items()[0];
+
+            for (index, item) in items.iter().enumerate() {
+                collect_sites(
+                    root,
+                    item_schema,
+                    Some(item),
+                    position.clone().index(index as isize),
+                    sites,
+                );
+            }
+        }
+        _ => {}
+    };
+}
+
+fn collect_function_call_identifiers(call: &FunctionCall, identifiers: &mut Vec<Identifier>) {
+    for arg in &call.args {
+        collect_expression_identifiers(arg, identifiers);
+    }
+}
+
+fn collect_value_identifiers(value: &ExpressionValue, identifiers: &mut Vec<Identifier>) {
+    match value {
+        ExpressionValue::Identifier(identifier) => {
+            for value in &identifier.values {
+                if let IdentifierValue::Identifier(nested) = value {
+                    identifiers.push(nested.clone());
+                }
+            }
+            identifiers.push(identifier.clone());
+        }
+        ExpressionValue::Math(math) => {
+            collect_expression_identifiers(&math.lhs, identifiers);
+            collect_expression_identifiers(&math.rhs, identifiers);
+        }
+        ExpressionValue::Logical(logical) => {
+            collect_expression_identifiers(&logical.lhs, identifiers);
+            collect_expression_identifiers(&logical.rhs, identifiers);
+        }
+        ExpressionValue::FunctionCall(call) => collect_function_call_identifiers(call, identifiers),
+        ExpressionValue::StringConcat(concat) => {
+            for value in &concat.values {
+                collect_value_identifiers(value, identifiers);
+            }
+        }
+        ExpressionValue::Ternary(ternary) => {
+            collect_expression_identifiers(&ternary.condition, identifiers);
+            collect_expression_identifiers(&ternary.truthy, identifiers);
+            collect_expression_identifiers(&ternary.falsy, identifiers);
+        }
+        ExpressionValue::Integer(_)
+        | ExpressionValue::Float(_)
+        | ExpressionValue::Boolean(_)
+        | ExpressionValue::String(_) => {}
+    };
+}
+
+fn collect_expression_identifiers(expression: &Expression, identifiers: &mut Vec<Identifier>) {
+    collect_value_identifiers(&expression.value, identifiers);
+
+    for filter in &expression.filters {
+        collect_function_call_identifiers(filter, identifiers);
+    }
+}
+
+// Filters & functions are registered with uppercased names in the temen engine,
+// but the DSL allows any case (`super.ssid | slugify`)
+fn normalize(formula: &str) -> String {
+    let mut result = String::with_capacity(formula.len());
+    let mut chars = formula.chars().peekable();
+    let mut after_pipe = false;
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' | '\'' | '`' => {
+                result.push(c);
+                for x in chars.by_ref() {
+                    result.push(x);
+                    if x == c {
+                        break;
+                    }
+                }
+                after_pipe = false;
+            }
+            c if c.is_ascii_alphabetic() || c == '_' => {
+                let mut name = c.to_string();
+                while let Some(&x) = chars.peek() {
+                    if x.is_ascii_alphanumeric() || x == '_' {
+                        name.push(x);
+                        chars.next();
+                    } else {
+                        break;
+                    }
+                }
+
+                let mut whitespace = String::new();
+                while let Some(&x) = chars.peek() {
+                    if x.is_whitespace() {
+                        whitespace.push(x);
+                        chars.next();
+                    } else {
+                        break;
+                    }
+                }
+
+                let is_keyword = ["and", "or", "not", "true", "false"].contains(&name.as_str());
+                if !is_keyword && (after_pipe || chars.peek() == Some(&'(')) {
+                    result.push_str(&name.to_uppercase());
+                } else {
+                    result.push_str(&name);
+                }
+                result.push_str(&whitespace);
+                after_pipe = false;
+            }
+            '|' => {
+                result.push(c);
+                after_pipe = true;
+            }
+            c if c.is_whitespace() => result.push(c),
+            _ => {
+                result.push(c);
+                after_pipe = false;
+            }
+        }
+    }
+
+    result
+}
+
+fn is_prefix(prefix: &[IdentifierValue], values: &[IdentifierValue]) -> bool {
+    prefix.len() <= values.len() && prefix == &values[..prefix.len()]
+}
+
+// Site `a` depends on site `b` if `a` references `b`, any of its parents
+// or any of its children
+fn depends_on(a: &Site, b: &Site) -> bool {
+    a.dependencies
+        .iter()
+        .any(|x| is_prefix(&x.values, &b.position.values) || is_prefix(&b.position.values, &x.values))
+}
+
+// Depth first topological sort
+fn sort(sites: &[Site]) -> Result<Vec<usize>, FormulaError> {
+    #[derive(Clone, Copy, PartialEq)]
+    enum Mark {
+        None,
+        Visiting,
+        Done,
+    }
+
+    fn visit(
+        index: usize,
+        sites: &[Site],
+        marks: &mut Vec<Mark>,
+        stack: &mut Vec<usize>,
+        order: &mut Vec<usize>,
+    ) -> Result<(), FormulaError> {
+        match marks[index] {
+            Mark::Done => return Ok(()),
+            Mark::Visiting => {
+                let start = stack.iter().position(|x| *x == index).unwrap_or(0);
+                let cycle: Vec<String> = stack[start..]
+                    .iter()
+                    .chain(Some(&index))
+                    .map(|x| sites[*x].data_path())
+                    .collect();
+                return Err(FormulaError::new(
+                    sites[index].data_path(),
+                    format!("formula dependency cycle: {}", cycle.join(" -> ")),
+                ));
+            }
+            Mark::None => {}
+        };
+
+        marks[index] = Mark::Visiting;
+        stack.push(index);
+
+        for dependency in 0..sites.len() {
+            if dependency != index && depends_on(&sites[index], &sites[dependency]) {
+                visit(dependency, sites, marks, stack, order)?;
+            }
+        }
+
+        stack.pop();
+        marks[index] = Mark::Done;
+        order.push(index);
+        Ok(())
+    }
+
+    let mut marks = vec![Mark::None; sites.len()];
+    let mut stack = vec![];
+    let mut order = vec![];
+
+    for index in 0..sites.len() {
+        visit(index, sites, &mut marks, &mut stack, &mut order)?;
+    }
+
+    Ok(order)
+}
+
+// Stores the value at the position, missing (null) parent objects are created,
+// the value is not stored if a parent is not an object or the array index is out of bounds
+fn store(data: &mut Value, position: &Identifier, value: Value) {
+    let mut current = data;
+
+    for component in &position.values {
+        if current.is_null() {
+            *current = Value::Object(Map::new());
+        }
+
+        current = match (component, current) {
+            (IdentifierValue::Name(name), Value::Object(object)) => object.entry(name.as_str()).or_insert(Value::Null),
+            (IdentifierValue::Index(index), Value::Array(items)) if *index >= 0 && (*index as usize) < items.len() => {
+                &mut items[*index as usize]
+            }
+            _ => return,
+        };
+    }
+
+    *current = value;
+}
+
+// Parses single formula (or condition), filter names are normalized
+pub(crate) fn parse(formula: &str) -> Result<Expression, balena_temen::error::Error> {
+    normalize(formula).parse::<Expression>()
+}
+
+// Evaluates single formula at the position
+pub(crate) fn evaluate(
+    formula: &str,
+    position: &Identifier,
+    data: &Value,
+) -> Result<Value, balena_temen::error::Error> {
+    Engine::default().eval(&normalize(formula), position, data, &mut Context::default())
+}
+
+// Evaluates the `when` condition against the object containing the conditional property,
+// anything else than `true` (missing values, non boolean result, ...) is considered as `false`
+pub(crate) fn evaluate_condition(condition: &str, object: &Value) -> bool {
+    match evaluate(condition, &Identifier::default(), object) {
+        Ok(Value::Bool(x)) => x,
+        _ => false,
+    }
+}
+
+/// Evaluates all formulas and stores computed values in the data
+///
+/// Formulas are evaluated in the dependency order, already existing values
+/// are replaced with the computed ones.
+///
+/// # Arguments
+///
+/// * `schema` - JellySchema
+/// * `data` - JSON data to evaluate formulas against
+pub fn evaluate_formulas(schema: &Schema, data: &mut Value) -> Result<(), FormulaError> {
+    let mut sites = vec![];
+    collect_sites(schema, schema, Some(data), Identifier::default(), &mut sites);
+
+    let expressions = sites
+        .iter()
+        .map(|site| {
+            parse(site.formula).map_err(|e| FormulaError::new(site.data_path(), format!("invalid formula: {}", e)))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    for (site, expression) in sites.iter_mut().zip(expressions.iter()) {
+        let mut identifiers = vec![];
+        collect_expression_identifiers(expression, &mut identifiers);
+
+        for identifier in identifiers {
+            let canonical = identifier
+                .canonicalize(&site.position)
+                .map_err(|e| FormulaError::new(site.data_path(), format!("invalid formula reference: {}", e)))?;
+            site.dependencies.push(canonical);
+        }
+    }
+
+    let engine = Engine::default();
+    let mut context = Context::default();
+
+    for index in sort(&sites)? {
+        let site = &sites[index];
+        let value = engine
+            .eval(&normalize(site.formula), &site.position, data, &mut context)
+            .map_err(|e| FormulaError::new(site.data_path(), format!("unable to evaluate formula: {}", e)))?;
+        store(data, &site.position, value);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    const BALENA_OS: &str = include_str!("../../fuzz/seeds/balena-os.yml");
+
+    fn evaluate(schema: &str, data: Value) -> Result<Value, FormulaError> {
+        let schema: Schema = schema.parse().unwrap();
+        let mut data = data;
+        evaluate_formulas(&schema, &mut data)?;
+        Ok(data)
+    }
+
+    #[test]
+    fn normalize_names() {
+        assert_eq!(normalize("super.ssid | slugify"), "super.ssid | SLUGIFY");
+        assert_eq!(normalize("uuid() ~ `uuid()`"), "UUID() ~ `uuid()`");
+        assert_eq!(normalize("a.b|lower"), "a.b|LOWER");
+        assert_eq!(normalize("uuid  ()  ~ not a"), "UUID  ()  ~ not a");
+    }
+
+    #[test]
+    fn balena_os_network_id() {
+        let schema: Schema = BALENA_OS.parse().unwrap();
+        let mut data = json!({
+            "network": [
+                {"ssid": "My Network", "password": "secret"},
+                {"id": "stale", "ssid": "Other", "password": "secret"}
+            ]
+        });
+
+        evaluate_formulas(&schema, &mut data).unwrap();
+        assert_eq!(data["network"][0]["id"], json!("my-network"));
+        assert_eq!(data["network"][1]["id"], json!("other"));
+    }
+
+    #[test]
+    fn sibling_and_absolute_references() {
+        let schema = r#"
+            properties:
+              - first:
+                  type: integer
+              - second:
+                  type: integer
+                  formula: this.super.first + 1
+              - third:
+                  type: integer
+                  formula: second * 2
+        "#;
+        assert_eq!(
+            evaluate(schema, json!({"first": 1})).unwrap(),
+            json!({"first": 1, "second": 2, "third": 4})
+        );
+    }
+
+    #[test]
+    fn dependency_order() {
+        let schema = r#"
+            properties:
+              - a:
+                  type: string
+                  formula: super.b ~ `!`
+              - b:
+                  type: string
+                  formula: super.c | upper
+              - c:
+                  type: string
+        "#;
+        assert_eq!(
+            evaluate(schema, json!({"c": "foo"})).unwrap(),
+            json!({"a": "FOO!", "b": "FOO", "c": "foo"})
+        );
+    }
+
+    #[test]
+    fn fail_on_cycle() {
+        let schema = r#"
+            properties:
+              - a:
+                  type: integer
+                  formula: super.b + 1
+              - b:
+                  type: integer
+                  formula: super.a + 1
+        "#;
+        let error = evaluate(schema, json!({})).unwrap_err();
+        assert_eq!(error.data_path(), "a");
+        assert_eq!(error.message(), "formula dependency cycle: a -> b -> a");
+    }
+
+    #[test]
+    fn fail_on_evaluation_error() {
+        let schema = r#"
+            properties:
+              - items:
+                  type: array
+                  items:
+                    properties:
+                      - id:
+                          type: string
+                          formula: super.name | slugify
+        "#;
+        let error = evaluate(schema, json!({"items": [{}]})).unwrap_err();
+        assert_eq!(error.data_path(), "items[0].id");
+    }
+
+    #[test]
+    fn fail_on_invalid_formula() {
+        let schema = r#"
+            properties:
+              - a:
+                  type: integer
+                  formula: 1 +
+        "#;
+        assert!(evaluate(schema, json!({})).is_err());
+    }
+}
@@ -8,6 +8,8 @@ use crate::{
 
 mod serialization;
 
+pub(crate) use serialization::condition_dependency;
+
 fn generate_json_schema(schema: &Schema) -> Value {
     let json_schema: JsonSchema = JsonSchema::with_default_schema_url(schema);
     serde_json::to_value(json_schema).expect("Internal error: inconsistent schema: json schema")
@@ -1,11 +1,15 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::string::ToString;
 
+use balena_temen::ast::{Expression, ExpressionValue, IdentifierValue, LogicalOperator};
 use serde::ser::{Error, SerializeMap};
 use serde::{Serialize, Serializer};
-use serde_json::{json, Value};
+use serde_json::{json, Map, Value};
 
-use crate::schema::{PrimitiveType, Schema, UniqueItems};
+use crate::{
+    formula,
+    schema::{PrimitiveType, Schema, UniqueItems},
+};
 
 // we output Draft 4 of the Json Schema specification because the downstream consumers
 // of the JSON schema we produce fully support Draft 4, and not really Draft 7;
@@ -14,6 +18,8 @@ const SCHEMA_URL: &str = "http://json-schema.org/draft-04/schema#";
 
 pub struct JsonSchema<'a> {
     schema_url: Option<&'static str>,
+    // Root schema, used to resolve references
+    root: &'a Schema,
     schema: &'a Schema,
 }
 
@@ -28,57 +34,76 @@ impl<'a> Serialize for JsonSchema<'a> {
             map.serialize_entry("$schema", url)?;
         }
 
-        serialize_as_json_schema(&self.schema, &mut map)?;
+        match self.schema.r#ref() {
+            // Draft 4 ignores all other keywords, annotations are kept for the UI,
+            // optionality is handled by the parent `required` keyword
+            Some(reference) => {
+                map.serialize_entry("$ref", reference)?;
+                serialize_annotations(self.schema, &mut map)?;
+            }
+            None => serialize_as_json_schema(self.root, self.schema, &mut map)?,
+        };
+
+        // Definitions are often used as YAML anchors holders only, emit them
+        // when they are referenced
+        if std::ptr::eq(self.root, self.schema) && self.root.has_references() {
+            let definitions: BTreeMap<&str, JsonSchema> = self
+                .root
+                .definitions()
+                .iter()
+                .map(|(name, schema)| (name.as_str(), JsonSchema::nested(self.root, schema)))
+                .collect();
+            map.serialize_entry("definitions", &definitions)?;
+        }
+
         map.end()
     }
 }
 
 impl<'a> JsonSchema<'a> {
-    pub fn new(schema: &'a Schema) -> Self {
+    pub fn with_default_schema_url(schema: &'a Schema) -> Self {
         JsonSchema {
+            root: schema,
             schema,
-            schema_url: None,
+            schema_url: Some(SCHEMA_URL),
         }
     }
 
-    pub fn with_default_schema_url(schema: &'a Schema) -> Self {
+    fn nested(root: &'a Schema, schema: &'a Schema) -> Self {
         JsonSchema {
+            root,
             schema,
-            schema_url: Some(SCHEMA_URL),
+            schema_url: None,
         }
     }
 }
 
-fn serialize_type<O, E, S>(schema: &Schema, map: &mut S) -> Result<(), E>
-where
-    E: Error,
-    S: SerializeMap<Ok = O, Error = E>,
-{
-    let primitive_type = schema.r#type().primitive_type();
-
-    let (typ, additional_keywords): (&str, Value) = match primitive_type {
-        PrimitiveType::Hostname => ("string", json!({"format": "hostname"})),
-        PrimitiveType::Password => ("string", json!({"writeOnly": true})),
-        PrimitiveType::DateTime => ("string", json!({"format": "date-time"})),
-        PrimitiveType::Date => ("string", json!({"format": "date"})),
-        PrimitiveType::Time => ("string", json!({"format": "time"})),
-        PrimitiveType::IPv4 => ("string", json!({"format": "ipv4"})),
-        PrimitiveType::IPv6 => ("string", json!({"format": "ipv6"})),
-        PrimitiveType::Uri => ("string", json!({"format": "uri"})),
-        PrimitiveType::Text => ("string", Value::Null),
-        PrimitiveType::StringList => ("array", Value::Null),
-        PrimitiveType::DNSMasqAddress => ("string", json!({"format": "dnsmasq-address"})),
-        PrimitiveType::ChronyAddress => ("string", json!({"format": "chrony-address"})),
-        PrimitiveType::IPTablesAddress => ("string", json!({"format": "iptables-address"})),
-        PrimitiveType::Email => ("string", json!({"format": "email"})),
-        PrimitiveType::Object => ("object", Value::Null),
-        PrimitiveType::Array => ("array", Value::Null),
-        PrimitiveType::String => ("string", Value::Null),
-        PrimitiveType::Boolean => ("boolean", Value::Null),
-        PrimitiveType::Integer => ("integer", Value::Null),
-        PrimitiveType::Number => ("number", Value::Null),
-        PrimitiveType::File => ("string", json!({"format": "data-url"})),
-        PrimitiveType::Port => ("integer", {
+// JSON Schema type & additional keywords of the primitive type
+fn json_type(primitive_type: &PrimitiveType, schema: &Schema) -> (Value, Value) {
+    match primitive_type {
+        PrimitiveType::Hostname => (json!("string"), json!({"format": "hostname"})),
+        PrimitiveType::Password => (json!("string"), json!({"writeOnly": true})),
+        PrimitiveType::DateTime => (json!("string"), json!({"format": "date-time"})),
+        PrimitiveType::Date => (json!("string"), json!({"format": "date"})),
+        PrimitiveType::Time => (json!("string"), json!({"format": "time"})),
+        PrimitiveType::IPv4 => (json!("string"), json!({"format": "ipv4"})),
+        PrimitiveType::IPv6 => (json!("string"), json!({"format": "ipv6"})),
+        PrimitiveType::Uri => (json!("string"), json!({"format": "uri"})),
+        PrimitiveType::Text => (json!("string"), Value::Null),
+        // Separator-joined strings are accepted as well
+        PrimitiveType::StringList => (json!(["array", "string"]), Value::Null),
+        PrimitiveType::DNSMasqAddress => (json!("string"), json!({"format": "dnsmasq-address"})),
+        PrimitiveType::ChronyAddress => (json!("string"), json!({"format": "chrony-address"})),
+        PrimitiveType::IPTablesAddress => (json!("string"), json!({"format": "iptables-address"})),
+        PrimitiveType::Email => (json!("string"), json!({"format": "email"})),
+        PrimitiveType::Object => (json!("object"), Value::Null),
+        PrimitiveType::Array => (json!("array"), Value::Null),
+        PrimitiveType::String => (json!("string"), Value::Null),
+        PrimitiveType::Boolean => (json!("boolean"), Value::Null),
+        PrimitiveType::Integer => (json!("integer"), Value::Null),
+        PrimitiveType::Number => (json!("number"), Value::Null),
+        PrimitiveType::File => (json!("string"), json!({"format": "data-url"})),
+        PrimitiveType::Port => (json!("integer"), {
             match (schema.min(), schema.max()) {
                 (None, Some(_)) => json!({"minimum": 0}),
                 (Some(_), None) => json!({"maximum": 65535}),
@@ -86,9 +111,27 @@ where
                 _ => Value::Null,
             }
         }),
-    };
+        PrimitiveType::Custom(custom_type) => {
+            let (typ, mut additional_keywords) = json_type(custom_type.base(), schema);
+            if let Some(format) = custom_type.format() {
+                if !additional_keywords.is_object() {
+                    additional_keywords = json!({});
+                }
+                additional_keywords["format"] = json!(format);
+            }
+            (typ, additional_keywords)
+        }
+    }
+}
+
+fn serialize_type<O, E, S>(schema: &Schema, map: &mut S) -> Result<(), E>
+where
+    E: Error,
+    S: SerializeMap<Ok = O, Error = E>,
+{
+    let (typ, additional_keywords) = json_type(schema.r#type().primitive_type(), schema);
 
-    map.serialize_entry("type", typ)?;
+    map.serialize_entry("type", &typ)?;
 
     if let Some(obj) = additional_keywords.as_object() {
         for (k, v) in obj.iter() {
@@ -115,7 +158,7 @@ where
     Ok(())
 }
 
-fn serialize_array_keywords<O, E, S>(schema: &Schema, map: &mut S) -> Result<(), E>
+fn serialize_array_keywords<O, E, S>(root: &Schema, schema: &Schema, map: &mut S) -> Result<(), E>
 where
     E: Error,
     S: SerializeMap<Ok = O, Error = E>,
@@ -137,22 +180,102 @@ where
     let items_count = schema.items().len();
     match items_count {
         0 => {}
-        1 => map.serialize_entry("items", &JsonSchema::new(schema.items().first().unwrap()))?,
+        1 => map.serialize_entry("items", &JsonSchema::nested(root, schema.items().first().unwrap()))?,
         _ => {
-            let json_schemas: Vec<JsonSchema> = schema.items().iter().map(JsonSchema::new).collect();
-            map.serialize_entry("items", &json!({ "oneOf": json_schemas }))?;
+            map.serialize_entry("items", &json!({ "oneOf": nested_schemas(root, schema.items()) }))?;
         }
     };
 
     Ok(())
 }
 
-fn serialize_object_keywords<O, E, S>(schema: &Schema, map: &mut S) -> Result<(), E>
+// Returns the sibling property name if the expression is a plain (not negated, without filters) identifier
+fn sibling_name(expression: &Expression) -> Option<&str> {
+    match &expression.value {
+        ExpressionValue::Identifier(identifier) if !expression.negated && expression.filters.is_empty() => {
+            match identifier.values.as_slice() {
+                [IdentifierValue::Name(name)] => Some(name),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+fn literal_value(expression: &Expression) -> Option<Value> {
+    if expression.negated || !expression.filters.is_empty() {
+        return None;
+    }
+
+    match &expression.value {
+        ExpressionValue::Integer(x) => Some(json!(x)),
+        ExpressionValue::Float(x) => Some(json!(x)),
+        ExpressionValue::Boolean(x) => Some(json!(x)),
+        ExpressionValue::String(x) => Some(json!(x)),
+        _ => None,
+    }
+}
+
+// Only `sibling == literal` (or just `sibling` for booleans) conditions can be expressed
+// with the Draft 4 `dependencies`, returns the sibling name and the literal
+//
+// Properties with any other condition are emitted as optional ones, without any
+// dependency, the linter reports them (`unsupported-condition`)
+pub(crate) fn condition_dependency(condition: &str) -> Option<(String, Value)> {
+    let expression = formula::parse(condition).ok()?;
+
+    if let Some(name) = sibling_name(&expression) {
+        return Some((name.to_string(), json!(true)));
+    }
+
+    match &expression.value {
+        ExpressionValue::Logical(logical)
+            if logical.operator == LogicalOperator::Equal && !expression.negated && expression.filters.is_empty() =>
+        {
+            match (sibling_name(&logical.lhs), literal_value(&logical.rhs)) {
+                (Some(name), Some(literal)) => Some((name.to_string(), literal)),
+                _ => match (literal_value(&logical.lhs), sibling_name(&logical.rhs)) {
+                    (Some(literal), Some(name)) => Some((name.to_string(), literal)),
+                    _ => None,
+                },
+            }
+        }
+        _ => None,
+    }
+}
+
+// Conditional properties are not required at the object level, they're required in the
+// `oneOf` branch matching the sibling value
+fn dependencies(conditions: BTreeMap<String, Vec<(Value, Vec<&str>)>>) -> Map<String, Value> {
+    conditions
+        .into_iter()
+        .map(|(sibling, branches)| {
+            let literals: Vec<&Value> = branches.iter().map(|(literal, _)| literal).collect();
+            let otherwise = json!({ "properties": { sibling.as_str(): { "not": { "enum": literals } } } });
+
+            let mut one_of: Vec<Value> = branches
+                .iter()
+                .map(|(literal, required)| {
+                    let mut branch = json!({ "properties": { sibling.as_str(): { "enum": [literal] } } });
+                    if !required.is_empty() {
+                        branch["required"] = json!(required);
+                    }
+                    branch
+                })
+                .collect();
+            one_of.push(otherwise);
+
+            (sibling, json!({ "oneOf": one_of }))
+        })
+        .collect()
+}
+
+fn serialize_object_keywords<O, E, S>(root: &Schema, schema: &Schema, map: &mut S) -> Result<(), E>
 where
     E: Error,
     S: SerializeMap<Ok = O, Error = E>,
 {
-    if let PrimitiveType::Object = schema.r#type().primitive_type() {
+    if let PrimitiveType::Object = schema.r#type().primitive_type().base_type() {
         map.serialize_entry("additionalProperties", &schema.additional_properties())?;
     }
 
@@ -160,15 +283,35 @@ where
         let mut required = vec![];
         let mut order = vec![];
 
+        let mut conditions = BTreeMap::<String, Vec<(Value, Vec<&str>)>>::new();
+
         let mut properties = HashMap::<&str, JsonSchema>::new();
 
         for property in schema.properties() {
-            if property.schema().r#type().is_required() {
-                required.push(property.name());
-            }
+            let is_required = root.resolve(property.schema()).r#type().is_required();
+
+            match property.schema().when() {
+                Some(condition) => {
+                    if let Some((sibling, literal)) = condition_dependency(condition) {
+                        let branches = conditions.entry(sibling).or_default();
+                        let index = match branches.iter().position(|(x, _)| *x == literal) {
+                            Some(index) => index,
+                            None => {
+                                branches.push((literal, vec![]));
+                                branches.len() - 1
+                            }
+                        };
+                        if is_required {
+                            branches[index].1.push(property.name());
+                        }
+                    }
+                }
+                None if is_required => required.push(property.name()),
+                None => {}
+            };
             order.push(property.name());
 
-            properties.insert(property.name(), JsonSchema::new(property.schema()));
+            properties.insert(property.name(), JsonSchema::nested(root, property.schema()));
         }
 
         if !required.is_empty() {
@@ -182,12 +325,16 @@ where
         if !properties.is_empty() {
             map.serialize_entry("properties", &properties)?;
         }
+
+        if !conditions.is_empty() {
+            map.serialize_entry("dependencies", &dependencies(conditions))?;
+        }
     }
 
     match (schema.keys(), schema.values()) {
         (Some(keys), Some(values)) if keys.pattern().is_some() => map.serialize_entry(
             "patternProperties",
-            &json!({ keys.pattern().unwrap().to_string(): JsonSchema::new(values) }),
+            &json!({ keys.pattern().unwrap().to_string(): JsonSchema::nested(root, values) }),
         )?,
         _ => {}
     };
@@ -249,14 +396,45 @@ where
     Ok(())
 }
 
-fn serialize_as_json_schema<O, E, S>(schema: &Schema, map: &mut S) -> Result<(), E>
+fn nested_schemas<'a>(root: &'a Schema, schemas: &'a [Schema]) -> Vec<JsonSchema<'a>> {
+    schemas.iter().map(|x| JsonSchema::nested(root, x)).collect()
+}
+
+fn serialize_combinators<O, E, S>(root: &Schema, schema: &Schema, has_enum: bool, map: &mut S) -> Result<(), E>
+where
+    E: Error,
+    S: SerializeMap<Ok = O, Error = E>,
+{
+    let mut all_of: Vec<Value> = nested_schemas(root, schema.all_of()).iter().map(|x| json!(x)).collect();
+
+    if !schema.one_of().is_empty() {
+        if has_enum {
+            // `oneOf` is already used for `enum`, wrap it with `allOf`
+            all_of.insert(0, json!({ "oneOf": nested_schemas(root, schema.one_of()) }));
+        } else {
+            map.serialize_entry("oneOf", &nested_schemas(root, schema.one_of()))?;
+        }
+    }
+
+    if !schema.any_of().is_empty() {
+        map.serialize_entry("anyOf", &nested_schemas(root, schema.any_of()))?;
+    }
+
+    if !all_of.is_empty() {
+        map.serialize_entry("allOf", &all_of)?;
+    }
+
+    Ok(())
+}
+
+fn serialize_as_json_schema<O, E, S>(root: &Schema, schema: &Schema, map: &mut S) -> Result<(), E>
 where
     E: Error,
     S: SerializeMap<Ok = O, Error = E>,
 {
     serialize_annotations(schema, map)?;
-    serialize_array_keywords(schema, map)?;
-    serialize_object_keywords(schema, map)?;
+    serialize_array_keywords(root, schema, map)?;
+    serialize_object_keywords(root, schema, map)?;
     serialize_number_keywords(schema, map)?;
     serialize_string_keywords(schema, map)?;
 
@@ -295,5 +473,7 @@ where
         map.serialize_entry("oneOf", &values)?;
     }
 
+    serialize_combinators(root, schema, !values.is_empty(), map)?;
+
     Ok(())
 }
@@ -1,5 +1,6 @@
 mod json_schema;
 mod ui_schema;
 
+pub(crate) use json_schema::condition_dependency;
 pub use json_schema::JsonSchema;
 pub use ui_schema::UiSchema;
@@ -16,7 +16,11 @@ impl<'a> Serialize for UiSchema<'a> {
         S: Serializer,
     {
         let mut map = serializer.serialize_map(None)?;
-        serialize_as_ui_schema(&self.schema, &mut map)?;
+        let mut context = Context {
+            root: self.schema,
+            expanding: vec![],
+        };
+        serialize_as_ui_schema(&mut context, self.schema, &mut map)?;
         map.end()
     }
 }
@@ -27,6 +31,13 @@ impl<'a> UiSchema<'a> {
     }
 }
 
+// References are inlined, the UI schema has no definitions
+struct Context<'a> {
+    root: &'a Schema,
+    // References being inlined, recursive references are not expanded again
+    expanding: Vec<&'a str>,
+}
+
 fn serialize_widget(schema: &Schema, map: &mut Map<String, Value>) {
     // TODO Is there any other way how to express this? To avoid clashes
     //      of `type: password` & `hidden: true` for example
@@ -40,12 +51,21 @@ fn serialize_widget(schema: &Schema, map: &mut Map<String, Value>) {
 
     // Do this as a last thing, because `type` is preferred and if it clashes,
     // we'd like to have a widget based on the `type`
-    let primitive_type = schema.r#type().primitive_type();
+    if let Some(widget) = type_widget(schema.r#type().primitive_type()) {
+        map.insert("ui:widget".to_string(), json!(widget));
+    }
+}
+
+fn type_widget(primitive_type: &PrimitiveType) -> Option<String> {
     match primitive_type {
-        PrimitiveType::Password => map.insert("ui:widget".to_string(), json!("password")),
-        PrimitiveType::Text => map.insert("ui:widget".to_string(), json!("textarea")),
+        PrimitiveType::Password => Some("password".to_string()),
+        PrimitiveType::Text => Some("textarea".to_string()),
+        PrimitiveType::Custom(custom_type) => custom_type
+            .widget()
+            .map(str::to_string)
+            .or_else(|| type_widget(custom_type.base())),
         _ => None,
-    };
+    }
 }
 
 fn serialize_ui_options(schema: &Schema, map: &mut Map<String, Value>) {
@@ -87,7 +107,7 @@ fn serialize_annotations(schema: &Schema, map: &mut Map<String, Value>) {
     }
 }
 
-fn serialize_properties(schema: &Schema, map: &mut Map<String, Value>) {
+fn serialize_properties<'a>(context: &mut Context<'a>, schema: &'a Schema, map: &mut Map<String, Value>) {
     let mut order = vec![];
     let mut properties = Map::<String, Value>::new();
 
@@ -95,7 +115,11 @@ fn serialize_properties(schema: &Schema, map: &mut Map<String, Value>) {
         order.push(property.name());
 
         let mut property_map = Map::<String, Value>::new();
-        serialize_ui_schema_into_map(property.schema(), &mut property_map);
+        serialize_ui_schema_into_map(context, property.schema(), &mut property_map);
+
+        if let Some(condition) = property.schema().when() {
+            property_map.insert("ui:when".to_string(), Value::String(condition.to_string()));
+        }
 
         if !property_map.is_empty() {
             properties.insert(property.name().to_string(), Value::Object(property_map));
@@ -113,43 +137,81 @@ fn serialize_properties(schema: &Schema, map: &mut Map<String, Value>) {
     }
 }
 
-fn serialize_array_items(schema: &Schema, map: &mut Map<String, Value>) {
-    if schema.items().is_empty() {
-        return;
+// Merges UI schema of an alternative into the existing one, existing values win
+// except `ui:order`, which is extended with missing properties
+fn merge_ui_schema(map: &mut Map<String, Value>, other: Map<String, Value>) {
+    for (key, value) in other {
+        match (map.get_mut(&key), value) {
+            (None, value) => {
+                map.insert(key, value);
+            }
+            (Some(Value::Array(order)), Value::Array(other_order)) if key == "ui:order" => {
+                for name in other_order {
+                    if !order.contains(&name) {
+                        order.push(name);
+                    }
+                }
+            }
+            (Some(Value::Object(existing)), Value::Object(other)) => merge_ui_schema(existing, other),
+            _ => {}
+        };
     }
+}
 
-    if schema.items().len() > 1 {
-        // FIXME How it should look like?
-        return;
+// UI schema of the alternative schemas (multiple `items`, `oneOf`, ...) is shared
+// by all of them, that's how the forms apply it
+fn serialize_alternatives<'a>(context: &mut Context<'a>, schemas: &'a [Schema], map: &mut Map<String, Value>) {
+    for schema in schemas {
+        let mut result: Map<String, Value> = Map::new();
+        serialize_ui_schema_into_map(context, schema, &mut result);
+        merge_ui_schema(map, result);
     }
+}
 
+fn serialize_array_items<'a>(context: &mut Context<'a>, schema: &'a Schema, map: &mut Map<String, Value>) {
     let mut result: Map<String, Value> = Map::new();
-    serialize_ui_schema_into_map(schema.items().first().unwrap(), &mut result);
+    serialize_alternatives(context, schema.items(), &mut result);
 
     if !result.is_empty() {
         map.insert("items".to_string(), json!(result));
     }
 }
 
-fn serialize_ui_schema_into_map(schema: &Schema, map: &mut Map<String, Value>) {
+fn serialize_ui_schema_into_map<'a>(context: &mut Context<'a>, schema: &'a Schema, map: &mut Map<String, Value>) {
+    if let Some(reference) = schema.r#ref() {
+        if context.expanding.contains(&reference) {
+            return;
+        }
+
+        context.expanding.push(reference);
+        let resolved = context.root.resolve(schema);
+        serialize_ui_schema_into_map(context, resolved, map);
+        context.expanding.pop();
+        return;
+    }
+
     serialize_annotations(schema, map);
-    serialize_properties(schema, map);
+    serialize_properties(context, schema, map);
     serialize_widget(schema, map);
     serialize_ui_options(schema, map);
-    serialize_array_items(schema, map);
+    serialize_array_items(context, schema, map);
 
     if schema.read_only() {
         map.insert("ui:readonly".to_string(), json!(true));
     }
+
+    serialize_alternatives(context, schema.all_of(), map);
+    serialize_alternatives(context, schema.one_of(), map);
+    serialize_alternatives(context, schema.any_of(), map);
 }
 
-fn serialize_as_ui_schema<O, E, S>(schema: &Schema, map: &mut S) -> Result<(), E>
+fn serialize_as_ui_schema<'a, O, E, S>(context: &mut Context<'a>, schema: &'a Schema, map: &mut S) -> Result<(), E>
 where
     E: Error,
     S: SerializeMap<Ok = O, Error = E>,
 {
     let mut result: Map<String, Value> = Map::new();
-    serialize_ui_schema_into_map(schema, &mut result);
+    serialize_ui_schema_into_map(context, schema, &mut result);
 
     for (k, v) in result.iter() {
         map.serialize_entry(k, v)?;
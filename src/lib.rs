@@ -4,6 +4,13 @@
 //!
 //! * transform configuration DSL into the JSON Schema & UI Object Schema with custom extensions
 //! * parse configuration DSL
+//! * evaluate formulas computing values from other values
+//! * render mapped configuration files and read them back
+//! * lint schemas for mistakes the parser accepts
+//! * migrate schemas to the latest version
+//! * upgrade data to the current schema revision
+//! * compare schemas and check backwards compatibility
+//! * extend the validator & generator with custom types
 //!
 //! # Versioning
 //!
@@ -37,9 +44,14 @@
 //!
 //! [balena]: https://www.balena.io
 //! [Semantic Versioning]: https://semver.org/
-pub(crate) mod deref;
+pub mod address;
+pub mod diff;
 pub mod error;
 pub mod filler;
+pub mod formula;
+pub mod lint;
+pub mod mapper;
+pub mod migrator;
 pub mod schema;
 pub mod validator;
 
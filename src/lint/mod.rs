@@ -0,0 +1,680 @@
+//! A module containing the schema linter.
+//!
+//! The deserializer accepts plenty of schemas which do not make sense, like `min` greater
+//! than `max`, `minLength` on an integer or a `default` value not valid against its own
+//! schema. The linter walks the schema and reports them.
+//!
+//! # Examples
+//!
+//! ```
+//! use jellyschema::lint::{lint, Severity};
+//! use jellyschema::schema::Schema;
+//!
+//! let schema: Schema = r#"
+//!   version: 1
+//!   properties:
+//!     - retries:
+//!         type: integer
+//!         min: 10
+//!         max: 5
+//! "#.parse().unwrap();
+//!
+//! let diagnostics = lint(&schema);
+//! assert_eq!(diagnostics[0].severity(), Severity::Error);
+//! assert_eq!(diagnostics[0].schema_path(), "properties[0].retries.min");
+//! ```
+use std::fmt;
+
+use serde_derive::Serialize;
+use serde_json::Number;
+
+use crate::{
+    generator::condition_dependency,
+    schema::{mapping::Mapping, PrimitiveType, Schema, UniqueItems},
+    validator::validate_with_root,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+impl AsRef<str> for Severity {
+    fn as_ref(&self) -> &str {
+        match self {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Code {
+    /// Lower bound is greater than the upper bound (`min` > `max`, ...)
+    InvertedRange,
+    /// Keyword is ignored for the schema type (`minLength` on an integer, ...)
+    InapplicableKeyword,
+    /// `default` is not valid against the schema
+    InvalidDefault,
+    /// `const` is not one of the `enum` values
+    ConstOutsideEnum,
+    /// `keys` schema without the `values` schema
+    KeysWithoutValues,
+    /// `mapping.target` references target not defined in the root `mapping.targets`
+    UndefinedTarget,
+    /// `when` condition can't be expressed in the JSON Schema, the property is optional there
+    UnsupportedCondition,
+}
+
+impl AsRef<str> for Code {
+    fn as_ref(&self) -> &str {
+        match self {
+            Code::InvertedRange => "inverted-range",
+            Code::InapplicableKeyword => "inapplicable-keyword",
+            Code::InvalidDefault => "invalid-default",
+            Code::ConstOutsideEnum => "const-outside-enum",
+            Code::KeysWithoutValues => "keys-without-values",
+            Code::UndefinedTarget => "undefined-target",
+            Code::UnsupportedCondition => "unsupported-condition",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Diagnostic {
+    severity: Severity,
+    code: Code,
+    #[serde(rename = "schemaPath")]
+    schema_path: String,
+    message: String,
+}
+
+impl Diagnostic {
+    pub fn severity(&self) -> Severity {
+        self.severity
+    }
+
+    pub fn code(&self) -> Code {
+        self.code
+    }
+
+    pub fn schema_path(&self) -> &str {
+        &self.schema_path
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}[{}]: schema path: '{}', message: '{}'",
+            self.severity.as_ref(),
+            self.code.as_ref(),
+            self.schema_path,
+            self.message
+        )
+    }
+}
+
+fn join(path: &str, component: &str) -> String {
+    if path.is_empty() {
+        component.to_string()
+    } else {
+        format!("{}.{}", path, component)
+    }
+}
+
+fn is_number_based(primitive_type: &PrimitiveType) -> bool {
+    matches!(
+        primitive_type,
+        PrimitiveType::Integer | PrimitiveType::Number | PrimitiveType::Port
+    )
+}
+
+fn is_array_based(primitive_type: &PrimitiveType) -> bool {
+    matches!(primitive_type, PrimitiveType::Array | PrimitiveType::StringList)
+}
+
+fn is_string_based(primitive_type: &PrimitiveType) -> bool {
+    !is_number_based(primitive_type)
+        && !is_array_based(primitive_type)
+        && !matches!(
+            primitive_type,
+            PrimitiveType::Object | PrimitiveType::Boolean | PrimitiveType::File
+        )
+}
+
+struct Linter<'a> {
+    root: &'a Schema,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl<'a> Linter<'a> {
+    fn push<S>(&mut self, severity: Severity, code: Code, schema_path: String, message: S)
+    where
+        S: Into<String>,
+    {
+        self.diagnostics.push(Diagnostic {
+            severity,
+            code,
+            schema_path,
+            message: message.into(),
+        });
+    }
+
+    fn lint(&mut self, schema: &'a Schema, path: &str) {
+        self.lint_condition(schema, path);
+
+        // All other keywords are ignored, definitions are linted separately
+        if schema.r#ref().is_some() {
+            return;
+        }
+
+        self.lint_ranges(schema, path);
+        self.lint_keywords(schema, path);
+        self.lint_values(schema, path);
+        self.lint_mapping(schema, path);
+
+        if schema.keys().is_some() && schema.values().is_none() {
+            self.push(
+                Severity::Warning,
+                Code::KeysWithoutValues,
+                join(path, "keys"),
+                "keys schema is ignored without the values schema",
+            );
+        }
+
+        self.lint_children(schema, path);
+    }
+
+    fn lint_children(&mut self, schema: &'a Schema, path: &str) {
+        for (name, definition) in schema.definitions() {
+            self.lint(definition, &join(path, &format!("definitions.{}", name)));
+        }
+
+        for (index, property) in schema.properties().iter().enumerate() {
+            let path = join(path, &format!("properties[{}].{}", index, property.name()));
+            self.lint(property.schema(), &path);
+        }
+
+        if let Some(keys) = schema.keys() {
+            self.lint(keys, &join(path, "keys"));
+        }
+
+        if let Some(values) = schema.values() {
+            self.lint(values, &join(path, "values"));
+        }
+
+        match schema.items() {
+            [item] => self.lint(item, &join(path, "items")),
+            items => {
+                for (index, item) in items.iter().enumerate() {
+                    self.lint(item, &join(path, &format!("items[{}]", index)));
+                }
+            }
+        };
+
+        for (keyword, schemas) in &[
+            ("oneOf", schema.one_of()),
+            ("anyOf", schema.any_of()),
+            ("allOf", schema.all_of()),
+        ] {
+            for (index, nested) in schemas.iter().enumerate() {
+                self.lint(nested, &join(path, &format!("{}[{}]", keyword, index)));
+            }
+        }
+    }
+
+    fn lint_range<T>(&mut self, path: &str, lower: (&str, Option<T>), upper: (&str, Option<T>), allow_equal: bool)
+    where
+        T: PartialOrd + fmt::Display,
+    {
+        if let ((lower_keyword, Some(lower)), (upper_keyword, Some(upper))) = (lower, upper) {
+            if lower > upper || (!allow_equal && lower == upper) {
+                self.push(
+                    Severity::Error,
+                    Code::InvertedRange,
+                    join(path, lower_keyword),
+                    format!(
+                        "{} ({}) does not allow any value with {} ({})",
+                        lower_keyword, lower, upper_keyword, upper
+                    ),
+                );
+            }
+        }
+    }
+
+    fn lint_ranges(&mut self, schema: &Schema, path: &str) {
+        let number = |x: Option<&Number>| x.and_then(Number::as_f64);
+
+        self.lint_range(path, ("min", number(schema.min())), ("max", number(schema.max())), true);
+        self.lint_range(
+            path,
+            ("exclusiveMin", number(schema.exclusive_min())),
+            ("exclusiveMax", number(schema.exclusive_max())),
+            false,
+        );
+        self.lint_range(
+            path,
+            ("minLength", schema.min_length()),
+            ("maxLength", schema.max_length()),
+            true,
+        );
+        self.lint_range(
+            path,
+            ("minItems", schema.min_items()),
+            ("maxItems", schema.max_items()),
+            true,
+        );
+    }
+
+    fn lint_keywords(&mut self, schema: &Schema, path: &str) {
+        let primitive_type = &schema.r#type().primitive_type().base_type();
+
+        let keywords = [
+            ("min", schema.min().is_some(), is_number_based(primitive_type)),
+            ("max", schema.max().is_some(), is_number_based(primitive_type)),
+            (
+                "exclusiveMin",
+                schema.exclusive_min().is_some(),
+                is_number_based(primitive_type),
+            ),
+            (
+                "exclusiveMax",
+                schema.exclusive_max().is_some(),
+                is_number_based(primitive_type),
+            ),
+            (
+                "multipleOf",
+                schema.multiple_of().is_some(),
+                is_number_based(primitive_type),
+            ),
+            (
+                "minLength",
+                schema.min_length().is_some(),
+                is_string_based(primitive_type),
+            ),
+            (
+                "maxLength",
+                schema.max_length().is_some(),
+                is_string_based(primitive_type),
+            ),
+            ("pattern", schema.pattern().is_some(), is_string_based(primitive_type)),
+            ("items", !schema.items().is_empty(), is_array_based(primitive_type)),
+            ("minItems", schema.min_items().is_some(), is_array_based(primitive_type)),
+            ("maxItems", schema.max_items().is_some(), is_array_based(primitive_type)),
+            (
+                "uniqueItems",
+                schema.unique_items() != &UniqueItems::default(),
+                is_array_based(primitive_type),
+            ),
+            (
+                "properties",
+                !schema.properties().is_empty(),
+                primitive_type == &PrimitiveType::Object,
+            ),
+            (
+                "keys",
+                schema.keys().is_some(),
+                primitive_type == &PrimitiveType::Object,
+            ),
+            (
+                "values",
+                schema.values().is_some(),
+                primitive_type == &PrimitiveType::Object,
+            ),
+            (
+                "separator",
+                schema.separator().is_some(),
+                primitive_type == &PrimitiveType::StringList,
+            ),
+            (
+                "strictMask",
+                schema.strict_mask(),
+                primitive_type == &PrimitiveType::IPTablesAddress,
+            ),
+        ];
+
+        for (keyword, is_present, is_applicable) in keywords.iter() {
+            if *is_present && !is_applicable {
+                self.push(
+                    Severity::Warning,
+                    Code::InapplicableKeyword,
+                    join(path, keyword),
+                    format!(
+                        "{} is ignored for the '{}' type",
+                        keyword,
+                        schema.r#type().primitive_type()
+                    ),
+                );
+            }
+        }
+    }
+
+    fn lint_values(&mut self, schema: &Schema, path: &str) {
+        if let Some(default) = schema.r#default() {
+            if let Some(error) = validate_with_root(self.root, schema, default).errors().first() {
+                self.push(
+                    Severity::Error,
+                    Code::InvalidDefault,
+                    join(path, "default"),
+                    format!("default value is not valid: {}", error.message()),
+                );
+            }
+        }
+
+        if let Some(constant) = schema.r#const() {
+            if !schema.r#enum().is_empty() && !schema.r#enum().iter().any(|x| x.value() == constant) {
+                self.push(
+                    Severity::Error,
+                    Code::ConstOutsideEnum,
+                    join(path, "const"),
+                    "const value is not one of the enum values",
+                );
+            }
+        }
+    }
+
+    // Invalid conditions are rejected by the deserializer
+    fn lint_condition(&mut self, schema: &Schema, path: &str) {
+        if let Some(condition) = schema.when() {
+            if condition_dependency(condition).is_none() {
+                self.push(
+                    Severity::Warning,
+                    Code::UnsupportedCondition,
+                    join(path, "when"),
+                    format!(
+                        "condition '{}' can't be expressed in the JSON Schema, only `sibling` and \
+                         `sibling == literal` can, the property is optional there",
+                        condition
+                    ),
+                );
+            }
+        }
+    }
+
+    fn lint_mapping(&mut self, schema: &Schema, path: &str) {
+        let reference = match schema.mapping().and_then(Mapping::target).and_then(|x| x.reference()) {
+            Some(x) => x,
+            None => return,
+        };
+
+        let is_defined = self
+            .root
+            .mapping()
+            .map(|x| x.targets().contains_key(reference))
+            .unwrap_or(false);
+
+        if !is_defined {
+            self.push(
+                Severity::Error,
+                Code::UndefinedTarget,
+                join(path, "mapping.target"),
+                format!("target '{}' is not defined in the root mapping.targets", reference),
+            );
+        }
+    }
+}
+
+/// Lints the schema
+///
+/// Returns diagnostics in the schema order, an empty vector if there's nothing to report.
+///
+/// # Arguments
+///
+/// * `schema` - JellySchema
+pub fn lint(schema: &Schema) -> Vec<Diagnostic> {
+    let mut linter = Linter {
+        root: schema,
+        diagnostics: vec![],
+    };
+    linter.lint(schema, "");
+    linter.diagnostics
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BALENA_OS: &str = include_str!("../../fuzz/seeds/balena-os.yml");
+
+    fn lint_str(schema: &str) -> Vec<Diagnostic> {
+        lint(&schema.parse::<Schema>().unwrap())
+    }
+
+    fn codes(schema: &str) -> Vec<(Code, String)> {
+        lint_str(schema)
+            .into_iter()
+            .map(|x| (x.code(), x.schema_path().to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn balena_os() {
+        assert_eq!(codes(BALENA_OS), vec![]);
+    }
+
+    #[test]
+    fn inverted_ranges() {
+        let schema = r#"
+            properties:
+                - retries:
+                    type: integer
+                    min: 10
+                    max: 5
+                - ratio:
+                    type: number
+                    exclusiveMin: 1
+                    exclusiveMax: 1
+                - name:
+                    type: string
+                    minLength: 3
+                    maxLength: 3
+                - tags:
+                    type: array
+                    minItems: 2
+                    maxItems: 1
+        "#;
+        assert_eq!(
+            codes(schema),
+            vec![
+                (Code::InvertedRange, "properties[0].retries.min".to_string()),
+                (Code::InvertedRange, "properties[1].ratio.exclusiveMin".to_string()),
+                (Code::InvertedRange, "properties[3].tags.minItems".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn inapplicable_keywords() {
+        let schema = r#"
+            properties:
+                - retries:
+                    type: integer
+                    minLength: 1
+                    pattern: "^[0-9]+$"
+                - name:
+                    type: string
+                    separator: ","
+                    max: 3
+                    strictMask: true
+                - servers:
+                    type: stringlist
+                    separator: ","
+                    minItems: 1
+                - whitelist:
+                    type: iptables-address
+                    strictMask: true
+        "#;
+        assert_eq!(
+            codes(schema),
+            vec![
+                (Code::InapplicableKeyword, "properties[0].retries.minLength".to_string()),
+                (Code::InapplicableKeyword, "properties[0].retries.pattern".to_string()),
+                (Code::InapplicableKeyword, "properties[1].name.max".to_string()),
+                (Code::InapplicableKeyword, "properties[1].name.separator".to_string()),
+                (Code::InapplicableKeyword, "properties[1].name.strictMask".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_default() {
+        let schema = r##"
+            definitions:
+                port:
+                    type: port
+                    default: 80
+            properties:
+                - name:
+                    type: string
+                    minLength: 3
+                    default: ab
+                - port:
+                    $ref: "#/definitions/port"
+                - mode:
+                    type: string
+                    enum:
+                        - a
+                        - b
+                    default: c
+        "##;
+        assert_eq!(
+            codes(schema),
+            vec![
+                (Code::InvalidDefault, "properties[0].name.default".to_string()),
+                (Code::InvalidDefault, "properties[2].mode.default".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn const_outside_enum() {
+        let schema = r#"
+            properties:
+                - mode:
+                    type: string
+                    const: c
+                    enum:
+                        - a
+                        - b
+                - other:
+                    type: string
+                    const: c
+        "#;
+        assert_eq!(
+            codes(schema),
+            vec![(Code::ConstOutsideEnum, "properties[0].mode.const".to_string())]
+        );
+    }
+
+    #[test]
+    fn keys_without_values() {
+        let schema = r#"
+            properties:
+                - rules:
+                    keys:
+                        type: string
+        "#;
+        assert_eq!(
+            codes(schema),
+            vec![(Code::KeysWithoutValues, "properties[0].rules.keys".to_string())]
+        );
+    }
+
+    #[test]
+    fn undefined_target() {
+        let schema = r#"
+            mapping:
+                targets:
+                    config:
+                        type: file
+                        format: json
+                        location:
+                            partition: boot
+                            path: /config.json
+            properties:
+                - hostname:
+                    type: hostname
+                    mapping:
+                        target: config
+                        path: hostname
+                - ssid:
+                    type: string
+                    mapping:
+                        target: network
+                        path: wifi.ssid
+        "#;
+        let diagnostics = lint_str(schema);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].severity(), Severity::Error);
+        assert_eq!(diagnostics[0].code(), Code::UndefinedTarget);
+        assert_eq!(diagnostics[0].schema_path(), "properties[1].ssid.mapping.target");
+    }
+
+    #[test]
+    fn unsupported_condition() {
+        let schema = r#"
+            properties:
+                - proxyType:
+                    type: string
+                - authenticate:
+                    type: boolean
+                - server:
+                    type: hostname
+                    when: proxyType == "socks5"
+                - login:
+                    type: string
+                    when: authenticate
+                - password:
+                    type: password
+                    when: authenticate and proxyType == "socks5"
+        "#;
+        assert_eq!(
+            codes(schema),
+            vec![(Code::UnsupportedCondition, "properties[4].password.when".to_string())]
+        );
+    }
+
+    #[test]
+    fn nested_schemas() {
+        let schema = r#"
+            type: array
+            items:
+                properties:
+                    - a:
+                        type: string
+                        oneOf:
+                            - minLength: 5
+                              maxLength: 1
+        "#;
+        assert_eq!(
+            codes(schema),
+            vec![(
+                Code::InvertedRange,
+                "items.properties[0].a.oneOf[0].minLength".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn serialize_diagnostic() {
+        let diagnostics = lint_str("properties:\n  - a:\n      keys:\n        type: string");
+        assert_eq!(
+            serde_json::to_value(&diagnostics[0]).unwrap(),
+            serde_json::json!({
+                "severity": "warning",
+                "code": "keys-without-values",
+                "schemaPath": "properties[0].a.keys",
+                "message": "keys schema is ignored without the values schema"
+            })
+        );
+    }
+}
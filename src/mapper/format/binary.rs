@@ -0,0 +1,85 @@
+use base64::Engine;
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde_json::Value;
+
+use crate::{error::Error, schema::mapping::TargetLocation};
+
+lazy_static! {
+    // data:image/png;name=logo.png;base64,aGV...
+    static ref DATA_URL_REGEX: Regex = Regex::new(r"^data:.*;base64,(.*)$").unwrap();
+}
+
+pub(crate) fn serialize(document: &Value) -> Result<Vec<u8>, Error> {
+    let data = document
+        .as_str()
+        .and_then(|x| DATA_URL_REGEX.captures(x))
+        .ok_or_else(|| Error::message("binary document must be a base64 data url"))?;
+
+    base64::engine::general_purpose::STANDARD
+        .decode(&data[1])
+        .map_err(|e| Error::message(format!("unable to decode file data: {}", e)))
+}
+
+// MIME type of the file, the original one is lost when the data url is rendered
+fn mime_type(name: &str) -> &'static str {
+    let extension = match name.rfind('.') {
+        Some(index) => name[index + 1..].to_lowercase(),
+        None => return "application/octet-stream",
+    };
+
+    match extension.as_str() {
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "bmp" => "image/bmp",
+        "svg" => "image/svg+xml",
+        "ico" => "image/x-icon",
+        "txt" => "text/plain",
+        "json" => "application/json",
+        _ => "application/octet-stream",
+    }
+}
+
+// The file name in the data url is the target file name
+pub(crate) fn deserialize(location: &TargetLocation, content: &[u8]) -> Result<Value, Error> {
+    let name = location.path().rsplit('/').next().unwrap_or_default();
+
+    Ok(Value::String(format!(
+        "data:{};name={};base64,{}",
+        mime_type(name),
+        name,
+        base64::engine::general_purpose::STANDARD.encode(content)
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::schema::mapping::LocationPartition;
+
+    #[test]
+    fn round_trip() {
+        let location = TargetLocation::new(LocationPartition::Index(1), "/splash/logo.png");
+        let document = deserialize(&location, b"\x89PNG").unwrap();
+        assert_eq!(document, json!("data:image/png;name=logo.png;base64,iVBORw=="));
+        assert_eq!(serialize(&document).unwrap(), b"\x89PNG".to_vec());
+    }
+
+    #[test]
+    fn infer_mime_type() {
+        assert_eq!(mime_type("logo.PNG"), "image/png");
+        assert_eq!(mime_type("logo.jpeg"), "image/jpeg");
+        assert_eq!(mime_type("no_proxy"), "application/octet-stream");
+        assert_eq!(mime_type("overlay.dtbo"), "application/octet-stream");
+    }
+
+    #[test]
+    fn fail_on_invalid_data_url() {
+        assert!(serialize(&json!("foo")).is_err());
+        assert!(serialize(&json!("data:text/plain;name=a.txt;base64,!!!")).is_err());
+        assert!(serialize(&json!(10)).is_err());
+    }
+}
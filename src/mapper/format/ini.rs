@@ -0,0 +1,400 @@
+use std::collections::HashSet;
+
+use serde_json::{Map, Value};
+
+use super::ordered;
+use crate::error::Error;
+
+enum Line {
+    // Blank line or comment
+    Other(String),
+    Section(String, String),
+    Entry(String, String, String),
+}
+
+fn parse(content: &[u8]) -> Result<Vec<Line>, Error> {
+    let content = std::str::from_utf8(content).map_err(|e| Error::message(format!("invalid ini file: {}", e)))?;
+
+    let mut lines = vec![];
+
+    for (index, raw) in content.lines().enumerate() {
+        let line = raw.trim();
+
+        if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+            lines.push(Line::Other(raw.to_string()));
+        } else if line.starts_with('[') && line.ends_with(']') {
+            let name = line[1..line.len() - 1].trim();
+            lines.push(Line::Section(name.to_string(), raw.to_string()));
+        } else if let Some(index) = line.find('=') {
+            let key = line[..index].trim();
+            let value = line[index + 1..].trim();
+            lines.push(Line::Entry(key.to_string(), value.to_string(), raw.to_string()));
+        } else {
+            return Err(Error::message(format!("invalid ini file, line {}: {}", index + 1, raw)));
+        }
+    }
+
+    Ok(lines)
+}
+
+fn value_to_string(key: &str, value: &Value) -> Result<Option<String>, Error> {
+    match value {
+        Value::Null => Ok(None),
+        Value::String(s) => Ok(Some(s.clone())),
+        Value::Bool(b) => Ok(Some(b.to_string())),
+        Value::Number(n) => Ok(Some(n.to_string())),
+        _ => Err(Error::message(format!(
+            "unable to serialize '{}' into ini, only primitive values are supported",
+            key
+        ))),
+    }
+}
+
+type Entries<'a> = Vec<(&'a String, &'a Value)>;
+
+// Optional section name and header line followed by the section lines
+type Block = (Option<(String, String)>, Vec<Line>);
+
+struct Document<'a> {
+    globals: Entries<'a>,
+    sections: Vec<(&'a String, Entries<'a>)>,
+    mapped: &'a HashSet<String>,
+}
+
+impl<'a> Document<'a> {
+    fn new(document: &'a Value, order: &[String], mapped: &'a HashSet<String>) -> Result<Document<'a>, Error> {
+        let document = document
+            .as_object()
+            .ok_or_else(|| Error::message("ini document must be an object"))?;
+
+        let globals = ordered(document, "", order)
+            .into_iter()
+            .filter(|(_, value)| !value.is_object())
+            .collect();
+
+        let sections = ordered(document, "", order)
+            .into_iter()
+            .filter_map(|(name, value)| Some((name, ordered(value.as_object()?, name, order))))
+            .collect();
+
+        Ok(Document {
+            globals,
+            sections,
+            mapped,
+        })
+    }
+
+    fn section(&self, name: Option<&str>) -> Option<&[(&'a String, &'a Value)]> {
+        match name {
+            Some(name) => self
+                .sections
+                .iter()
+                .find(|(x, _)| x.as_str() == name)
+                .map(|(_, entries)| entries.as_slice()),
+            None => Some(&self.globals),
+        }
+    }
+
+    fn value(&self, section: Option<&str>, key: &str) -> Result<Option<String>, Error> {
+        match self
+            .section(section)
+            .and_then(|x| x.iter().find(|(x, _)| x.as_str() == key))
+        {
+            Some((_, value)) => value_to_string(key, value),
+            None => Ok(None),
+        }
+    }
+
+    // Checks if the dotted path or any of its parents is mapped by the schema
+    fn is_mapped(&self, path: &str) -> bool {
+        self.mapped.contains("")
+            || self.mapped.contains(path)
+            || path
+                .match_indices('.')
+                .any(|(index, _)| self.mapped.contains(&path[..index]))
+    }
+}
+
+fn entry_path(section: Option<&str>, key: &str) -> String {
+    match section {
+        Some(section) => format!("{}.{}", section, key),
+        None => key.to_string(),
+    }
+}
+
+// Renders the document into the existing ini file lines
+//
+// Comments, blank lines, unchanged entries and entries the schema does not
+// map are kept as they are. Mapped entries not present in the document are
+// removed, sections are removed if they are mapped as a whole or if all their
+// entries were removed. New entries are appended to the end of their section,
+// new sections to the end of the file.
+fn render(lines: Vec<Line>, document: &Document) -> Result<Vec<u8>, Error> {
+    // Globals followed by sections, each with its own lines
+    let mut blocks: Vec<Block> = vec![(None, vec![])];
+    for line in lines {
+        match line {
+            Line::Section(name, raw) => blocks.push((Some((name, raw)), vec![])),
+            line => blocks.last_mut().unwrap().1.push(line),
+        };
+    }
+
+    let mut output: Vec<String> = vec![];
+    let mut written_sections = HashSet::new();
+    let mut written_keys = HashSet::new();
+
+    for (header, lines) in blocks {
+        let name = header.as_ref().map(|(name, _)| name.as_str());
+        let first = name.map(|x| written_sections.insert(x.to_string())).unwrap_or(true);
+
+        let mut block = vec![];
+        let mut insert_at = 0;
+        let mut kept = 0;
+        let mut removed = false;
+
+        for line in lines {
+            match line {
+                Line::Entry(key, value, raw) => {
+                    let path = entry_path(name, &key);
+                    if written_keys.contains(&path) {
+                        continue;
+                    }
+
+                    match document.value(name, &key)? {
+                        Some(new_value) => {
+                            if new_value == value {
+                                block.push(raw);
+                            } else {
+                                block.push(format!("{}={}", key, new_value));
+                            }
+                            written_keys.insert(path);
+                        }
+                        None if document.is_mapped(&path) => {
+                            removed = true;
+                            continue;
+                        }
+                        None => block.push(raw),
+                    };
+
+                    kept += 1;
+                    insert_at = block.len();
+                }
+                Line::Other(raw) | Line::Section(_, raw) => block.push(raw),
+            };
+        }
+
+        if first {
+            for (key, value) in document.section(name).unwrap_or_default() {
+                let path = entry_path(name, key);
+                if written_keys.contains(&path) {
+                    continue;
+                }
+                if let Some(value) = value_to_string(key, value)? {
+                    block.insert(insert_at, format!("{}={}", key, value));
+                    insert_at += 1;
+                    kept += 1;
+                    written_keys.insert(path);
+                }
+            }
+        }
+
+        match header {
+            None => output.extend(block),
+            Some((name, raw)) => {
+                let keep = kept > 0
+                    || (first && document.section(Some(&name)).is_some())
+                    || (first && !removed && !document.is_mapped(&name));
+
+                if keep {
+                    output.push(raw);
+                    output.extend(block);
+                }
+            }
+        };
+    }
+
+    for (name, entries) in &document.sections {
+        if written_sections.contains(name.as_str()) {
+            continue;
+        }
+
+        if output.last().map(|x| !x.trim().is_empty()).unwrap_or(false) {
+            output.push(String::new());
+        }
+
+        output.push(format!("[{}]", name));
+        for (key, value) in entries {
+            if let Some(value) = value_to_string(key, value)? {
+                output.push(format!("{}={}", key, value));
+            }
+        }
+    }
+
+    let mut content = output.join("\n");
+    content.push('\n');
+    Ok(content.into_bytes())
+}
+
+pub(crate) fn serialize(
+    document: &Value,
+    existing: Option<&[u8]>,
+    order: &[String],
+    mapped: &HashSet<String>,
+) -> Result<Vec<u8>, Error> {
+    let document = Document::new(document, order, mapped)?;
+    let lines = match existing {
+        Some(existing) => parse(existing)?,
+        None => vec![],
+    };
+    render(lines, &document)
+}
+
+pub(crate) fn deserialize(content: &[u8]) -> Result<Value, Error> {
+    let mut result = Map::new();
+    let mut current: Option<String> = None;
+
+    for line in parse(content)? {
+        match line {
+            Line::Section(name, _) => {
+                if !result.get(&name).map(Value::is_object).unwrap_or(false) {
+                    result.insert(name.clone(), Value::Object(Map::new()));
+                }
+                current = Some(name);
+            }
+            Line::Entry(key, value, _) => {
+                let map = match &current {
+                    Some(section) => result.get_mut(section).and_then(Value::as_object_mut).unwrap(),
+                    None => &mut result,
+                };
+                map.insert(key, Value::String(value));
+            }
+            Line::Other(_) => {}
+        };
+    }
+
+    Ok(Value::Object(result))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn paths(paths: &[&str]) -> Vec<String> {
+        paths.iter().map(|x| x.to_string()).collect()
+    }
+
+    fn serialize_str(document: Value, existing: Option<&str>) -> String {
+        serialize_mapped(document, existing, &[], &[])
+    }
+
+    fn serialize_mapped(document: Value, existing: Option<&str>, order: &[&str], mapped: &[&str]) -> String {
+        let mapped = paths(mapped).into_iter().collect();
+        let content = serialize(&document, existing.map(str::as_bytes), &paths(order), &mapped).unwrap();
+        String::from_utf8(content).unwrap()
+    }
+
+    #[test]
+    fn sections_and_globals() {
+        let document = json!({
+            "version": 1,
+            "connection": {
+                "id": "home",
+                "type": "wifi"
+            },
+            "wifi-security": {
+                "psk": "secret"
+            }
+        });
+        assert_eq!(
+            serialize_str(document, None),
+            "version=1\n\n[connection]\nid=home\ntype=wifi\n\n[wifi-security]\npsk=secret\n"
+        );
+    }
+
+    #[test]
+    fn preserve_key_order() {
+        let document = json!({
+            "wifi": {"ssid": "home", "mode": "infrastructure", "hidden": true},
+            "connection": {"id": "home"}
+        });
+        assert_eq!(
+            serialize_mapped(
+                document,
+                None,
+                &["wifi.ssid", "wifi.mode", "connection.id", "wifi.hidden"],
+                &[]
+            ),
+            "[wifi]\nssid=home\nmode=infrastructure\nhidden=true\n\n[connection]\nid=home\n"
+        );
+    }
+
+    #[test]
+    fn round_trip_comments() {
+        let existing =
+            "# Managed file\n[connection]\n# Connection name\nid = home\nuuid=1234\n\n; removed\n[ipv4]\nmethod=auto\n";
+        let document = json!({
+            "connection": {
+                "id": "home",
+                "type": "wifi"
+            },
+            "wifi": {
+                "ssid": "home"
+            }
+        });
+        assert_eq!(
+            serialize_mapped(
+                document,
+                Some(existing),
+                &[],
+                &["connection.id", "connection.type", "wifi.ssid", "ipv4"]
+            ),
+            "# Managed file\n[connection]\n# Connection name\nid = home\nuuid=1234\ntype=wifi\n\n; removed\n\n[wifi]\nssid=home\n"
+        );
+    }
+
+    #[test]
+    fn remove_mapped_entries() {
+        let existing = "version=1\n[connection]\nid=home\nuuid=1234\n[wifi]\n# ssid\nssid=old\n[ipv6]\nmethod=auto\n";
+        let document = json!({"connection": {"id": "home"}});
+        assert_eq!(
+            serialize_mapped(
+                document,
+                Some(existing),
+                &[],
+                &["version", "connection.id", "connection.uuid", "wifi.ssid"]
+            ),
+            "[connection]\nid=home\n[ipv6]\nmethod=auto\n"
+        );
+    }
+
+    #[test]
+    fn update_values() {
+        let existing = "[wifi]\nssid = old\n# mode\nmode=infrastructure\n";
+        let document = json!({"wifi": {"ssid": "new", "mode": "infrastructure"}});
+        assert_eq!(
+            serialize_str(document, Some(existing)),
+            "[wifi]\nssid=new\n# mode\nmode=infrastructure\n"
+        );
+    }
+
+    #[test]
+    fn deserialize_sections() {
+        let content = b"version=1\n# comment\n[connection]\nid = home\n[wifi]\n";
+        assert_eq!(
+            deserialize(content).unwrap(),
+            json!({"version": "1", "connection": {"id": "home"}, "wifi": {}})
+        );
+    }
+
+    #[test]
+    fn fail_on_nested_objects() {
+        assert!(serialize(&json!({"a": {"b": {"c": 1}}}), None, &[], &HashSet::new()).is_err());
+    }
+
+    #[test]
+    fn fail_on_invalid_line() {
+        assert!(deserialize(b"[section]\ninvalid\n").is_err());
+    }
+}
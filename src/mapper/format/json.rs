@@ -0,0 +1,13 @@
+use serde_json::Value;
+
+use crate::error::Error;
+
+pub(crate) fn serialize(document: &Value) -> Result<Vec<u8>, Error> {
+    let mut content = serde_json::to_vec_pretty(document)?;
+    content.push(b'\n');
+    Ok(content)
+}
+
+pub(crate) fn deserialize(content: &[u8]) -> Result<Value, Error> {
+    Ok(serde_json::from_slice(content)?)
+}
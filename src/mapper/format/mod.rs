@@ -0,0 +1,72 @@
+use std::collections::HashSet;
+
+use serde_json::{Map, Value};
+
+use crate::{
+    error::Error,
+    schema::mapping::{TargetFormat, TargetLocation},
+};
+
+mod binary;
+mod ini;
+mod json;
+mod redsocks;
+mod text;
+
+/// Serializes target document into the target format
+///
+/// Formats supporting comments keep them from the `existing` file content. The
+/// `order` contains dotted paths of the document values in the order they were
+/// written, existing values at the `mapped` dotted paths are replaced by the
+/// document ones.
+pub(crate) fn serialize(
+    format: TargetFormat,
+    document: &Value,
+    existing: Option<&[u8]>,
+    order: &[String],
+    mapped: &HashSet<String>,
+) -> Result<Vec<u8>, Error> {
+    match format {
+        TargetFormat::Json => json::serialize(document),
+        TargetFormat::Ini => ini::serialize(document, existing, order, mapped),
+        TargetFormat::Redsocks => redsocks::serialize(document, order),
+        TargetFormat::Text => text::serialize(document),
+        TargetFormat::Binary => binary::serialize(document),
+    }
+}
+
+/// Orders object entries by the position of their first written value in the `order`
+///
+/// The `path` is a dotted path of the object, entries without any written value
+/// are ordered by their keys after the other ones.
+pub(crate) fn ordered<'a>(
+    object: &'a Map<String, Value>,
+    path: &str,
+    order: &[String],
+) -> Vec<(&'a String, &'a Value)> {
+    let rank = |key: &str| {
+        let path = match path {
+            "" => key.to_string(),
+            path => format!("{}.{}", path, key),
+        };
+        order
+            .iter()
+            .position(|x| x == &path || (x.starts_with(&path) && x[path.len()..].starts_with('.')))
+            .unwrap_or(usize::MAX)
+    };
+
+    let mut entries: Vec<_> = object.iter().collect();
+    entries.sort_by_cached_key(|(key, _)| (rank(key), key.to_string()));
+    entries
+}
+
+/// Deserializes target document from the target format
+pub(crate) fn deserialize(format: TargetFormat, location: &TargetLocation, content: &[u8]) -> Result<Value, Error> {
+    match format {
+        TargetFormat::Json => json::deserialize(content),
+        TargetFormat::Ini => ini::deserialize(content),
+        TargetFormat::Redsocks => redsocks::deserialize(content),
+        TargetFormat::Text => text::deserialize(content),
+        TargetFormat::Binary => binary::deserialize(location, content),
+    }
+}
@@ -0,0 +1,288 @@
+//! redsocks.conf format
+//!
+//! ```text
+//! base {
+//!     log = stderr;
+//! }
+//!
+//! redsocks {
+//!     type = socks5;
+//!     ip = 10.0.0.1;
+//!     port = 1080;
+//! }
+//! ```
+//!
+//! Top level document keys are sections, section values are primitive values. An array
+//! of objects is rendered as a repeated section (multiple `redsocks` sections for example).
+use serde_json::{Map, Value};
+
+use super::ordered;
+use crate::error::Error;
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Word(String),
+    Quoted(String),
+    OpenBrace,
+    CloseBrace,
+    Equal,
+    Semicolon,
+}
+
+fn tokenize(content: &str) -> Result<Vec<Token>, Error> {
+    let mut tokens = vec![];
+    let mut chars = content.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '{' => tokens.push(Token::OpenBrace),
+            '}' => tokens.push(Token::CloseBrace),
+            '=' => tokens.push(Token::Equal),
+            ';' => tokens.push(Token::Semicolon),
+            '#' => {
+                chars.by_ref().find(|x| *x == '\n');
+            }
+            '/' if chars.peek() == Some(&'/') => {
+                chars.by_ref().find(|x| *x == '\n');
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut previous = None;
+                loop {
+                    match chars.next() {
+                        Some('/') if previous == Some('*') => break,
+                        Some(x) => previous = Some(x),
+                        None => return Err(Error::message("invalid redsocks file, unterminated comment")),
+                    }
+                }
+            }
+            '"' => {
+                let mut value = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(x) => value.push(x),
+                            None => return Err(Error::message("invalid redsocks file, unterminated string")),
+                        },
+                        Some(x) => value.push(x),
+                        None => return Err(Error::message("invalid redsocks file, unterminated string")),
+                    }
+                }
+                tokens.push(Token::Quoted(value));
+            }
+            c if c.is_whitespace() => {}
+            c => {
+                let mut value = c.to_string();
+                while let Some(&x) = chars.peek() {
+                    if x.is_whitespace() || "{}=;\"".contains(x) {
+                        break;
+                    }
+                    value.push(x);
+                    chars.next();
+                }
+                tokens.push(Token::Word(value));
+            }
+        };
+    }
+
+    Ok(tokens)
+}
+
+fn expect(tokens: &mut impl Iterator<Item = Token>, expected: Token) -> Result<(), Error> {
+    match tokens.next() {
+        Some(ref token) if *token == expected => Ok(()),
+        token => Err(Error::message(format!(
+            "invalid redsocks file, expected {:?}, got {:?}",
+            expected, token
+        ))),
+    }
+}
+
+fn parse_section(tokens: &mut impl Iterator<Item = Token>) -> Result<Map<String, Value>, Error> {
+    let mut section = Map::new();
+
+    loop {
+        let key = match tokens.next() {
+            Some(Token::CloseBrace) => return Ok(section),
+            Some(Token::Word(key)) => key,
+            token => return Err(Error::message(format!("invalid redsocks file, unexpected {:?}", token))),
+        };
+
+        expect(tokens, Token::Equal)?;
+
+        let value = match tokens.next() {
+            Some(Token::Word(value)) | Some(Token::Quoted(value)) => value,
+            token => return Err(Error::message(format!("invalid redsocks file, unexpected {:?}", token))),
+        };
+
+        expect(tokens, Token::Semicolon)?;
+        section.insert(key, Value::String(value));
+    }
+}
+
+fn is_bare(value: &str) -> bool {
+    !value.is_empty() && value.chars().all(|x| x.is_ascii_alphanumeric() || "_-.:/".contains(x))
+}
+
+fn serialize_value(key: &str, value: &Value) -> Result<Option<String>, Error> {
+    match value {
+        Value::Null => Ok(None),
+        Value::Bool(b) => Ok(Some(if *b { "on" } else { "off" }.to_string())),
+        Value::Number(n) => Ok(Some(n.to_string())),
+        Value::String(s) if is_bare(s) => Ok(Some(s.clone())),
+        Value::String(s) => Ok(Some(format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\"")))),
+        _ => Err(Error::message(format!(
+            "unable to serialize '{}' into redsocks, only primitive values are supported",
+            key
+        ))),
+    }
+}
+
+fn serialize_section(name: &str, section: &Value, order: &[String], output: &mut String) -> Result<(), Error> {
+    let section = section
+        .as_object()
+        .ok_or_else(|| Error::message(format!("redsocks section '{}' must be an object", name)))?;
+
+    if !output.is_empty() {
+        output.push('\n');
+    }
+
+    output.push_str(name);
+    output.push_str(" {\n");
+
+    for (key, value) in ordered(section, name, order) {
+        if let Some(value) = serialize_value(key, value)? {
+            output.push_str(&format!("\t{} = {};\n", key, value));
+        }
+    }
+
+    output.push_str("}\n");
+    Ok(())
+}
+
+pub(crate) fn serialize(document: &Value, order: &[String]) -> Result<Vec<u8>, Error> {
+    let document = document
+        .as_object()
+        .ok_or_else(|| Error::message("redsocks document must be an object"))?;
+
+    let mut output = String::new();
+
+    for (name, value) in ordered(document, "", order) {
+        match value {
+            Value::Array(sections) => {
+                for section in sections {
+                    serialize_section(name, section, order, &mut output)?;
+                }
+            }
+            section => serialize_section(name, section, order, &mut output)?,
+        };
+    }
+
+    Ok(output.into_bytes())
+}
+
+pub(crate) fn deserialize(content: &[u8]) -> Result<Value, Error> {
+    let content = std::str::from_utf8(content).map_err(|e| Error::message(format!("invalid redsocks file: {}", e)))?;
+    let mut tokens = tokenize(content)?.into_iter();
+    let mut result = Map::new();
+
+    while let Some(token) = tokens.next() {
+        let name = match token {
+            Token::Word(name) => name,
+            token => return Err(Error::message(format!("invalid redsocks file, unexpected {:?}", token))),
+        };
+
+        expect(&mut tokens, Token::OpenBrace)?;
+        let section = Value::Object(parse_section(&mut tokens)?);
+
+        // Repeated sections are collected into an array
+        match result.get_mut(&name) {
+            Some(Value::Array(sections)) => sections.push(section),
+            Some(existing) => {
+                let first = existing.take();
+                *existing = Value::Array(vec![first, section]);
+            }
+            None => {
+                result.insert(name, section);
+            }
+        };
+    }
+
+    Ok(Value::Object(result))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn serialize_sections() {
+        let document = json!({
+            "base": {
+                "log": "stderr",
+                "log_debug": false
+            },
+            "redsocks": {
+                "type": "socks5",
+                "ip": "10.0.0.1",
+                "port": 1080,
+                "login": "user name"
+            }
+        });
+        let order: Vec<String> = [
+            "base.log",
+            "redsocks.type",
+            "redsocks.ip",
+            "redsocks.port",
+            "redsocks.login",
+        ]
+        .iter()
+        .map(|x| x.to_string())
+        .collect();
+        assert_eq!(
+            String::from_utf8(serialize(&document, &order).unwrap()).unwrap(),
+            "base {\n\tlog = stderr;\n\tlog_debug = off;\n}\n\nredsocks {\n\ttype = socks5;\n\tip = 10.0.0.1;\n\tport = 1080;\n\tlogin = \"user name\";\n}\n"
+        );
+    }
+
+    #[test]
+    fn repeated_sections() {
+        let document = json!({"redsocks": [{"port": "1"}, {"port": "2"}]});
+        let content = serialize(&document, &[]).unwrap();
+        assert_eq!(deserialize(&content).unwrap(), document);
+    }
+
+    #[test]
+    fn deserialize_with_comments() {
+        let content = br#"
+            // redsocks configuration
+            base {
+                log = "file:/var/log/redsocks"; # log file
+                /* daemon = on; */
+            }
+            redsocks { type = socks5; password = "a \"b\""; }
+        "#;
+        assert_eq!(
+            deserialize(content).unwrap(),
+            json!({
+                "base": {"log": "file:/var/log/redsocks"},
+                "redsocks": {"type": "socks5", "password": "a \"b\""}
+            })
+        );
+    }
+
+    #[test]
+    fn fail_on_invalid_syntax() {
+        assert!(deserialize(b"base { log = stderr }").is_err());
+        assert!(deserialize(b"base { log = stderr;").is_err());
+        assert!(deserialize(b"base log = stderr;").is_err());
+    }
+
+    #[test]
+    fn fail_on_nested_objects() {
+        assert!(serialize(&json!({"base": {"log": {"a": "b"}}}), &[]).is_err());
+    }
+}
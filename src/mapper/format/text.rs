@@ -0,0 +1,16 @@
+use serde_json::Value;
+
+use crate::error::Error;
+
+pub(crate) fn serialize(document: &Value) -> Result<Vec<u8>, Error> {
+    match document {
+        Value::String(s) => Ok(s.as_bytes().to_vec()),
+        _ => Err(Error::message("text document must be a string")),
+    }
+}
+
+pub(crate) fn deserialize(content: &[u8]) -> Result<Value, Error> {
+    let content =
+        String::from_utf8(content.to_vec()).map_err(|e| Error::message(format!("invalid text file: {}", e)))?;
+    Ok(Value::String(content))
+}
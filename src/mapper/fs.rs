@@ -0,0 +1,267 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+
+use crate::{
+    error::Error,
+    mapper::is_safe_file_name,
+    schema::mapping::{LocationPartition, TargetLocation},
+};
+
+/// File system abstraction used by the mapping engine
+pub trait FileSystem {
+    /// Returns the file content or `None` if the file does not exist
+    fn read(&self, location: &TargetLocation) -> Result<Option<Vec<u8>>, Error>;
+
+    /// Creates new or replaces an existing file
+    fn write(&mut self, location: &TargetLocation, content: &[u8]) -> Result<(), Error>;
+
+    /// Removes an existing file, missing file is not an error
+    fn remove(&mut self, location: &TargetLocation) -> Result<(), Error>;
+
+    /// Lists files inside the directory (not recursive), sorted by path
+    fn list(&self, partition: &LocationPartition, directory: &str) -> Result<Vec<TargetLocation>, Error>;
+}
+
+// Returns parent directory path without the trailing slash
+fn parent(path: &str) -> &str {
+    match path.rfind('/') {
+        Some(index) => &path[..index],
+        None => "",
+    }
+}
+
+/// In-memory file system
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct MemoryFileSystem {
+    files: HashMap<TargetLocation, Vec<u8>>,
+}
+
+impl MemoryFileSystem {
+    pub fn new() -> MemoryFileSystem {
+        MemoryFileSystem::default()
+    }
+
+    pub fn files(&self) -> &HashMap<TargetLocation, Vec<u8>> {
+        &self.files
+    }
+}
+
+impl From<HashMap<TargetLocation, Vec<u8>>> for MemoryFileSystem {
+    fn from(files: HashMap<TargetLocation, Vec<u8>>) -> MemoryFileSystem {
+        MemoryFileSystem { files }
+    }
+}
+
+impl FileSystem for MemoryFileSystem {
+    fn read(&self, location: &TargetLocation) -> Result<Option<Vec<u8>>, Error> {
+        Ok(self.files.get(location).cloned())
+    }
+
+    fn write(&mut self, location: &TargetLocation, content: &[u8]) -> Result<(), Error> {
+        self.files.insert(location.clone(), content.to_vec());
+        Ok(())
+    }
+
+    fn remove(&mut self, location: &TargetLocation) -> Result<(), Error> {
+        self.files.remove(location);
+        Ok(())
+    }
+
+    fn list(&self, partition: &LocationPartition, directory: &str) -> Result<Vec<TargetLocation>, Error> {
+        let directory = directory.trim_end_matches('/');
+
+        let mut result: Vec<TargetLocation> = self
+            .files
+            .keys()
+            .filter(|x| x.partition() == partition && parent(x.path()) == directory)
+            .cloned()
+            .collect();
+        result.sort_by(|a, b| a.path().cmp(b.path()));
+
+        Ok(result)
+    }
+}
+
+/// Local directory file system
+///
+/// Every partition is a subdirectory named after the partition (`boot`, `1`, ...).
+#[derive(Debug, Clone, PartialEq)]
+pub struct DirectoryFileSystem {
+    directory: PathBuf,
+}
+
+impl DirectoryFileSystem {
+    pub fn new<P: Into<PathBuf>>(directory: P) -> DirectoryFileSystem {
+        DirectoryFileSystem {
+            directory: directory.into(),
+        }
+    }
+
+    /// Local path of the target location
+    ///
+    /// Fails if the location escapes the directory (`..` components, ...).
+    pub fn path(&self, location: &TargetLocation) -> Result<PathBuf, Error> {
+        let partition = location.partition().to_string();
+
+        // Leading, repeated and trailing slashes & `.` components are harmless
+        let is_safe = is_safe_file_name(&partition)
+            && location
+                .path()
+                .split('/')
+                .filter(|x| !x.is_empty() && *x != ".")
+                .all(is_safe_file_name);
+
+        if !is_safe {
+            return Err(Error::message(format!(
+                "target location '{}' escapes the directory",
+                location
+            )));
+        }
+
+        Ok(self
+            .directory
+            .join(partition)
+            .join(location.path().trim_start_matches('/')))
+    }
+}
+
+impl FileSystem for DirectoryFileSystem {
+    fn read(&self, location: &TargetLocation) -> Result<Option<Vec<u8>>, Error> {
+        let path = self.path(location)?;
+        match fs::read(&path) {
+            Ok(content) => Ok(Some(content)),
+            Err(ref e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(Error::message(format!("unable to read '{}': {}", path.display(), e))),
+        }
+    }
+
+    fn write(&mut self, location: &TargetLocation, content: &[u8]) -> Result<(), Error> {
+        let path = self.path(location)?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| Error::message(format!("unable to create '{}': {}", parent.display(), e)))?;
+        }
+        fs::write(&path, content).map_err(|e| Error::message(format!("unable to write '{}': {}", path.display(), e)))
+    }
+
+    fn remove(&mut self, location: &TargetLocation) -> Result<(), Error> {
+        let path = self.path(location)?;
+        match fs::remove_file(&path) {
+            Err(ref e) if e.kind() != ErrorKind::NotFound => {
+                Err(Error::message(format!("unable to remove '{}': {}", path.display(), e)))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn list(&self, partition: &LocationPartition, directory: &str) -> Result<Vec<TargetLocation>, Error> {
+        let directory = directory.trim_end_matches('/');
+        let path = self.path(&TargetLocation::new(partition.clone(), directory))?;
+
+        let entries = match fs::read_dir(&path) {
+            Ok(entries) => entries,
+            Err(ref e) if e.kind() == ErrorKind::NotFound => return Ok(vec![]),
+            Err(e) => return Err(Error::message(format!("unable to list '{}': {}", path.display(), e))),
+        };
+
+        let mut result = vec![];
+
+        for entry in entries {
+            let entry = entry.map_err(|e| Error::message(format!("unable to list '{}': {}", path.display(), e)))?;
+            if entry.path().is_file() {
+                let name = entry.file_name().to_string_lossy().to_string();
+                result.push(TargetLocation::new(
+                    partition.clone(),
+                    format!("{}/{}", directory, name),
+                ));
+            }
+        }
+        result.sort_by(|a, b| a.path().cmp(b.path()));
+
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_missing_file() {
+        let fs = MemoryFileSystem::new();
+        let location = TargetLocation::new(LocationPartition::Index(0), "/foo");
+        assert_eq!(fs.read(&location).unwrap(), None);
+    }
+
+    #[test]
+    fn list_directory() {
+        let boot = LocationPartition::Label("boot".to_string());
+        let mut fs = MemoryFileSystem::new();
+        fs.write(&TargetLocation::new(boot.clone(), "/dir/b"), b"").unwrap();
+        fs.write(&TargetLocation::new(boot.clone(), "/dir/a"), b"").unwrap();
+        fs.write(&TargetLocation::new(boot.clone(), "/dir/nested/c"), b"")
+            .unwrap();
+        fs.write(&TargetLocation::new(boot.clone(), "/d"), b"").unwrap();
+        fs.write(&TargetLocation::new(LocationPartition::Index(1), "/dir/e"), b"")
+            .unwrap();
+
+        let files = fs.list(&boot, "/dir/").unwrap();
+        let paths: Vec<&str> = files.iter().map(TargetLocation::path).collect();
+        assert_eq!(paths, vec!["/dir/a", "/dir/b"]);
+    }
+
+    #[test]
+    fn directory_file_system() {
+        let directory = std::env::temp_dir().join(format!("jellyschema-fs-{}", std::process::id()));
+        let boot = LocationPartition::Label("boot".to_string());
+        let mut fs = DirectoryFileSystem::new(&directory);
+
+        let location = TargetLocation::new(boot.clone(), "/dir/a");
+        fs.write(&location, b"foo").unwrap();
+        fs.write(&TargetLocation::new(boot.clone(), "/dir/nested/b"), b"")
+            .unwrap();
+        assert_eq!(fs.read(&location).unwrap(), Some(b"foo".to_vec()));
+        assert_eq!(fs.path(&location).unwrap(), directory.join("boot/dir/a"));
+
+        let files = fs.list(&boot, "/dir/").unwrap();
+        let paths: Vec<&str> = files.iter().map(TargetLocation::path).collect();
+        assert_eq!(paths, vec!["/dir/a"]);
+
+        fs.remove(&location).unwrap();
+        fs.remove(&location).unwrap();
+        assert_eq!(fs.read(&location).unwrap(), None);
+
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn reject_escaping_locations() {
+        let fs = DirectoryFileSystem::new("/tmp/jellyschema");
+        let boot = LocationPartition::Label("boot".to_string());
+
+        assert!(fs
+            .path(&TargetLocation::new(boot.clone(), "/../../etc/passwd"))
+            .is_err());
+        assert!(fs.path(&TargetLocation::new(boot.clone(), "/dir/../../b")).is_err());
+        assert!(fs.path(&TargetLocation::new(boot.clone(), "..")).is_err());
+        assert!(fs
+            .path(&TargetLocation::new(LocationPartition::Label("..".to_string()), "/a"))
+            .is_err());
+        assert!(fs.read(&TargetLocation::new(boot.clone(), "/../a")).is_err());
+
+        assert_eq!(
+            fs.path(&TargetLocation::new(boot, "//dir/./a..b")).unwrap(),
+            PathBuf::from("/tmp/jellyschema/boot/dir/./a..b")
+        );
+    }
+
+    #[test]
+    fn write_and_read() {
+        let mut fs = MemoryFileSystem::new();
+        let location = TargetLocation::new(LocationPartition::Label("boot".to_string()), "/foo");
+        fs.write(&location, b"bar").unwrap();
+        assert_eq!(fs.read(&location).unwrap(), Some(b"bar".to_vec()));
+    }
+}
@@ -0,0 +1,1122 @@
+//! A module containing the mapping engine.
+//!
+//! Mapping engine walks the schema together with the data, writes every mapped
+//! value into the target document and renders target documents in the target
+//! format. The reverse direction, reading existing target files back into the
+//! data, is provided by the [`read_data`] function.
+//!
+//! Mapping rules:
+//!
+//! * `mapping.target` is inherited by all nested properties
+//! * `mapping.path` is a dotted path inside the target document, the whole value
+//!   is written at this path
+//! * a property with its own `mapping.target`, but without `mapping.path` and
+//!   `properties`, is written as the whole target document
+//! * `mapping.template` is merged into the target document (at the `mapping.path`
+//!   if provided) before any value is written
+//! * `ini` targets map the first path component to the section and the second one to
+//!   the key (`wifi-security.psk`), existing entries the schema does not map are kept
+//! * `ini` and `redsocks` keys are written in the order they are mapped, `mapping.template`
+//!   keys in alphabetical order
+//! * `text` targets contain a string, `stringlist` values are joined with the
+//!   `separator` (`\n` by default)
+//! * `binary` targets contain a `file` value, data url content is decoded
+//! * array items with their own `fileset` target are stored as one file per item
+//!   inside the `fileset` location directory, the file name is provided by the
+//!   `mapping.filename` (evaluated for every item if it is a formula)
+//!
+//! Formulas are evaluated before the data are mapped. Fileset files which do not
+//! correspond to any array item are reported by the [`stale_targets`] function.
+//!
+//! [`read_data`]: fn.read_data.html
+//! [`stale_targets`]: fn.stale_targets.html
+use std::collections::{HashMap, HashSet};
+
+use balena_temen::ast::Identifier;
+use serde_json::{Map, Value};
+
+use crate::{
+    error::Error,
+    formula,
+    schema::{
+        mapping::{FileName, Mapping, RawTarget, Target, TargetFormat, TargetLocation},
+        PrimitiveType, Schema,
+    },
+};
+
+pub use self::{
+    fs::{DirectoryFileSystem, FileSystem, MemoryFileSystem},
+    reader::read_data,
+};
+
+mod format;
+mod fs;
+mod reader;
+
+/// Rendered target file
+#[derive(Debug, Clone, PartialEq)]
+pub struct RenderedTarget {
+    location: TargetLocation,
+    format: TargetFormat,
+    content: Vec<u8>,
+}
+
+impl RenderedTarget {
+    pub fn location(&self) -> &TargetLocation {
+        &self.location
+    }
+
+    pub fn format(&self) -> &TargetFormat {
+        &self.format
+    }
+
+    pub fn content(&self) -> &[u8] {
+        &self.content
+    }
+}
+
+struct Document {
+    location: TargetLocation,
+    format: TargetFormat,
+    value: Value,
+    // Dotted paths of the written values in the order they were written
+    order: Vec<String>,
+}
+
+impl Document {
+    fn insert(&mut self, path: &str, value: Value) -> Result<(), Error> {
+        leaf_paths(path, &value, &mut self.order);
+        insert(&mut self.value, path, value)
+    }
+}
+
+struct Mapper<'a> {
+    root: &'a Schema,
+    targets: Option<&'a HashMap<String, RawTarget>>,
+    data: &'a Value,
+    documents: Vec<Document>,
+    // Dotted paths mapped by the schema, existing values at these paths are replaced
+    mapped: HashMap<TargetLocation, HashSet<String>>,
+    // Location of the fileset item being mapped
+    item_location: Option<TargetLocation>,
+}
+
+impl<'a> Mapper<'a> {
+    fn new(schema: &'a Schema, data: &'a Value) -> Mapper<'a> {
+        Mapper {
+            root: schema,
+            targets: schema.mapping().map(Mapping::targets),
+            data,
+            documents: vec![],
+            mapped: HashMap::new(),
+            item_location: None,
+        }
+    }
+
+    fn location(&self, target: &RawTarget) -> Result<TargetLocation, Error> {
+        if target.type_().is_file_set() {
+            self.item_location.clone().ok_or_else(|| {
+                Error::message(format!(
+                    "fileset target can be used for array items only: {}",
+                    target.location()
+                ))
+            })
+        } else {
+            Ok(target.location().clone())
+        }
+    }
+
+    // Records paths of the `value` leaves at the dotted `path` as mapped
+    fn mark_mapped(&mut self, target: &RawTarget, path: &str, value: &Value) {
+        if let Ok(location) = self.location(target) {
+            let mut paths = vec![];
+            leaf_paths(path, value, &mut paths);
+            self.mapped.entry(location).or_default().extend(paths);
+        }
+    }
+
+    fn document_mut(&mut self, target: &RawTarget) -> Result<&mut Document, Error> {
+        let location = self.location(target)?;
+
+        let index = match self.documents.iter().position(|x| x.location == location) {
+            Some(index) if &self.documents[index].format != target.format() => {
+                return Err(Error::message(format!(
+                    "conflicting target formats '{}' and '{}': {}",
+                    self.documents[index].format,
+                    target.format(),
+                    location
+                )));
+            }
+            Some(index) => index,
+            None => {
+                self.documents.push(Document {
+                    location,
+                    format: *target.format(),
+                    value: Value::Null,
+                    order: vec![],
+                });
+                self.documents.len() - 1
+            }
+        };
+
+        Ok(&mut self.documents[index])
+    }
+
+    fn map(
+        &mut self,
+        schema: &'a Schema,
+        data: Option<&'a Value>,
+        position: Identifier,
+        inherited: Option<&'a RawTarget>,
+    ) -> Result<(), Error> {
+        let data = match data {
+            None | Some(Value::Null) => return self.map_absent(schema, inherited, &mut vec![]),
+            Some(data) => data,
+        };
+
+        let schema = self.root.resolve(schema);
+        let mapping = schema.mapping();
+        let path = mapping.and_then(Mapping::path);
+
+        let own_target = match mapping.and_then(Mapping::target) {
+            Some(target) => Some(resolve_target(self.targets, target)?),
+            None => None,
+        };
+        let target = own_target.or(inherited);
+
+        match target {
+            Some(target) => {
+                if let Some(template) = mapping.and_then(Mapping::template) {
+                    self.mark_mapped(target, path.unwrap_or(""), template);
+                    self.document_mut(target)?
+                        .insert(path.unwrap_or(""), template.clone())?;
+                }
+
+                if path.is_some() || (own_target.is_some() && schema.properties().is_empty()) {
+                    let value = encode(schema, *target.format(), data);
+                    self.mark_mapped(target, path.unwrap_or(""), &Value::Null);
+                    return self.document_mut(target)?.insert(path.unwrap_or(""), value);
+                }
+            }
+            None => {
+                if let Some(path) = path {
+                    return Err(Error::message(format!("mapping path '{}' without target", path)));
+                }
+            }
+        };
+
+        if let Some(object) = data.as_object() {
+            for property in schema.properties() {
+                self.map(
+                    property.schema(),
+                    object.get(property.name()),
+                    position.clone().name(property.name()),
+                    target,
+                )?;
+            }
+        }
+
+        if let Some(items) = data.as_array() {
+            if schema.items().iter().any(|x| self.root.resolve(x).mapping().is_some()) {
+                self.map_file_set(schema, items, position)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    // Records paths the schema maps without any data, their existing values are removed
+    fn map_absent(
+        &mut self,
+        schema: &'a Schema,
+        inherited: Option<&'a RawTarget>,
+        visited: &mut Vec<*const Schema>,
+    ) -> Result<(), Error> {
+        let schema = self.root.resolve(schema);
+        if visited.contains(&(schema as *const Schema)) {
+            return Ok(());
+        }
+
+        let mapping = schema.mapping();
+        let path = mapping.and_then(Mapping::path);
+
+        let own_target = match mapping.and_then(Mapping::target) {
+            Some(target) => Some(resolve_target(self.targets, target)?),
+            None => None,
+        };
+        let target = own_target.or(inherited);
+
+        if let Some(target) = target {
+            if let Some(template) = mapping.and_then(Mapping::template) {
+                self.mark_mapped(target, path.unwrap_or(""), template);
+            }
+
+            if path.is_some() || (own_target.is_some() && schema.properties().is_empty()) {
+                self.mark_mapped(target, path.unwrap_or(""), &Value::Null);
+                return Ok(());
+            }
+        }
+
+        visited.push(schema);
+        for property in schema.properties() {
+            self.map_absent(property.schema(), target, visited)?;
+        }
+        visited.pop();
+
+        Ok(())
+    }
+
+    fn map_file_set(&mut self, schema: &'a Schema, items: &'a [Value], position: Identifier) -> Result<(), Error> {
+        let item_schema = match schema.items() {
+            [item_schema] => self.root.resolve(item_schema),
+            _ => return Err(Error::message("array items mapping requires single items schema")),
+        };
+
+        let mapping = item_schema.mapping();
+        let target = match mapping.and_then(Mapping::target) {
+            Some(target) => resolve_target(self.targets, target)?,
+            None => return Err(Error::message("array items must be mapped to a fileset target")),
+        };
+
+        if !target.type_().is_file_set() {
+            return Err(Error::message(format!(
+                "array items must be mapped to a fileset target: {}",
+                target.location()
+            )));
+        }
+
+        let filename = mapping
+            .and_then(Mapping::filename)
+            .ok_or_else(|| Error::message(format!("fileset target without filename: {}", target.location())))?;
+
+        let mut names = HashSet::new();
+
+        for (index, item) in items.iter().enumerate() {
+            let position = position.clone().index(index as isize);
+            let name = self.file_name(filename, &position)?;
+
+            if !names.insert(name.clone()) {
+                return Err(Error::message(format!(
+                    "duplicate fileset file name '{}': {}",
+                    name,
+                    target.location()
+                )));
+            }
+
+            let directory = target.location().path().trim_end_matches('/');
+            self.item_location = Some(TargetLocation::new(
+                target.location().partition().clone(),
+                format!("{}/{}", directory, name),
+            ));
+            let result = self.map(item_schema, Some(item), position, None);
+            self.item_location = None;
+            result?;
+        }
+
+        Ok(())
+    }
+
+    fn file_name(&self, filename: &FileName, position: &Identifier) -> Result<String, Error> {
+        let name = match filename {
+            FileName::Name(name) => name.clone(),
+            FileName::Formula(formula) => match formula::evaluate(formula, position, self.data) {
+                Ok(Value::String(name)) => name,
+                Ok(Value::Number(number)) => number.to_string(),
+                Ok(value) => {
+                    return Err(Error::message(format!(
+                        "fileset file name must be a string, got '{}'",
+                        value
+                    )));
+                }
+                Err(e) => {
+                    return Err(Error::message(format!(
+                        "unable to evaluate file name formula '{}': {}",
+                        formula, e
+                    )));
+                }
+            },
+        };
+
+        if is_safe_file_name(&name) {
+            Ok(name)
+        } else {
+            Err(Error::message(format!("invalid fileset file name '{}'", name)))
+        }
+    }
+}
+
+fn is_safe_file_name(name: &str) -> bool {
+    !name.is_empty() && name != "." && name != ".." && name.chars().all(|x| !x.is_control() && x != '/' && x != '\\')
+}
+
+// Simple glob pattern matching, `*` matches any sequence, `?` any single character
+fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+
+    let (mut p, mut n) = (0, 0);
+    let mut backtrack = None;
+
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, n));
+                p += 1;
+            }
+            Some(x) if *x == '?' || *x == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match backtrack {
+                Some((bp, bn)) => {
+                    p = bp + 1;
+                    n = bn + 1;
+                    backtrack = Some((bp, bn + 1));
+                }
+                None => return false,
+            },
+        };
+    }
+
+    pattern[p..].iter().all(|x| *x == '*')
+}
+
+// Lists existing fileset files matching the target glob
+fn list_file_set<F>(fs: &F, target: &RawTarget) -> Result<Vec<TargetLocation>, Error>
+where
+    F: FileSystem,
+{
+    let files = fs.list(target.location().partition(), target.location().path())?;
+
+    Ok(match target.glob() {
+        Some(glob) => files
+            .into_iter()
+            .filter(|x| glob_match(glob, x.path().rsplit('/').next().unwrap_or_default()))
+            .collect(),
+        None => files,
+    })
+}
+
+// Collects all fileset targets used in the schema
+fn collect_file_sets<'a>(
+    targets: Option<&'a HashMap<String, RawTarget>>,
+    schema: &'a Schema,
+    result: &mut Vec<&'a RawTarget>,
+) -> Result<(), Error> {
+    if let Some(target) = schema.mapping().and_then(Mapping::target) {
+        let target = resolve_target(targets, target)?;
+        if target.type_().is_file_set() && !result.iter().any(|x| x.location() == target.location()) {
+            result.push(target);
+        }
+    }
+
+    for property in schema.properties() {
+        collect_file_sets(targets, property.schema(), result)?;
+    }
+
+    for item in schema.items() {
+        collect_file_sets(targets, item, result)?;
+    }
+
+    for definition in schema.definitions().values() {
+        collect_file_sets(targets, definition, result)?;
+    }
+
+    Ok(())
+}
+
+fn resolve_target<'a>(
+    targets: Option<&'a HashMap<String, RawTarget>>,
+    target: &'a Target,
+) -> Result<&'a RawTarget, Error> {
+    match target {
+        Target::Raw(raw) => Ok(raw),
+        Target::Reference(name) => targets
+            .and_then(|targets| targets.get(name))
+            .ok_or_else(|| Error::message(format!("unknown mapping target '{}'", name))),
+    }
+}
+
+// Text targets do not support arrays, `stringlist` values are joined with the separator
+fn encode(schema: &Schema, format: TargetFormat, value: &Value) -> Value {
+    match (format, schema.r#type().primitive_type().base_type(), value) {
+        (TargetFormat::Text, PrimitiveType::StringList, Value::Array(items)) => {
+            Value::String(schema.join_stringlist(items.iter().filter_map(Value::as_str)))
+        }
+        _ => value.clone(),
+    }
+}
+
+// Deep merge, objects are merged, anything else is replaced
+fn merge(destination: &mut Value, source: Value) {
+    match (destination, source) {
+        (Value::Object(destination), Value::Object(source)) => {
+            for (key, value) in source {
+                merge(destination.entry(key).or_insert(Value::Null), value);
+            }
+        }
+        (destination, source) => *destination = source,
+    }
+}
+
+// Joins dotted paths, empty components are ignored
+fn join_path(prefix: &str, key: &str) -> String {
+    prefix
+        .split('.')
+        .chain(key.split('.'))
+        .filter(|x| !x.is_empty())
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+// Collects dotted paths of all non-object (or empty object) values
+fn leaf_paths(path: &str, value: &Value, result: &mut Vec<String>) {
+    match value {
+        Value::Object(map) if !map.is_empty() => {
+            for (key, value) in map {
+                leaf_paths(&join_path(path, key), value, result);
+            }
+        }
+        _ => result.push(join_path(path, "")),
+    }
+}
+
+// Merges value into the document at the dotted path
+fn insert(document: &mut Value, path: &str, value: Value) -> Result<(), Error> {
+    let mut current = document;
+
+    for component in path.split('.').filter(|x| !x.is_empty()) {
+        if current.is_null() {
+            *current = Value::Object(Map::new());
+        }
+
+        current = match current {
+            Value::Object(map) => map.entry(component).or_insert(Value::Null),
+            _ => {
+                return Err(Error::message(format!(
+                    "unable to map '{}', parent value is not an object",
+                    path
+                )));
+            }
+        };
+    }
+
+    merge(current, value);
+    Ok(())
+}
+
+// Renders mapped documents, existing file content is used to keep comments
+fn render<F>(schema: &Schema, data: &Value, existing: F) -> Result<Vec<RenderedTarget>, Error>
+where
+    F: Fn(&TargetLocation) -> Result<Option<Vec<u8>>, Error>,
+{
+    let mut data = data.clone();
+    formula::evaluate_formulas(schema, &mut data).map_err(|e| Error::message(e.to_string()))?;
+
+    let mut mapper = Mapper::new(schema, &data);
+    mapper.map(schema, Some(&data), Identifier::default(), None)?;
+
+    let Mapper { documents, mapped, .. } = mapper;
+    let unmapped = HashSet::new();
+
+    documents
+        .into_iter()
+        .map(|document| {
+            let existing = existing(&document.location)?;
+            let content = format::serialize(
+                document.format,
+                &document.value,
+                existing.as_deref(),
+                &document.order,
+                mapped.get(&document.location).unwrap_or(&unmapped),
+            )?;
+            Ok(RenderedTarget {
+                content,
+                location: document.location,
+                format: document.format,
+            })
+        })
+        .collect()
+}
+
+/// Renders all targets the data are mapped to
+///
+/// Targets without any mapped value are not rendered.
+///
+/// # Arguments
+///
+/// * `schema` - JellySchema
+/// * `data` - JSON data, validated against the `schema`
+pub fn render_targets(schema: &Schema, data: &Value) -> Result<Vec<RenderedTarget>, Error> {
+    render(schema, data, |_| Ok(None))
+}
+
+/// Renders all targets the data are mapped to over the existing files
+///
+/// Same as [`render_targets`], but formats supporting comments (`ini`) keep
+/// comments and the layout of the existing files.
+///
+/// # Arguments
+///
+/// * `schema` - JellySchema
+/// * `data` - JSON data, validated against the `schema`
+/// * `fs` - File system with existing target files
+///
+/// [`render_targets`]: fn.render_targets.html
+pub fn render_updated_targets<F>(schema: &Schema, data: &Value, fs: &F) -> Result<Vec<RenderedTarget>, Error>
+where
+    F: FileSystem,
+{
+    render(schema, data, |location| fs.read(location))
+}
+
+/// Writes rendered targets into the file system
+pub fn write_targets<F>(fs: &mut F, targets: &[RenderedTarget]) -> Result<(), Error>
+where
+    F: FileSystem,
+{
+    for target in targets {
+        fs.write(target.location(), target.content())?;
+    }
+    Ok(())
+}
+
+/// Returns existing fileset files which do not correspond to any rendered target
+///
+/// Only files matching the fileset target `glob` are considered.
+///
+/// # Arguments
+///
+/// * `schema` - JellySchema
+/// * `targets` - Rendered targets
+/// * `fs` - File system with existing target files
+pub fn stale_targets<F>(schema: &Schema, targets: &[RenderedTarget], fs: &F) -> Result<Vec<TargetLocation>, Error>
+where
+    F: FileSystem,
+{
+    let mut file_sets = vec![];
+    collect_file_sets(schema.mapping().map(Mapping::targets), schema, &mut file_sets)?;
+
+    let mut result = vec![];
+
+    for file_set in file_sets {
+        for location in list_file_set(fs, file_set)? {
+            if !targets.iter().any(|x| x.location() == &location) {
+                result.push(location);
+            }
+        }
+    }
+
+    Ok(result)
+}
+
+/// Removes stale files from the file system
+pub fn remove_targets<F>(fs: &mut F, locations: &[TargetLocation]) -> Result<(), Error>
+where
+    F: FileSystem,
+{
+    for location in locations {
+        fs.remove(location)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::schema::mapping::LocationPartition;
+
+    const BALENA_OS: &str = include_str!("../../fuzz/seeds/balena-os.yml");
+
+    fn location(partition: &str, path: &str) -> TargetLocation {
+        TargetLocation::new(LocationPartition::Label(partition.to_string()), path)
+    }
+
+    fn render_json(schema: &str, data: Value) -> Vec<(TargetLocation, Value)> {
+        render_targets(&schema.parse().unwrap(), &data)
+            .unwrap()
+            .into_iter()
+            .map(|x| (x.location().clone(), serde_json::from_slice(x.content()).unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn balena_os_config_json() {
+        let schema: Schema = BALENA_OS.parse().unwrap();
+        let data = json!({
+            "advanced": {
+                "appUpdatePollInterval": 10,
+                "hostname": "balena",
+                "persistentLogging": false,
+                "dnsServers": ["8.8.8.8", "8.8.4.4"],
+                "udevRules": {
+                    "10": "rule"
+                }
+            },
+            "blobs": {}
+        });
+
+        let targets = render_targets(&schema, &data).unwrap();
+        assert_eq!(targets.len(), 1);
+
+        let mut fs = MemoryFileSystem::new();
+        write_targets(&mut fs, &targets).unwrap();
+
+        let content = fs.read(&location("resin-boot", "/config.json")).unwrap().unwrap();
+        let config: Value = serde_json::from_slice(&content).unwrap();
+        assert_eq!(
+            config,
+            json!({
+                "appUpdatePollInterval": 10,
+                "hostname": "balena",
+                "persistentLogging": false,
+                "dnsServers": ["8.8.8.8", "8.8.4.4"],
+                "os": {
+                    "udevRules": {
+                        "10": "rule"
+                    }
+                }
+            })
+        );
+    }
+
+    #[test]
+    fn no_data_no_targets() {
+        let schema: Schema = BALENA_OS.parse().unwrap();
+        assert!(render_targets(&schema, &json!({})).unwrap().is_empty());
+    }
+
+    #[test]
+    fn inherited_target() {
+        let schema = r#"
+            mapping:
+              targets:
+                config:
+                  type: file
+                  format: json
+                  location:
+                    partition: boot
+                    path: /config.json
+            properties:
+              - foo:
+                  mapping:
+                    target: config
+                  properties:
+                    - bar:
+                        properties:
+                          - baz:
+                              type: string
+                              mapping:
+                                path: a.b
+        "#;
+        let targets = render_json(schema, json!({"foo": {"bar": {"baz": "value"}}}));
+        assert_eq!(
+            targets,
+            vec![(location("boot", "/config.json"), json!({"a": {"b": "value"}}))]
+        );
+    }
+
+    #[test]
+    fn raw_target() {
+        let schema = r#"
+            properties:
+              - foo:
+                  type: string
+                  mapping:
+                    target:
+                      type: file
+                      format: json
+                      location:
+                        partition: boot
+                        path: /foo.json
+                    path: foo
+        "#;
+        let targets = render_json(schema, json!({"foo": "bar"}));
+        assert_eq!(targets, vec![(location("boot", "/foo.json"), json!({"foo": "bar"}))]);
+    }
+
+    #[test]
+    fn whole_document() {
+        let schema = r#"
+            properties:
+              - foo:
+                  type: object
+                  additionalProperties: true
+                  mapping:
+                    target:
+                      type: file
+                      format: json
+                      location:
+                        partition: boot
+                        path: /foo.json
+        "#;
+        let targets = render_json(schema, json!({"foo": {"bar": "baz"}}));
+        assert_eq!(targets, vec![(location("boot", "/foo.json"), json!({"bar": "baz"}))]);
+    }
+
+    #[test]
+    fn template() {
+        let schema = r#"
+            properties:
+              - foo:
+                  mapping:
+                    target:
+                      type: file
+                      format: json
+                      location:
+                        partition: boot
+                        path: /foo.json
+                    template:
+                      base:
+                        debug: false
+                      foo:
+                        bar: default
+                  properties:
+                    - bar:
+                        type: string
+                        mapping:
+                          path: foo.bar
+        "#;
+        let targets = render_json(schema, json!({"foo": {"bar": "baz"}}));
+        assert_eq!(
+            targets,
+            vec![(
+                location("boot", "/foo.json"),
+                json!({"base": {"debug": false}, "foo": {"bar": "baz"}})
+            )]
+        );
+    }
+
+    #[test]
+    fn balena_os_redsocks() {
+        let schema: Schema = BALENA_OS.parse().unwrap();
+        let data = json!({
+            "proxy": {
+                "redsocks": {
+                    "proxyType": "socks5",
+                    "server": "10.0.0.1",
+                    "port": 1080
+                }
+            }
+        });
+
+        let targets = render_targets(&schema, &data).unwrap();
+        assert_eq!(targets.len(), 1);
+        assert_eq!(
+            targets[0].location(),
+            &location("resin-boot", "/system-proxy/redsocks.conf")
+        );
+        assert_eq!(
+            std::str::from_utf8(targets[0].content()).unwrap(),
+            "base {\n\tlog = stderr;\n\tlog_debug = off;\n\tlog_info = on;\n\tredirector = iptables;\n}\n\n\
+             redsocks {\n\ttype = socks5;\n\tip = 10.0.0.1;\n\tport = 1080;\n}\n"
+        );
+
+        let mut fs = MemoryFileSystem::new();
+        write_targets(&mut fs, &targets).unwrap();
+        assert_eq!(read_data(&schema, &fs).unwrap()["proxy"], data["proxy"]);
+    }
+
+    #[test]
+    fn balena_os_text_and_binary() {
+        let schema: Schema = BALENA_OS.parse().unwrap();
+        let data = json!({
+            "blobs": {
+                "logo": "data:image/png;name=resin-logo.png;base64,iVBORw=="
+            },
+            "proxy": {
+                "proxyWhitelistIPs": ["10.0.0.0/8", "192.168.1.1"]
+            }
+        });
+
+        let mut fs = MemoryFileSystem::new();
+        write_targets(&mut fs, &render_targets(&schema, &data).unwrap()).unwrap();
+
+        assert_eq!(
+            fs.read(&location("resin-boot", "/splash/resin-logo.png")).unwrap(),
+            Some(b"\x89PNG".to_vec())
+        );
+        assert_eq!(
+            fs.read(&location("resin-boot", "/system-proxy/no_proxy")).unwrap(),
+            Some(b"10.0.0.0/8\n192.168.1.1".to_vec())
+        );
+
+        let read = read_data(&schema, &fs).unwrap();
+        assert_eq!(read["proxy"]["proxyWhitelistIPs"], data["proxy"]["proxyWhitelistIPs"]);
+        assert_eq!(read["blobs"]["logo"], data["blobs"]["logo"]);
+    }
+
+    #[test]
+    fn escaped_separator() {
+        let schema: Schema = r#"
+            properties:
+              - list:
+                  type: stringlist
+                  separator: \t
+                  mapping:
+                    target:
+                      type: file
+                      format: text
+                      location:
+                        partition: boot
+                        path: /list
+        "#
+        .parse()
+        .unwrap();
+        let data = json!({"list": ["a", "b"]});
+
+        let targets = render_targets(&schema, &data).unwrap();
+        assert_eq!(targets[0].content(), b"a\tb");
+
+        let mut fs = MemoryFileSystem::new();
+        write_targets(&mut fs, &targets).unwrap();
+        assert_eq!(read_data(&schema, &fs).unwrap(), data);
+    }
+
+    #[test]
+    fn balena_os_networks() {
+        let schema: Schema = BALENA_OS.parse().unwrap();
+        let data = json!({
+            "network": [
+                {"ssid": "Home Net", "password": "secret"},
+                {"ssid": "Office", "password": "secret"}
+            ]
+        });
+
+        let targets = render_targets(&schema, &data).unwrap();
+        let paths: Vec<&str> = targets.iter().map(|x| x.location().path()).collect();
+        assert_eq!(
+            paths,
+            vec!["/system-connections/home-net", "/system-connections/office"]
+        );
+        assert_eq!(
+            std::str::from_utf8(targets[0].content()).unwrap(),
+            "[connection]\ntype=wifi\nid=home-net\n\n[ipv4]\nmethod=auto\n\n\
+             [ipv6]\naddr-gen-mode=stable-privacy\nmethod=auto\n\n[wifi]\nhidden=true\nmode=infrastructure\n\
+             ssid=Home Net\n\n[wifi-security]\nauth-alg=open\nkey-mgmt=wpa-psk\npsk=secret\n"
+        );
+
+        let mut fs = MemoryFileSystem::new();
+        write_targets(&mut fs, &targets).unwrap();
+        assert_eq!(
+            read_data(&schema, &fs).unwrap()["network"],
+            json!([
+                {"id": "home-net", "ssid": "Home Net", "password": "secret"},
+                {"id": "office", "ssid": "Office", "password": "secret"}
+            ])
+        );
+    }
+
+    const FILE_SET: &str = r#"
+        mapping:
+          targets:
+            items:
+              type: fileset
+              format: json
+              glob: "*.json"
+              location:
+                partition: boot
+                path: /items
+        properties:
+          - items:
+              type: array
+              items:
+                properties:
+                  - name:
+                      type: string
+                      mapping:
+                        path: name
+                mapping:
+                  target: items
+                  filename:
+                    formula: this.name ~ `.json`
+    "#;
+
+    #[test]
+    fn stale_file_set_files() {
+        let schema: Schema = FILE_SET.parse().unwrap();
+
+        let mut fs = MemoryFileSystem::new();
+        fs.write(&location("boot", "/items/old.json"), b"{}").unwrap();
+        fs.write(&location("boot", "/items/a.json"), b"{}").unwrap();
+        fs.write(&location("boot", "/items/README"), b"").unwrap();
+
+        let targets = render_targets(&schema, &json!({"items": [{"name": "a"}, {"name": "b"}]})).unwrap();
+        let stale = stale_targets(&schema, &targets, &fs).unwrap();
+        assert_eq!(stale, vec![location("boot", "/items/old.json")]);
+
+        write_targets(&mut fs, &targets).unwrap();
+        remove_targets(&mut fs, &stale).unwrap();
+
+        let mut paths: Vec<&str> = fs.files().keys().map(TargetLocation::path).collect();
+        paths.sort();
+        assert_eq!(paths, vec!["/items/README", "/items/a.json", "/items/b.json"]);
+        assert_eq!(
+            read_data(&schema, &fs).unwrap(),
+            json!({"items": [{"name": "a"}, {"name": "b"}]})
+        );
+    }
+
+    #[test]
+    fn fail_on_duplicate_file_name() {
+        let schema: Schema = FILE_SET.parse().unwrap();
+        assert!(render_targets(&schema, &json!({"items": [{"name": "a"}, {"name": "a"}]})).is_err());
+    }
+
+    #[test]
+    fn fail_on_unsafe_file_name() {
+        let schema: Schema = FILE_SET.parse().unwrap();
+        assert!(render_targets(&schema, &json!({"items": [{"name": "../a"}]})).is_err());
+        assert!(render_targets(&schema, &json!({"items": [{"name": "a/b"}]})).is_err());
+    }
+
+    #[test]
+    fn glob() {
+        assert!(glob_match("*", "foo"));
+        assert!(glob_match("*.json", "foo.json"));
+        assert!(glob_match("f?o*", "foo.json"));
+        assert!(glob_match("*a*b", "xaxxb"));
+        assert!(!glob_match("*.json", "foo.ini"));
+        assert!(!glob_match("f?o", "fo"));
+    }
+
+    const NETWORK_INI: &str = r#"
+        properties:
+          - network:
+              mapping:
+                target:
+                  type: file
+                  format: ini
+                  location:
+                    partition: boot
+                    path: /system-connections/home
+                template:
+                  connection:
+                    type: wifi
+                  wifi:
+                    mode: infrastructure
+              properties:
+                - ssid:
+                    type: string
+                    mapping:
+                      path: wifi.ssid
+                - password:
+                    type: password
+                    mapping:
+                      path: wifi-security.psk
+    "#;
+
+    #[test]
+    fn ini_template() {
+        let schema: Schema = NETWORK_INI.parse().unwrap();
+        let data = json!({"network": {"ssid": "home", "password": "secret"}});
+
+        let targets = render_targets(&schema, &data).unwrap();
+        assert_eq!(targets.len(), 1);
+        assert_eq!(
+            std::str::from_utf8(targets[0].content()).unwrap(),
+            "[connection]\ntype=wifi\n\n[wifi]\nmode=infrastructure\nssid=home\n\n[wifi-security]\npsk=secret\n"
+        );
+
+        let mut fs = MemoryFileSystem::new();
+        write_targets(&mut fs, &targets).unwrap();
+        assert_eq!(read_data(&schema, &fs).unwrap(), data);
+    }
+
+    #[test]
+    fn ini_keep_comments() {
+        let schema: Schema = NETWORK_INI.parse().unwrap();
+        let data = json!({"network": {"ssid": "home", "password": "secret"}});
+
+        let mut fs = MemoryFileSystem::new();
+        fs.write(
+            &location("boot", "/system-connections/home"),
+            b"# Home network\n[connection]\ntype=wifi\n\n[wifi]\n# Do not change\nmode=infrastructure\nssid=old\n",
+        )
+        .unwrap();
+
+        let targets = render_updated_targets(&schema, &data, &fs).unwrap();
+        assert_eq!(
+            std::str::from_utf8(targets[0].content()).unwrap(),
+            "# Home network\n[connection]\ntype=wifi\n\n[wifi]\n# Do not change\nmode=infrastructure\nssid=home\n\n[wifi-security]\npsk=secret\n"
+        );
+    }
+
+    #[test]
+    fn ini_keep_unmapped_entries() {
+        let schema: Schema = NETWORK_INI.parse().unwrap();
+        let data = json!({"network": {"ssid": "home"}});
+
+        let mut fs = MemoryFileSystem::new();
+        fs.write(
+            &location("boot", "/system-connections/home"),
+            b"[connection]\nuuid=1234\ntype=wifi\n\n[wifi]\nssid=old\n\n[wifi-security]\npsk=old\n\n[ipv4]\nmethod=auto\n",
+        )
+        .unwrap();
+
+        let targets = render_updated_targets(&schema, &data, &fs).unwrap();
+        assert_eq!(
+            std::str::from_utf8(targets[0].content()).unwrap(),
+            "[connection]\nuuid=1234\ntype=wifi\n\n[wifi]\nssid=home\nmode=infrastructure\n\n[ipv4]\nmethod=auto\n"
+        );
+    }
+
+    #[test]
+    fn fail_on_unknown_target() {
+        let schema: Schema = r#"
+            properties:
+              - foo:
+                  type: string
+                  mapping:
+                    target: unknown
+                    path: foo
+        "#
+        .parse()
+        .unwrap();
+        assert!(render_targets(&schema, &json!({"foo": "bar"})).is_err());
+    }
+
+    #[test]
+    fn fail_on_path_without_target() {
+        let schema: Schema = r#"
+            properties:
+              - foo:
+                  type: string
+                  mapping:
+                    path: foo
+        "#
+        .parse()
+        .unwrap();
+        assert!(render_targets(&schema, &json!({"foo": "bar"})).is_err());
+    }
+
+    #[test]
+    fn fail_on_non_object_parent() {
+        let schema: Schema = r#"
+            mapping:
+              targets:
+                config:
+                  type: file
+                  format: json
+                  location:
+                    partition: boot
+                    path: /config.json
+            properties:
+              - foo:
+                  type: string
+                  mapping:
+                    target: config
+                    path: foo
+              - bar:
+                  type: string
+                  mapping:
+                    target: config
+                    path: foo.bar
+        "#
+        .parse()
+        .unwrap();
+        assert!(render_targets(&schema, &json!({"foo": "a", "bar": "b"})).is_err());
+    }
+}
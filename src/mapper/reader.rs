@@ -0,0 +1,463 @@
+use std::collections::HashMap;
+
+use serde_json::{Map, Number, Value};
+
+use crate::{
+    error::Error,
+    mapper::{format, fs::FileSystem, list_file_set, resolve_target},
+    schema::{
+        mapping::{Mapping, RawTarget, TargetFormat, TargetLocation},
+        PrimitiveType, Schema,
+    },
+};
+
+struct Reader<'a, 'b, F> {
+    root: &'a Schema,
+    fs: &'b F,
+    targets: Option<&'a HashMap<String, RawTarget>>,
+    documents: HashMap<TargetLocation, Option<Value>>,
+    // References being read, recursive references would never end otherwise
+    expanding: Vec<&'a str>,
+}
+
+impl<'a, 'b, F> Reader<'a, 'b, F>
+where
+    F: FileSystem,
+{
+    fn new(schema: &'a Schema, fs: &'b F) -> Reader<'a, 'b, F> {
+        Reader {
+            root: schema,
+            fs,
+            targets: schema.mapping().map(Mapping::targets),
+            documents: HashMap::new(),
+            expanding: vec![],
+        }
+    }
+
+    fn load(&self, location: &TargetLocation, format: TargetFormat) -> Result<Option<Value>, Error> {
+        match self.fs.read(location)? {
+            Some(content) => format::deserialize(format, location, &content)
+                .map(Some)
+                .map_err(|e| Error::message(format!("unable to read '{}': {}", location, e))),
+            None => Ok(None),
+        }
+    }
+
+    fn document(&mut self, target: &RawTarget) -> Result<Option<Value>, Error> {
+        if !self.documents.contains_key(target.location()) {
+            let document = self.load(target.location(), *target.format())?;
+            self.documents.insert(target.location().clone(), document);
+        }
+        Ok(self.documents[target.location()].clone())
+    }
+
+    fn read(
+        &mut self,
+        schema: &'a Schema,
+        inherited: Option<&'a RawTarget>,
+        document: Option<&Value>,
+    ) -> Result<Option<Value>, Error> {
+        let reference = schema.r#ref();
+
+        if let Some(reference) = reference {
+            if self.expanding.contains(&reference) {
+                return Ok(None);
+            }
+            self.expanding.push(reference);
+        }
+
+        let result = self.read_schema(self.root.resolve(schema), inherited, document);
+
+        if reference.is_some() {
+            self.expanding.pop();
+        }
+
+        result
+    }
+
+    fn read_schema(
+        &mut self,
+        schema: &'a Schema,
+        inherited: Option<&'a RawTarget>,
+        document: Option<&Value>,
+    ) -> Result<Option<Value>, Error> {
+        let mapping = schema.mapping();
+        let path = mapping.and_then(Mapping::path);
+
+        let own_target = match mapping.and_then(Mapping::target) {
+            Some(target) => Some(resolve_target(self.targets, target)?),
+            None => None,
+        };
+
+        let own_document;
+        let (target, document) = match own_target {
+            Some(target) if target.type_().is_file() => {
+                own_document = self.document(target)?;
+                (Some(target), own_document.as_ref())
+            }
+            // Fileset item, document is provided by the array
+            Some(target) => (Some(target), document),
+            None => (inherited, document),
+        };
+
+        match target {
+            Some(target) => {
+                let format = *target.format();
+
+                if let Some(path) = path {
+                    return Ok(document
+                        .and_then(|x| lookup(x, path))
+                        .map(|x| coerce(schema, format, x)));
+                }
+
+                if own_target.is_some() && schema.properties().is_empty() {
+                    return Ok(document.map(|x| coerce(schema, format, x)));
+                }
+            }
+            None => {
+                if let Some(path) = path {
+                    return Err(Error::message(format!("mapping path '{}' without target", path)));
+                }
+            }
+        };
+
+        match schema.r#type().primitive_type().base_type() {
+            PrimitiveType::Object => {
+                let mut object = Map::new();
+
+                for property in schema.properties() {
+                    if let Some(value) = self.read(property.schema(), target, document)? {
+                        object.insert(property.name().to_string(), value);
+                    }
+                }
+
+                if object.is_empty() && schema.r#type().is_optional() {
+                    Ok(None)
+                } else {
+                    Ok(Some(Value::Object(object)))
+                }
+            }
+            PrimitiveType::Array => self.read_file_set(schema),
+            _ => Ok(None),
+        }
+    }
+
+    fn read_file_set(&mut self, schema: &'a Schema) -> Result<Option<Value>, Error> {
+        let item_schema = match schema.items() {
+            [item_schema] => self.root.resolve(item_schema),
+            _ => return Ok(None),
+        };
+
+        let target = match item_schema.mapping().and_then(Mapping::target) {
+            Some(target) => resolve_target(self.targets, target)?,
+            None => return Ok(None),
+        };
+
+        if !target.type_().is_file_set() {
+            return Err(Error::message(format!(
+                "array items must be mapped to a fileset target: {}",
+                target.location()
+            )));
+        }
+
+        let mut items = vec![];
+
+        for location in list_file_set(self.fs, target)? {
+            if let Some(document) = self.load(&location, *target.format())? {
+                if let Some(item) = self.read(item_schema, None, Some(&document))? {
+                    items.push(item);
+                }
+            }
+        }
+
+        if items.is_empty() && schema.r#type().is_optional() {
+            Ok(None)
+        } else {
+            Ok(Some(Value::Array(items)))
+        }
+    }
+}
+
+fn lookup<'a>(document: &'a Value, path: &str) -> Option<&'a Value> {
+    path.split('.')
+        .filter(|x| !x.is_empty())
+        .try_fold(document, |value, component| value.get(component))
+}
+
+// Redsocks booleans are rendered as `on` / `off`, `yes` / `no` are accepted as well
+fn parse_redsocks_bool(s: &str) -> Option<bool> {
+    match s {
+        "on" | "yes" => Some(true),
+        "off" | "no" => Some(false),
+        _ => s.parse().ok(),
+    }
+}
+
+// Some target formats (ini, text, ...) do not distinguish types, try to convert
+// strings to the expected primitive types
+fn coerce(schema: &Schema, format: TargetFormat, value: &Value) -> Value {
+    let s = match value.as_str() {
+        Some(s) => s,
+        None => return value.clone(),
+    };
+
+    let coerced = match schema.r#type().primitive_type().base_type() {
+        PrimitiveType::Integer | PrimitiveType::Port => s.parse::<i64>().ok().map(Value::from),
+        PrimitiveType::Number => s
+            .parse::<i64>()
+            .ok()
+            .map(Value::from)
+            .or_else(|| s.parse::<f64>().ok().and_then(Number::from_f64).map(Value::Number)),
+        PrimitiveType::Boolean if format.is_redsocks() => parse_redsocks_bool(s).map(Value::Bool),
+        PrimitiveType::Boolean => s.parse::<bool>().ok().map(Value::Bool),
+        PrimitiveType::StringList => Some(Value::Array(
+            schema.split_stringlist(s).into_iter().map(Value::String).collect(),
+        )),
+        _ => None,
+    };
+
+    coerced.unwrap_or_else(|| value.clone())
+}
+
+/// Reads existing target files back into the data
+///
+/// Values not mapped by the schema (`mapping.template` for example) are ignored.
+/// The result is not validated, use the [`validate`] function to do so.
+///
+/// # Arguments
+///
+/// * `schema` - JellySchema
+/// * `fs` - File system with target files
+///
+/// [`validate`]: ../validator/fn.validate.html
+pub fn read_data<F>(schema: &Schema, fs: &F) -> Result<Value, Error>
+where
+    F: FileSystem,
+{
+    let mut reader = Reader::new(schema, fs);
+    Ok(reader.read(schema, None, None)?.unwrap_or(Value::Null))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use serde_json::json;
+
+    use super::*;
+    use crate::{
+        mapper::{render_targets, write_targets, MemoryFileSystem},
+        schema::mapping::LocationPartition,
+        validator::validate,
+    };
+
+    const BALENA_OS: &str = include_str!("../../fuzz/seeds/balena-os.yml");
+
+    const NETWORKS: &str = r#"
+        mapping:
+          targets:
+            config:
+              type: file
+              format: json
+              location:
+                partition: boot
+                path: /config.json
+            networks:
+              type: fileset
+              format: json
+              location:
+                partition: boot
+                path: /networks
+        properties:
+          - hostname:
+              type: hostname
+              mapping:
+                target: config
+                path: hostname
+          - pollInterval:
+              type: integer
+              mapping:
+                target: config
+                path: poll.interval
+          - networks:
+              type: array?
+              items:
+                properties:
+                  - ssid:
+                      type: string
+                      mapping:
+                        path: wifi.ssid
+                mapping:
+                  target: networks
+    "#;
+
+    fn location(path: &str) -> TargetLocation {
+        TargetLocation::new(LocationPartition::Label("boot".to_string()), path)
+    }
+
+    fn fs(files: Vec<(&str, Value)>) -> MemoryFileSystem {
+        let files: HashMap<TargetLocation, Vec<u8>> = files
+            .into_iter()
+            .map(|(path, value)| (location(path), serde_json::to_vec(&value).unwrap()))
+            .collect();
+        files.into()
+    }
+
+    #[test]
+    fn balena_os_config_json() {
+        let schema: Schema = BALENA_OS.parse().unwrap();
+        let fs = MemoryFileSystem::from(
+            vec![(
+                TargetLocation::new(LocationPartition::Label("resin-boot".to_string()), "/config.json"),
+                serde_json::to_vec(&json!({
+                    "uuid": "not-mapped",
+                    "hostname": "balena",
+                    "persistentLogging": true,
+                    "os": {
+                        "udevRules": {
+                            "10": "rule"
+                        }
+                    }
+                }))
+                .unwrap(),
+            )]
+            .into_iter()
+            .collect::<HashMap<_, _>>(),
+        );
+
+        let data = read_data(&schema, &fs).unwrap();
+        assert_eq!(
+            data,
+            json!({
+                "advanced": {
+                    "hostname": "balena",
+                    "persistentLogging": true,
+                    "udevRules": {
+                        "10": "rule"
+                    }
+                },
+                "blobs": {},
+                "proxy": {
+                    "redsocks": {}
+                }
+            })
+        );
+    }
+
+    #[test]
+    fn file_set() {
+        let schema: Schema = NETWORKS.parse().unwrap();
+        let fs = fs(vec![
+            ("/config.json", json!({"hostname": "balena", "poll": {"interval": 10}})),
+            (
+                "/networks/b",
+                json!({"wifi": {"ssid": "second", "mode": "infrastructure"}}),
+            ),
+            ("/networks/a", json!({"wifi": {"ssid": "first"}})),
+        ]);
+
+        let data = read_data(&schema, &fs).unwrap();
+        assert_eq!(
+            data,
+            json!({
+                "hostname": "balena",
+                "pollInterval": 10,
+                "networks": [
+                    {"ssid": "first"},
+                    {"ssid": "second"}
+                ]
+            })
+        );
+        assert!(validate(&schema, &data).is_valid());
+    }
+
+    #[test]
+    fn missing_optional_file_set() {
+        let schema: Schema = NETWORKS.parse().unwrap();
+        let fs = fs(vec![(
+            "/config.json",
+            json!({"hostname": "balena", "poll": {"interval": 10}}),
+        )]);
+
+        let data = read_data(&schema, &fs).unwrap();
+        assert_eq!(data, json!({"hostname": "balena", "pollInterval": 10}));
+        assert!(validate(&schema, &data).is_valid());
+    }
+
+    #[test]
+    fn coerce_strings() {
+        let schema: Schema = NETWORKS.parse().unwrap();
+        let fs = fs(vec![(
+            "/config.json",
+            json!({"hostname": "balena", "poll": {"interval": "10"}}),
+        )]);
+
+        let data = read_data(&schema, &fs).unwrap();
+        assert_eq!(data, json!({"hostname": "balena", "pollInterval": 10}));
+    }
+
+    #[test]
+    fn render_read_round_trip() {
+        let schema: Schema = NETWORKS.parse().unwrap();
+        let data = json!({"hostname": "balena", "pollInterval": 10});
+
+        let mut fs = MemoryFileSystem::new();
+        write_targets(&mut fs, &render_targets(&schema, &data).unwrap()).unwrap();
+
+        assert_eq!(read_data(&schema, &fs).unwrap(), data);
+    }
+
+    #[test]
+    fn redsocks_round_trip() {
+        let schema: Schema = r#"
+            mapping:
+              targets:
+                redsocks:
+                  type: file
+                  format: redsocks
+                  location:
+                    partition: boot
+                    path: /redsocks.conf
+            properties:
+              - debug:
+                  type: boolean
+                  mapping:
+                    target: redsocks
+                    path: base.log_debug
+              - port:
+                  type: port
+                  mapping:
+                    target: redsocks
+                    path: redsocks.port
+        "#
+        .parse()
+        .unwrap();
+        let data = json!({"debug": false, "port": 1080});
+
+        let mut fs = MemoryFileSystem::new();
+        write_targets(&mut fs, &render_targets(&schema, &data).unwrap()).unwrap();
+
+        let read = read_data(&schema, &fs).unwrap();
+        assert_eq!(read, data);
+        assert!(validate(&schema, &read).is_valid());
+
+        let mut files = HashMap::new();
+        files.insert(
+            location("/redsocks.conf"),
+            b"base {\n    log_debug = yes;\n}\n".to_vec(),
+        );
+        assert_eq!(
+            read_data(&schema, &MemoryFileSystem::from(files)).unwrap(),
+            json!({"debug": true})
+        );
+    }
+
+    #[test]
+    fn fail_on_invalid_file() {
+        let schema: Schema = NETWORKS.parse().unwrap();
+        let mut files = HashMap::new();
+        files.insert(location("/config.json"), b"{".to_vec());
+
+        assert!(read_data(&schema, &MemoryFileSystem::from(files)).is_err());
+    }
+}
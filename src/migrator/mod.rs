@@ -0,0 +1,448 @@
+//! A module containing the data migrator.
+//!
+//! Upgrades data documents to the current schema revision with the migrations declared
+//! in the schema (see the [`data_migrations`] module for the schema side).
+//!
+//! # Examples
+//!
+//! ```
+//! use jellyschema::migrator::upgrade_data;
+//! use jellyschema::schema::Schema;
+//! use serde_json::json;
+//!
+//! let schema: Schema = r#"
+//!   version: 1
+//!   migrations:
+//!     - revision: 2
+//!       steps:
+//!         - rename:
+//!             path: ssid
+//!             name: networkName
+//!   properties:
+//!     - networkName:
+//!         type: string
+//! "#.parse().unwrap();
+//!
+//! let data = upgrade_data(&schema, &json!({"ssid": "foo"}), 1).unwrap();
+//! assert_eq!(data, json!({"networkName": "foo"}));
+//! ```
+//!
+//! [`data_migrations`]: ../schema/data_migrations/index.html
+use std::{error, fmt};
+
+use balena_temen::ast::{Identifier, IdentifierValue};
+use serde_json::{Map, Value};
+
+use crate::{
+    formula,
+    schema::{
+        data_migrations::{DataPath, PathComponent, Step},
+        Schema,
+    },
+    validator::{self, ValidationError, ValidationState},
+};
+
+/// Data migration error
+#[derive(Debug)]
+pub struct MigrationError {
+    data_path: String,
+    message: String,
+    validation: ValidationState,
+}
+
+impl MigrationError {
+    fn new<S1, S2>(data_path: S1, message: S2) -> MigrationError
+    where
+        S1: Into<String>,
+        S2: Into<String>,
+    {
+        MigrationError {
+            data_path: data_path.into(),
+            message: message.into(),
+            validation: ValidationState::new(),
+        }
+    }
+
+    pub fn data_path(&self) -> &str {
+        &self.data_path
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// Validation errors of the migrated data, empty if the migration itself failed
+    pub fn validation_errors(&self) -> &[ValidationError] {
+        self.validation.errors()
+    }
+}
+
+impl fmt::Display for MigrationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "data path: '{}', message: '{}'", self.data_path, self.message)
+    }
+}
+
+impl error::Error for MigrationError {}
+
+fn data_path(identifier: &Identifier) -> String {
+    let mut result = String::new();
+
+    for value in &identifier.values {
+        match value {
+            IdentifierValue::Name(name) if result.is_empty() => result.push_str(name),
+            IdentifierValue::Name(name) => {
+                result.push('.');
+                result.push_str(name);
+            }
+            IdentifierValue::Index(index) => result.push_str(&format!("[{}]", index)),
+            _ => {}
+        }
+    }
+
+    result
+}
+
+fn get_mut<'a>(data: &'a mut Value, identifier: &Identifier) -> Option<&'a mut Value> {
+    identifier
+        .values
+        .iter()
+        .try_fold(data, |current, component| match component {
+            IdentifierValue::Name(name) => current.get_mut(name),
+            IdentifierValue::Index(index) => current.get_mut(*index as usize),
+            _ => None,
+        })
+}
+
+// Positions of all existing values matching the path components
+fn expand(data: &Value, components: &[PathComponent], position: Identifier, positions: &mut Vec<Identifier>) {
+    let (component, rest) = match components.split_first() {
+        Some(x) => x,
+        None => {
+            positions.push(position);
+            return;
+        }
+    };
+
+    match (component, data) {
+        (PathComponent::Name(name), Value::Object(object)) => {
+            if let Some(value) = object.get(name) {
+                expand(value, rest, position.name(name.as_str()), positions);
+            }
+        }
+        (PathComponent::Index(index), Value::Array(items)) => {
+            if let Some(value) = items.get(*index) {
+                expand(value, rest, position.index(*index as isize), positions);
+            }
+        }
+        (PathComponent::Wildcard, Value::Array(items)) => {
+            for (index, value) in items.iter().enumerate() {
+                expand(value, rest, position.clone().index(index as isize), positions);
+            }
+        }
+        _ => {}
+    };
+}
+
+// Positions of all existing parents of values matching the path & the last path component
+fn expand_parents<'a>(data: &Value, path: &'a DataPath) -> (Vec<Identifier>, &'a PathComponent) {
+    let (last, parent) = path.components().split_last().expect("data path can't be empty");
+    let mut positions = vec![];
+    expand(data, parent, Identifier::default(), &mut positions);
+    (positions, last)
+}
+
+// Removes the value from its parent, returns `None` if it does not exist
+fn remove(parent: &mut Value, component: &PathComponent) -> Option<Value> {
+    match (component, parent) {
+        (PathComponent::Name(name), Value::Object(object)) => object.remove(name),
+        (PathComponent::Index(index), Value::Array(items)) if *index < items.len() => Some(items.remove(*index)),
+        _ => None,
+    }
+}
+
+// Stores the value at the path, missing objects are created, array items must exist
+fn store(data: &mut Value, path: &DataPath, value: Value) -> Result<(), String> {
+    let mut current = data;
+
+    for component in path.components() {
+        if current.is_null() {
+            *current = Value::Object(Map::new());
+        }
+
+        current = match (component, current) {
+            (PathComponent::Name(name), Value::Object(object)) => object.entry(name.as_str()).or_insert(Value::Null),
+            (PathComponent::Index(index), Value::Array(items)) if *index < items.len() => &mut items[*index],
+            _ => return Err(format!("unable to store value at '{}'", path)),
+        };
+    }
+
+    *current = value;
+    Ok(())
+}
+
+fn apply_step(step: &Step, data: &mut Value) -> Result<(), MigrationError> {
+    match step {
+        Step::Rename { path, name } => {
+            let (parents, last) = expand_parents(data, path);
+
+            for parent in parents {
+                let object = match get_mut(data, &parent) {
+                    Some(Value::Object(object)) => object,
+                    _ => continue,
+                };
+                if let PathComponent::Name(old_name) = last {
+                    if let Some(value) = object.remove(old_name) {
+                        object.insert(name.clone(), value);
+                    }
+                }
+            }
+        }
+        Step::Move { from, to } => {
+            let (parents, last) = expand_parents(data, from);
+
+            let value = parents
+                .first()
+                .and_then(|parent| get_mut(data, parent))
+                .and_then(|parent| remove(parent, last));
+
+            if let Some(value) = value {
+                store(data, to, value).map_err(|e| MigrationError::new(from.to_string(), e))?;
+            }
+        }
+        Step::Transform { path, formula } => {
+            let mut positions = vec![];
+            expand(data, path.components(), Identifier::default(), &mut positions);
+
+            for position in positions {
+                let value = formula::evaluate(formula, &position, data).map_err(|e| {
+                    MigrationError::new(data_path(&position), format!("unable to evaluate formula: {}", e))
+                })?;
+                if let Some(current) = get_mut(data, &position) {
+                    *current = value;
+                }
+            }
+        }
+        Step::Drop(path) => {
+            let (parents, last) = expand_parents(data, path);
+
+            // Reverse order keeps indexes of remaining array items valid
+            for parent in parents.iter().rev() {
+                if let Some(parent) = get_mut(data, parent) {
+                    remove(parent, last);
+                }
+            }
+        }
+    };
+
+    Ok(())
+}
+
+/// Upgrades the data to the current schema revision and validates them
+///
+/// # Arguments
+///
+/// * `schema` - JellySchema with migrations
+/// * `data` - JSON data to migrate
+/// * `revision` - Schema revision the data were created for
+pub fn upgrade_data(schema: &Schema, data: &Value, revision: u32) -> Result<Value, MigrationError> {
+    if revision == 0 || revision > schema.revision() {
+        return Err(MigrationError::new(
+            "",
+            format!(
+                "unsupported data revision {}, current schema revision is {}",
+                revision,
+                schema.revision()
+            ),
+        ));
+    }
+
+    let mut data = data.clone();
+
+    for migration in schema.migrations().iter().filter(|x| x.revision() > revision) {
+        for step in migration.steps() {
+            apply_step(step, &mut data).map_err(|e| MigrationError {
+                message: format!("migration to revision {} failed: {}", migration.revision(), e.message),
+                ..e
+            })?;
+        }
+    }
+
+    let state = validator::validate(schema, &data);
+    if !state.is_valid() {
+        return Err(MigrationError {
+            data_path: String::new(),
+            message: "migrated data are not valid".to_string(),
+            validation: state,
+        });
+    }
+
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn schema(migrations: &str, properties: &str) -> Schema {
+        format!("version: 1\nmigrations:\n{}\nproperties:\n{}", migrations, properties)
+            .parse()
+            .unwrap()
+    }
+
+    #[test]
+    fn rename_nested_properties() {
+        let schema = schema(
+            r#"
+  - revision: 2
+    steps:
+      - rename: { path: "networks[*].ssid", name: name }
+"#,
+            r#"
+  - networks:
+      type: array
+      items:
+        properties:
+          - name:
+              type: string
+"#,
+        );
+        let data = json!({"networks": [{"ssid": "a"}, {"ssid": "b"}]});
+        assert_eq!(
+            upgrade_data(&schema, &data, 1).unwrap(),
+            json!({"networks": [{"name": "a"}, {"name": "b"}]})
+        );
+    }
+
+    #[test]
+    fn apply_steps_in_order() {
+        let schema = schema(
+            r#"
+  - revision: 2
+    steps:
+      - move: { from: hostname, to: advanced.hostname }
+      - drop: legacy
+  - revision: 3
+    steps:
+      - transform: { path: advanced.hostname, formula: this | upper }
+      - transform: { path: retries, formula: this * 2 }
+"#,
+            r#"
+  - advanced:
+      properties:
+        - hostname:
+            type: string
+  - retries:
+      type: integer
+"#,
+        );
+        assert_eq!(schema.revision(), 3);
+
+        let data = json!({"hostname": "balena", "legacy": true, "retries": 2});
+        assert_eq!(
+            upgrade_data(&schema, &data, 1).unwrap(),
+            json!({"advanced": {"hostname": "BALENA"}, "retries": 4})
+        );
+
+        let data = json!({"advanced": {"hostname": "balena"}, "retries": 2});
+        assert_eq!(
+            upgrade_data(&schema, &data, 2).unwrap(),
+            json!({"advanced": {"hostname": "BALENA"}, "retries": 4})
+        );
+
+        assert_eq!(upgrade_data(&schema, &data, 3).unwrap(), data);
+    }
+
+    #[test]
+    fn skip_missing_values() {
+        let schema = schema(
+            r#"
+  - revision: 2
+    steps:
+      - rename: { path: a, name: b }
+      - move: { from: c, to: d }
+      - transform: { path: e, formula: this + 1 }
+      - drop: "f[1]"
+"#,
+            r#"
+  - b:
+      type: string?
+"#,
+        );
+        assert_eq!(upgrade_data(&schema, &json!({}), 1).unwrap(), json!({}));
+    }
+
+    #[test]
+    fn drop_array_items() {
+        let schema = schema(
+            r#"
+  - revision: 2
+    steps:
+      - drop: "items[*].secret"
+      - drop: "items[0]"
+"#,
+            r#"
+  - items:
+      type: array
+"#,
+        );
+        let data = json!({"items": [{"secret": 1}, {"secret": 2, "name": "b"}]});
+        assert_eq!(
+            upgrade_data(&schema, &data, 1).unwrap(),
+            json!({"items": [{"name": "b"}]})
+        );
+    }
+
+    #[test]
+    fn fail_on_invalid_migrated_data() {
+        let schema = schema(
+            r#"
+  - revision: 2
+    steps:
+      - rename: { path: port, name: retries }
+"#,
+            r#"
+  - retries:
+      type: integer
+"#,
+        );
+        let error = upgrade_data(&schema, &json!({"port": "foo"}), 1).unwrap_err();
+        assert_eq!(error.message(), "migrated data are not valid");
+        assert_eq!(error.validation_errors()[0].data_path(), "retries");
+    }
+
+    #[test]
+    fn fail_on_unsupported_revision() {
+        let schema = schema("  - revision: 2", "  - a:\n      type: string?");
+        assert!(upgrade_data(&schema, &json!({}), 3).is_err());
+        assert!(upgrade_data(&schema, &json!({}), 0).is_err());
+    }
+
+    #[test]
+    fn fail_on_step_error() {
+        let schema = schema(
+            r#"
+  - revision: 2
+    steps:
+      - move: { from: a, to: "b.c" }
+"#,
+            "  - a:\n      type: string?",
+        );
+        let error = upgrade_data(&schema, &json!({"a": "x", "b": 1}), 1).unwrap_err();
+        assert_eq!(error.data_path(), "a");
+        assert_eq!(
+            error.message(),
+            "migration to revision 2 failed: unable to store value at 'b.c'"
+        );
+    }
+
+    #[test]
+    fn migrations_in_root_only() {
+        let error = "properties:\n  - a:\n      migrations: []"
+            .parse::<Schema>()
+            .unwrap_err();
+        assert_eq!(error.msg(), "migrations are allowed in the root schema only");
+    }
+}
@@ -0,0 +1,834 @@
+use regex::Regex;
+use serde_json::{Number, Value};
+
+use crate::{
+    error::Error,
+    schema::{mapping::Mapping, EnumEntry, PrimitiveType, Property, Schema, Type, UniqueItems, Version},
+};
+
+/// Schema builder
+///
+/// Builds the same schema as the deserializer does. Invalid values (unsupported version,
+/// invalid pattern, ...) are reported by the [`build`] method, the first error wins.
+///
+/// # Examples
+///
+/// ```
+/// use jellyschema::schema::Schema;
+///
+/// let schema = Schema::builder()
+///     .version(1)
+///     .property("ssid", Schema::string().min_length(1))
+///     .property("passphrase", Schema::password().optional().min_length(8))
+///     .build()
+///     .unwrap();
+///
+/// assert_eq!(schema.properties()[1].schema().r#type().to_string(), "password?");
+/// ```
+///
+/// [`build`]: #method.build
+#[derive(Debug, Default)]
+pub struct SchemaBuilder {
+    schema: Schema,
+    error: Option<Error>,
+}
+
+macro_rules! primitive_types {
+    ($($name:ident => $primitive_type:ident),* $(,)?) => {
+        impl SchemaBuilder {
+            $(
+                #[doc = concat!("Sets the `", stringify!($name), "` type")]
+                pub fn $name(self) -> SchemaBuilder {
+                    self.r#type(Type::new_required(PrimitiveType::$primitive_type))
+                }
+            )*
+        }
+
+        impl Schema {
+            $(
+                #[doc = concat!("Starts building a schema of the `", stringify!($name), "` type")]
+                pub fn $name() -> SchemaBuilder {
+                    SchemaBuilder::new().$name()
+                }
+            )*
+        }
+    };
+}
+
+primitive_types! {
+    object => Object,
+    boolean => Boolean,
+    string => String,
+    password => Password,
+    hostname => Hostname,
+    integer => Integer,
+    array => Array,
+    number => Number,
+    datetime => DateTime,
+    date => Date,
+    time => Time,
+    email => Email,
+    ipv4 => IPv4,
+    ipv6 => IPv6,
+    uri => Uri,
+    file => File,
+    port => Port,
+    text => Text,
+    stringlist => StringList,
+    dnsmasq_address => DNSMasqAddress,
+    chrony_address => ChronyAddress,
+    iptables_address => IPTablesAddress,
+}
+
+impl SchemaBuilder {
+    pub fn new() -> SchemaBuilder {
+        // `SchemaBuilder::default` is the `default` keyword setter
+        <SchemaBuilder as Default>::default()
+    }
+
+    /// Builds the schema
+    ///
+    /// References are checked and combined schemas inherit types as if the schema
+    /// was deserialized.
+    pub fn build(self) -> Result<Schema, Error> {
+        match self.error {
+            Some(error) => Err(error),
+            None => self.schema.finalize(),
+        }
+    }
+
+    fn fail(mut self, error: Error) -> SchemaBuilder {
+        if self.error.is_none() {
+            self.error = Some(error);
+        }
+        self
+    }
+
+    // Nested schemas are not finalized, references point to the root schema definitions
+    fn nested<S>(&mut self, schema: S) -> Schema
+    where
+        S: Into<SchemaBuilder>,
+    {
+        let builder = schema.into();
+
+        if self.error.is_none() {
+            self.error = builder.error;
+        }
+
+        builder.schema
+    }
+
+    fn set_number<V>(self, keyword: &str, value: V, set: fn(&mut Schema, Number)) -> SchemaBuilder
+    where
+        V: Into<Value>,
+    {
+        match value.into() {
+            Value::Number(number) => {
+                let mut builder = self;
+                set(&mut builder.schema, number);
+                builder
+            }
+            value => self.fail(Error::message(format!("{} must be a number: {}", keyword, value))),
+        }
+    }
+}
+
+impl From<Schema> for SchemaBuilder {
+    fn from(schema: Schema) -> SchemaBuilder {
+        SchemaBuilder { schema, error: None }
+    }
+}
+
+//
+// Reusable definitions
+//
+impl SchemaBuilder {
+    pub fn version(self, version: u8) -> SchemaBuilder {
+        match Version::new(version) {
+            Ok(version) => {
+                let mut builder = self;
+                builder.schema.version = Some(version);
+                builder
+            }
+            Err(e) => self.fail(e),
+        }
+    }
+
+    pub fn definition<N, S>(mut self, name: N, schema: S) -> SchemaBuilder
+    where
+        N: Into<String>,
+        S: Into<SchemaBuilder>,
+    {
+        let schema = self.nested(schema);
+        self.schema.definitions.insert(name.into(), schema);
+        self
+    }
+
+    /// Reference to the definition (`#/definitions/<name>`)
+    pub fn r#ref<S>(mut self, reference: S) -> SchemaBuilder
+    where
+        S: Into<String>,
+    {
+        self.schema.r#ref = Some(reference.into());
+        self
+    }
+}
+
+//
+// Combinator keywords
+//
+impl SchemaBuilder {
+    pub fn one_of<I, S>(mut self, schemas: I) -> SchemaBuilder
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<SchemaBuilder>,
+    {
+        for schema in schemas {
+            let schema = self.nested(schema);
+            self.schema.one_of.push(schema);
+        }
+        self
+    }
+
+    pub fn any_of<I, S>(mut self, schemas: I) -> SchemaBuilder
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<SchemaBuilder>,
+    {
+        for schema in schemas {
+            let schema = self.nested(schema);
+            self.schema.any_of.push(schema);
+        }
+        self
+    }
+
+    pub fn all_of<I, S>(mut self, schemas: I) -> SchemaBuilder
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<SchemaBuilder>,
+    {
+        for schema in schemas {
+            let schema = self.nested(schema);
+            self.schema.all_of.push(schema);
+        }
+        self
+    }
+}
+
+//
+// Mapping extension
+//
+impl SchemaBuilder {
+    pub fn mapping(mut self, mapping: Mapping) -> SchemaBuilder {
+        self.schema.mapping = Some(mapping);
+        self
+    }
+}
+
+//
+// Any instance type
+//
+impl SchemaBuilder {
+    pub fn r#type(mut self, r#type: Type) -> SchemaBuilder {
+        self.schema.r#type = Some(r#type);
+        self
+    }
+
+    /// Makes the type optional, `object` is used if the type was not set
+    pub fn optional(self) -> SchemaBuilder {
+        let primitive_type = self.schema.r#type().primitive_type().clone();
+        self.r#type(Type::new_optional(primitive_type))
+    }
+
+    pub fn r#const<V>(mut self, value: V) -> SchemaBuilder
+    where
+        V: Into<Value>,
+    {
+        self.schema.r#const = Some(value.into());
+        self
+    }
+
+    pub fn r#default<V>(mut self, value: V) -> SchemaBuilder
+    where
+        V: Into<Value>,
+    {
+        self.schema.r#default = Some(value.into());
+        self
+    }
+
+    /// Adds the `enum` entry without a title
+    pub fn enum_value<V>(self, value: V) -> SchemaBuilder
+    where
+        V: Into<Value>,
+    {
+        match EnumEntry::new(value) {
+            Ok(entry) => self.enum_entry(entry),
+            Err(e) => self.fail(e),
+        }
+    }
+
+    pub fn enum_entry(mut self, entry: EnumEntry) -> SchemaBuilder {
+        self.schema.r#enum.push(entry);
+        self
+    }
+
+    pub fn formula<S>(mut self, formula: S) -> SchemaBuilder
+    where
+        S: Into<String>,
+    {
+        self.schema.formula = Some(formula.into());
+        self
+    }
+
+    pub fn when<S>(self, condition: S) -> SchemaBuilder
+    where
+        S: Into<String>,
+    {
+        let condition = condition.into();
+
+        match crate::formula::parse(&condition) {
+            Ok(_) => {
+                let mut builder = self;
+                builder.schema.when = Some(condition);
+                builder
+            }
+            Err(e) => self.fail(Error::message(format!("invalid condition '{}': {}", condition, e))),
+        }
+    }
+
+    pub fn read_only(mut self, read_only: bool) -> SchemaBuilder {
+        self.schema.read_only = read_only;
+        self
+    }
+
+    pub fn write_only(mut self, write_only: bool) -> SchemaBuilder {
+        self.schema.write_only = write_only;
+        self
+    }
+
+    pub fn placeholder<S>(mut self, placeholder: S) -> SchemaBuilder
+    where
+        S: Into<String>,
+    {
+        self.schema.placeholder = Some(placeholder.into());
+        self
+    }
+
+    pub fn hidden(mut self, hidden: bool) -> SchemaBuilder {
+        self.schema.hidden = hidden;
+        self
+    }
+}
+
+//
+// Object validation keywords
+//
+impl SchemaBuilder {
+    pub fn property<N, S>(mut self, name: N, schema: S) -> SchemaBuilder
+    where
+        N: Into<String>,
+        S: Into<SchemaBuilder>,
+    {
+        let schema = self.nested(schema);
+        self.schema.properties.push(Property::new(name, schema));
+        self
+    }
+
+    pub fn keys<S>(mut self, schema: S) -> SchemaBuilder
+    where
+        S: Into<SchemaBuilder>,
+    {
+        let schema = self.nested(schema);
+        self.schema.keys = Some(Box::new(schema));
+        self
+    }
+
+    pub fn values<S>(mut self, schema: S) -> SchemaBuilder
+    where
+        S: Into<SchemaBuilder>,
+    {
+        let schema = self.nested(schema);
+        self.schema.values = Some(Box::new(schema));
+        self
+    }
+
+    pub fn additional_properties(mut self, additional_properties: bool) -> SchemaBuilder {
+        self.schema.additional_properties = additional_properties;
+        self
+    }
+}
+
+//
+// StringList keywords
+//
+impl SchemaBuilder {
+    pub fn separator<S>(mut self, separator: S) -> SchemaBuilder
+    where
+        S: Into<String>,
+    {
+        self.schema.separator = Some(separator.into());
+        self
+    }
+}
+
+//
+// IPTablesAddress keywords
+//
+impl SchemaBuilder {
+    pub fn strict_mask(mut self, strict_mask: bool) -> SchemaBuilder {
+        self.schema.strict_mask = strict_mask;
+        self
+    }
+}
+
+//
+// Annotation keywords
+//
+impl SchemaBuilder {
+    pub fn title<S>(mut self, title: S) -> SchemaBuilder
+    where
+        S: Into<String>,
+    {
+        self.schema.title = Some(title.into());
+        self
+    }
+
+    pub fn help<S>(mut self, help: S) -> SchemaBuilder
+    where
+        S: Into<String>,
+    {
+        self.schema.help = Some(help.into());
+        self
+    }
+
+    pub fn warning<S>(mut self, warning: S) -> SchemaBuilder
+    where
+        S: Into<String>,
+    {
+        self.schema.warning = Some(warning.into());
+        self
+    }
+
+    pub fn description<S>(mut self, description: S) -> SchemaBuilder
+    where
+        S: Into<String>,
+    {
+        self.schema.description = Some(description.into());
+        self
+    }
+
+    pub fn collapsible(mut self, collapsible: bool) -> SchemaBuilder {
+        self.schema.collapsible = Some(collapsible);
+        self
+    }
+
+    pub fn collapsed(mut self, collapsed: bool) -> SchemaBuilder {
+        self.schema.collapsed = Some(collapsed);
+        self
+    }
+}
+
+//
+// Array validation keywords
+//
+impl SchemaBuilder {
+    /// Adds the `items` schema, data items must be valid against any of them
+    pub fn item<S>(mut self, schema: S) -> SchemaBuilder
+    where
+        S: Into<SchemaBuilder>,
+    {
+        let schema = self.nested(schema);
+        self.schema.items.push(schema);
+        self
+    }
+
+    pub fn max_items(mut self, max_items: usize) -> SchemaBuilder {
+        self.schema.max_items = Some(max_items);
+        self
+    }
+
+    pub fn min_items(mut self, min_items: usize) -> SchemaBuilder {
+        self.schema.min_items = Some(min_items);
+        self
+    }
+
+    pub fn unique_items(mut self, unique_items: bool) -> SchemaBuilder {
+        self.schema.unique_items = UniqueItems::Boolean(unique_items);
+        self
+    }
+
+    /// Items must be unique by the values at the paths (`wifi.ssid` for example)
+    pub fn unique_items_by<I, S>(mut self, paths: I) -> SchemaBuilder
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.schema.unique_items = UniqueItems::Paths(paths.into_iter().map(Into::into).collect());
+        self
+    }
+
+    pub fn orderable(mut self, orderable: bool) -> SchemaBuilder {
+        self.schema.orderable = Some(orderable);
+        self
+    }
+
+    pub fn addable(mut self, addable: bool) -> SchemaBuilder {
+        self.schema.addable = Some(addable);
+        self
+    }
+
+    pub fn removable(mut self, removable: bool) -> SchemaBuilder {
+        self.schema.removable = Some(removable);
+        self
+    }
+}
+
+//
+// Number validation keywords
+//
+impl SchemaBuilder {
+    pub fn multiple_of<V: Into<Value>>(self, value: V) -> SchemaBuilder {
+        self.set_number("multipleOf", value, |schema, x| schema.multiple_of = Some(x))
+    }
+
+    pub fn max<V: Into<Value>>(self, value: V) -> SchemaBuilder {
+        self.set_number("max", value, |schema, x| schema.max = Some(x))
+    }
+
+    pub fn exclusive_max<V: Into<Value>>(self, value: V) -> SchemaBuilder {
+        self.set_number("exclusiveMax", value, |schema, x| schema.exclusive_max = Some(x))
+    }
+
+    pub fn min<V: Into<Value>>(self, value: V) -> SchemaBuilder {
+        self.set_number("min", value, |schema, x| schema.min = Some(x))
+    }
+
+    pub fn exclusive_min<V: Into<Value>>(self, value: V) -> SchemaBuilder {
+        self.set_number("exclusiveMin", value, |schema, x| schema.exclusive_min = Some(x))
+    }
+}
+
+//
+// String based types validation keywords
+//
+impl SchemaBuilder {
+    pub fn max_length(mut self, max_length: usize) -> SchemaBuilder {
+        self.schema.max_length = Some(max_length);
+        self
+    }
+
+    pub fn min_length(mut self, min_length: usize) -> SchemaBuilder {
+        self.schema.min_length = Some(min_length);
+        self
+    }
+
+    pub fn pattern(self, pattern: &str) -> SchemaBuilder {
+        match Regex::new(pattern) {
+            Ok(regex) => {
+                let mut builder = self;
+                builder.schema.pattern = Some(regex);
+                builder
+            }
+            Err(e) => self.fail(Error::message(e.to_string())),
+        }
+    }
+}
+
+//
+// Vendor extensions
+//
+impl SchemaBuilder {
+    /// Adds the vendor extension keyword, no prefix is required
+    pub fn extension<S, V>(mut self, keyword: S, value: V) -> SchemaBuilder
+    where
+        S: Into<String>,
+        V: Into<Value>,
+    {
+        self.schema.extensions.insert(keyword.into(), value.into());
+        self
+    }
+}
+
+impl Schema {
+    pub fn builder() -> SchemaBuilder {
+        SchemaBuilder::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::schema::{
+        mapping::{FileName, LocationPartition, RawTarget, Target, TargetFormat, TargetLocation, TargetType},
+        ParseOptions,
+    };
+
+    fn assert_same(built: SchemaBuilder, parsed: &str) {
+        let built = serde_json::to_value(built.build().unwrap()).unwrap();
+        let parsed = serde_json::to_value(ParseOptions::new().extension_prefix("x-").parse(parsed).unwrap()).unwrap();
+        assert_eq!(built, parsed);
+    }
+
+    #[test]
+    fn annotations_and_any_type_keywords() {
+        let built = Schema::builder()
+            .version(1)
+            .title("Device")
+            .help("help")
+            .warning("warning")
+            .description("description")
+            .collapsible(true)
+            .collapsed(false)
+            .property(
+                "mode",
+                Schema::string()
+                    .enum_value("a")
+                    .enum_entry(EnumEntry::with_title("B", "b"))
+                    .r#default("a")
+                    .placeholder("mode")
+                    .read_only(true),
+            )
+            .property("secret", Schema::string().write_only(true).hidden(true).r#const("foo"))
+            .property("computed", Schema::integer().formula("2 + 3"))
+            .property("conditional", Schema::boolean().optional().when("mode == \"b\""))
+            .extension("x-owner", "os");
+
+        assert_same(
+            built,
+            r#"
+                version: 1
+                title: Device
+                help: help
+                warning: warning
+                description: description
+                collapsible: true
+                collapsed: false
+                x-owner: os
+                properties:
+                  - mode:
+                      type: string
+                      enum:
+                        - a
+                        - title: B
+                          value: b
+                      default: a
+                      placeholder: mode
+                      readOnly: true
+                  - secret:
+                      type: string
+                      writeOnly: true
+                      hidden: true
+                      const: foo
+                  - computed:
+                      type: integer
+                      formula: 2 + 3
+                  - conditional:
+                      type: boolean?
+                      when: mode == "b"
+            "#,
+        );
+    }
+
+    #[test]
+    fn validation_keywords() {
+        let built = Schema::object()
+            .additional_properties(true)
+            .keys(Schema::string().pattern("^[a-z]+$"))
+            .values(Schema::stringlist().separator(","))
+            .property(
+                "ports",
+                Schema::array()
+                    .item(Schema::port().multiple_of(2).min(10).max(100))
+                    .min_items(1)
+                    .max_items(3)
+                    .unique_items(true)
+                    .orderable(false)
+                    .addable(true)
+                    .removable(false),
+            )
+            .property(
+                "networks",
+                Schema::array()
+                    .item(Schema::builder().property("ssid", Schema::string().min_length(1).max_length(32)))
+                    .unique_items_by(vec!["ssid"]),
+            )
+            .property("ratio", Schema::number().exclusive_min(0).exclusive_max(1.5));
+
+        assert_same(
+            built,
+            r#"
+                type: object
+                additionalProperties: true
+                keys:
+                  type: string
+                  pattern: "^[a-z]+$"
+                values:
+                  type: stringlist
+                  separator: ","
+                properties:
+                  - ports:
+                      type: array
+                      items:
+                        type: port
+                        multipleOf: 2
+                        min: 10
+                        max: 100
+                      minItems: 1
+                      maxItems: 3
+                      uniqueItems: true
+                      orderable: false
+                      addable: true
+                      removable: false
+                  - networks:
+                      type: array
+                      items:
+                        properties:
+                          - ssid:
+                              type: string
+                              minLength: 1
+                              maxLength: 32
+                      uniqueItems:
+                        - ssid
+                  - ratio:
+                      type: number
+                      exclusiveMin: 0
+                      exclusiveMax: 1.5
+            "#,
+        );
+    }
+
+    #[test]
+    fn definitions_and_combinators() {
+        let built = Schema::builder()
+            .definition("name", Schema::string().min_length(1))
+            .property("first", Schema::builder().r#ref("#/definitions/name"))
+            .property(
+                "mode",
+                Schema::string()
+                    .one_of(vec![Schema::builder().r#const("a"), Schema::builder().r#const("b")])
+                    .any_of(vec![Schema::builder().min_length(1)])
+                    .all_of(vec![Schema::builder().max_length(2)]),
+            );
+
+        let parsed = r##"
+            definitions:
+              name:
+                type: string
+                minLength: 1
+            properties:
+              - first:
+                  $ref: "#/definitions/name"
+              - mode:
+                  type: string
+                  oneOf:
+                    - const: a
+                    - const: b
+                  anyOf:
+                    - minLength: 1
+                  allOf:
+                    - maxLength: 2
+        "##;
+        assert_same(built, parsed);
+
+        // Combined schemas inherit the type
+        let schema = Schema::string().one_of(vec![Schema::builder()]).build().unwrap();
+        assert_eq!(schema.one_of()[0].r#type(), &Type::new_required(PrimitiveType::String));
+    }
+
+    #[test]
+    fn mapping() {
+        let location = TargetLocation::new(LocationPartition::Label("resin-boot".to_string()), "/config.json");
+        let built = Schema::builder()
+            .mapping(
+                Mapping::new()
+                    .with_named_target("config", RawTarget::new(TargetType::File, TargetFormat::Json, location)),
+            )
+            .property(
+                "hostname",
+                Schema::hostname().mapping(
+                    Mapping::new()
+                        .with_target(Target::Reference("config".to_string()))
+                        .with_path("hostname"),
+                ),
+            )
+            .property(
+                "networks",
+                Schema::array().item(
+                    Schema::builder().mapping(
+                        Mapping::new()
+                            .with_target(Target::Raw(
+                                RawTarget::new(
+                                    TargetType::FileSet,
+                                    TargetFormat::Ini,
+                                    TargetLocation::new(LocationPartition::Index(1), "/system-connections"),
+                                )
+                                .with_glob("*.ini"),
+                            ))
+                            .with_filename(FileName::Formula("this.ssid".to_string()))
+                            .with_template(json!({"connection": {"type": "wifi"}})),
+                    ),
+                ),
+            );
+
+        assert_same(
+            built,
+            r#"
+                mapping:
+                  targets:
+                    config:
+                      type: file
+                      format: json
+                      location:
+                        partition: resin-boot
+                        path: /config.json
+                properties:
+                  - hostname:
+                      type: hostname
+                      mapping:
+                        target: config
+                        path: hostname
+                  - networks:
+                      type: array
+                      items:
+                        mapping:
+                          target:
+                            type: fileset
+                            format: ini
+                            glob: "*.ini"
+                            location:
+                              partition: 1
+                              path: /system-connections
+                          filename:
+                            formula: this.ssid
+                          template:
+                            connection:
+                              type: wifi
+            "#,
+        );
+    }
+
+    #[test]
+    fn report_first_error() {
+        let error = Schema::builder()
+            .property("a", Schema::string().pattern("("))
+            .property("b", Schema::string().when("=="))
+            .build()
+            .unwrap_err();
+        assert!(error.msg().contains("regex parse error"), "{}", error);
+
+        assert!(Schema::builder().version(3).build().is_err());
+        assert!(Schema::number().min("foo").build().is_err());
+        assert!(Schema::string().enum_value(json!([1])).build().is_err());
+    }
+
+    #[test]
+    fn check_references() {
+        let error = Schema::builder()
+            .property("a", Schema::builder().r#ref("#/definitions/missing"))
+            .build()
+            .unwrap_err();
+        assert!(error.msg().contains("missing"), "{}", error);
+    }
+}
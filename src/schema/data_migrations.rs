@@ -0,0 +1,361 @@
+//! Data migrations between schema revisions
+//!
+//! Schema changes (renamed property, narrowed type, ...) break existing data documents.
+//! The root schema can declare ordered migrations, each one upgrades data documents from
+//! the previous revision to its `revision`. The schema without migrations is at revision `1`,
+//! the first migration must be at revision `2`, the next one at `3`, ...
+//!
+//! ```yaml
+//! version: 1
+//! migrations:
+//!   - revision: 2
+//!     steps:
+//!       - rename:
+//!           path: network.ssid
+//!           name: networkName
+//!       - move:
+//!           from: hostname
+//!           to: advanced.hostname
+//!       - transform:
+//!           path: networks[*].id
+//!           formula: this | slugify
+//!       - drop: legacy
+//! ```
+//!
+//! Paths are data paths (`networks[0].ssid`), the `[*]` wildcard matches all array items.
+//! Migrations are applied with the [`upgrade_data`] function. Not to be confused with
+//! the [`migrate`] module, which upgrades the schema document version.
+//!
+//! [`upgrade_data`]: ../../migrator/fn.upgrade_data.html
+//! [`migrate`]: ../migrate/index.html
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{de, ser};
+use serde_derive::{Deserialize, Serialize};
+
+use crate::error::Error;
+
+/// Data path component
+#[derive(Debug, Clone, PartialEq)]
+pub enum PathComponent {
+    /// Object property name
+    Name(String),
+    /// Array item index
+    Index(usize),
+    /// All array items (`[*]`)
+    Wildcard,
+}
+
+/// Data path (`networks[*].ssid`)
+#[derive(Debug, Clone, PartialEq)]
+pub struct DataPath {
+    components: Vec<PathComponent>,
+}
+
+impl DataPath {
+    pub fn components(&self) -> &[PathComponent] {
+        &self.components
+    }
+
+    pub fn has_wildcard(&self) -> bool {
+        self.components.contains(&PathComponent::Wildcard)
+    }
+}
+
+impl FromStr for DataPath {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<DataPath, Error> {
+        let invalid = || Error::message(format!("invalid data path: '{}'", s));
+        let mut components = vec![];
+
+        for segment in s.split('.') {
+            let (name, mut indexes) = match segment.find('[') {
+                Some(index) => (&segment[..index], &segment[index..]),
+                None => (segment, ""),
+            };
+
+            if name.is_empty() || name.contains(']') {
+                return Err(invalid());
+            }
+            components.push(PathComponent::Name(name.to_string()));
+
+            while !indexes.is_empty() {
+                let end = indexes.find(']').ok_or_else(invalid)?;
+                if !indexes.starts_with('[') {
+                    return Err(invalid());
+                }
+                components.push(match &indexes[1..end] {
+                    "*" => PathComponent::Wildcard,
+                    index => PathComponent::Index(index.parse().map_err(|_| invalid())?),
+                });
+                indexes = &indexes[end + 1..];
+            }
+        }
+
+        Ok(DataPath { components })
+    }
+}
+
+impl fmt::Display for DataPath {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (index, component) in self.components.iter().enumerate() {
+            match component {
+                PathComponent::Name(name) if index == 0 => write!(f, "{}", name)?,
+                PathComponent::Name(name) => write!(f, ".{}", name)?,
+                PathComponent::Index(index) => write!(f, "[{}]", index)?,
+                PathComponent::Wildcard => write!(f, "[*]")?,
+            };
+        }
+        Ok(())
+    }
+}
+
+impl<'de> de::Deserialize<'de> for DataPath {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        super::deserialize_parsed_str(deserializer, |s| s.parse().map_err(|e: Error| e.msg().to_string()))
+    }
+}
+
+impl ser::Serialize for DataPath {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: ser::Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+/// Single migration step
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase", deny_unknown_fields)]
+pub enum Step {
+    /// Renames the object property
+    Rename {
+        #[serde(deserialize_with = "deserialize_property_path")]
+        path: DataPath,
+        name: String,
+    },
+    /// Moves the value to another path, missing objects are created
+    Move {
+        #[serde(deserialize_with = "deserialize_concrete_path")]
+        from: DataPath,
+        #[serde(deserialize_with = "deserialize_concrete_path")]
+        to: DataPath,
+    },
+    /// Replaces the value with the formula result, `this` is the original value
+    Transform {
+        path: DataPath,
+        #[serde(deserialize_with = "deserialize_formula")]
+        formula: String,
+    },
+    /// Removes the value
+    Drop(#[serde(deserialize_with = "deserialize_concrete_last_component")] DataPath),
+}
+
+/// Migration of data documents from the previous revision
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct DataMigration {
+    revision: u32,
+    #[serde(default)]
+    steps: Vec<Step>,
+}
+
+impl DataMigration {
+    /// Revision of the data after this migration
+    pub fn revision(&self) -> u32 {
+        self.revision
+    }
+
+    pub fn steps(&self) -> &[Step] {
+        &self.steps
+    }
+}
+
+fn deserialize_formula<'de, D>(deserializer: D) -> Result<String, D::Error>
+where
+    D: de::Deserializer<'de>,
+{
+    super::deserialize_parsed_str(deserializer, |formula| {
+        crate::formula::parse(formula)
+            .map(|_| formula.to_string())
+            .map_err(|e| format!("invalid formula '{}': {}", formula, e))
+    })
+}
+
+fn deserialize_checked_path<'de, D, F>(deserializer: D, check: F, message: &'static str) -> Result<DataPath, D::Error>
+where
+    D: de::Deserializer<'de>,
+    F: FnOnce(&DataPath) -> bool,
+{
+    super::deserialize_parsed_str(deserializer, |s| {
+        let path: DataPath = s.parse().map_err(|e: Error| e.msg().to_string())?;
+        if check(&path) {
+            Ok(path)
+        } else {
+            Err(format!("invalid data path '{}': {}", s, message))
+        }
+    })
+}
+
+fn deserialize_property_path<'de, D>(deserializer: D) -> Result<DataPath, D::Error>
+where
+    D: de::Deserializer<'de>,
+{
+    deserialize_checked_path(
+        deserializer,
+        |path| matches!(path.components.last(), Some(PathComponent::Name(_))),
+        "must end with a property name",
+    )
+}
+
+fn deserialize_concrete_path<'de, D>(deserializer: D) -> Result<DataPath, D::Error>
+where
+    D: de::Deserializer<'de>,
+{
+    deserialize_checked_path(deserializer, |path| !path.has_wildcard(), "wildcards are not allowed")
+}
+
+fn deserialize_concrete_last_component<'de, D>(deserializer: D) -> Result<DataPath, D::Error>
+where
+    D: de::Deserializer<'de>,
+{
+    deserialize_checked_path(
+        deserializer,
+        |path| path.components.last() != Some(&PathComponent::Wildcard),
+        "must not end with a wildcard",
+    )
+}
+
+/// Ordered data migrations
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Migrations(Vec<DataMigration>);
+
+impl Migrations {
+    pub fn migrations(&self) -> &[DataMigration] {
+        &self.0
+    }
+
+    /// Current revision of data documents
+    pub fn revision(&self) -> u32 {
+        self.0.last().map(DataMigration::revision).unwrap_or(1)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+struct MigrationsVisitor;
+
+impl<'de> de::Visitor<'de> for MigrationsVisitor {
+    type Value = Migrations;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("list of migrations")
+    }
+
+    fn visit_seq<A>(self, mut access: A) -> Result<Self::Value, A::Error>
+    where
+        A: de::SeqAccess<'de>,
+    {
+        let mut migrations: Vec<DataMigration> = vec![];
+
+        while let Some(migration) = access.next_element::<DataMigration>()? {
+            let expected = migrations.last().map(|x| x.revision + 1).unwrap_or(2);
+            if migration.revision != expected {
+                return Err(de::Error::custom(format!(
+                    "unexpected migration revision {}, expected {}",
+                    migration.revision, expected
+                )));
+            }
+            migrations.push(migration);
+        }
+
+        Ok(Migrations(migrations))
+    }
+}
+
+impl<'de> de::Deserialize<'de> for Migrations {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        deserializer.deserialize_seq(MigrationsVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_paths() {
+        let path: DataPath = "networks[*].tags[1].name".parse().unwrap();
+        assert_eq!(
+            path.components(),
+            &[
+                PathComponent::Name("networks".to_string()),
+                PathComponent::Wildcard,
+                PathComponent::Name("tags".to_string()),
+                PathComponent::Index(1),
+                PathComponent::Name("name".to_string()),
+            ]
+        );
+        assert_eq!(path.to_string(), "networks[*].tags[1].name");
+
+        assert!("".parse::<DataPath>().is_err());
+        assert!("a..b".parse::<DataPath>().is_err());
+        assert!("a[x]".parse::<DataPath>().is_err());
+        assert!("a[1".parse::<DataPath>().is_err());
+        assert!("[1]".parse::<DataPath>().is_err());
+    }
+
+    #[test]
+    fn parse_steps() {
+        let steps: Vec<Step> = serde_yaml::from_str(
+            r#"
+            - rename: { path: a.b, name: c }
+            - move: { from: a.c, to: d }
+            - transform: { path: "items[*]", formula: this | lower }
+            - drop: "items[0]"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(steps.len(), 4);
+        assert_eq!(
+            steps[3],
+            Step::Drop(DataPath {
+                components: vec![PathComponent::Name("items".to_string()), PathComponent::Index(0)]
+            })
+        );
+    }
+
+    #[test]
+    fn reject_invalid_steps() {
+        let error = |s: &str| serde_yaml::from_str::<Step>(s).unwrap_err().to_string();
+
+        assert!(error("rename: { path: 'a[0]', name: b }").contains("must end with a property name"));
+        assert!(error("move: { from: 'a[*].b', to: c }").contains("wildcards are not allowed"));
+        assert!(error("drop: 'a[*]'").contains("must not end with a wildcard"));
+        assert!(error("transform: { path: a, formula: '1 +' }").contains("invalid formula"));
+        assert!(error("copy: a").contains("unknown variant"));
+    }
+
+    #[test]
+    fn revisions_must_be_consecutive() {
+        let migrations: Migrations = serde_yaml::from_str("[{revision: 2}, {revision: 3}]").unwrap();
+        assert_eq!(migrations.revision(), 3);
+        assert_eq!(Migrations::default().revision(), 1);
+
+        let error = serde_yaml::from_str::<Migrations>("[{revision: 2}, {revision: 4}]").unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("unexpected migration revision 4, expected 3"));
+    }
+}
@@ -1,5 +1,12 @@
-use serde::de;
-use serde_json::Value;
+use std::fmt;
+
+use serde::{
+    de,
+    ser::{self, SerializeMap},
+};
+use serde_json::{Number, Value};
+
+use crate::error::Error;
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct EnumEntry {
@@ -8,6 +15,30 @@ pub struct EnumEntry {
 }
 
 impl EnumEntry {
+    /// Creates an entry without a title, title is required for null & sequence values
+    pub fn new<V>(value: V) -> Result<EnumEntry, Error>
+    where
+        V: Into<Value>,
+    {
+        match value.into() {
+            Value::Null | Value::Array(_) | Value::Object(_) => {
+                Err(Error::message("title is required for null or sequence value"))
+            }
+            value => Ok(EnumEntry { title: None, value }),
+        }
+    }
+
+    pub fn with_title<S, V>(title: S, value: V) -> EnumEntry
+    where
+        S: Into<String>,
+        V: Into<Value>,
+    {
+        EnumEntry {
+            title: Some(title.into()),
+            value: value.into(),
+        }
+    }
+
     pub fn title(&self) -> String {
         match &self.title {
             Some(v) => v.clone(),
@@ -26,34 +57,113 @@ impl EnumEntry {
     }
 }
 
+struct EnumEntryVisitor;
+
+impl EnumEntryVisitor {
+    fn entry(value: Value) -> EnumEntry {
+        EnumEntry { title: None, value }
+    }
+}
+
+impl<'de> de::Visitor<'de> for EnumEntryVisitor {
+    type Value = EnumEntry;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("enum entry")
+    }
+
+    fn visit_bool<E>(self, v: bool) -> Result<Self::Value, E> {
+        Ok(EnumEntryVisitor::entry(Value::Bool(v)))
+    }
+
+    fn visit_i64<E>(self, v: i64) -> Result<Self::Value, E> {
+        Ok(EnumEntryVisitor::entry(Value::from(v)))
+    }
+
+    fn visit_u64<E>(self, v: u64) -> Result<Self::Value, E> {
+        Ok(EnumEntryVisitor::entry(Value::from(v)))
+    }
+
+    fn visit_f64<E>(self, v: f64) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        Number::from_f64(v)
+            .map(|x| EnumEntryVisitor::entry(Value::Number(x)))
+            .ok_or_else(|| de::Error::custom("invalid number"))
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<Self::Value, E> {
+        Ok(EnumEntryVisitor::entry(Value::String(v.to_string())))
+    }
+
+    fn visit_unit<E>(self) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        Err(de::Error::custom("title is required for null or sequence value"))
+    }
+
+    fn visit_seq<A>(self, _seq: A) -> Result<Self::Value, A::Error>
+    where
+        A: de::SeqAccess<'de>,
+    {
+        Err(de::Error::custom("title is required for null or sequence value"))
+    }
+
+    fn visit_map<M>(self, mut access: M) -> Result<Self::Value, M::Error>
+    where
+        M: de::MapAccess<'de>,
+    {
+        let mut title = None;
+        let mut value = None;
+
+        while let Some(key) = access.next_key::<String>()? {
+            match key.as_str() {
+                "title" => match access.next_value()? {
+                    Value::String(s) => title = Some(s),
+                    _ => return Err(de::Error::custom("title is not a string")),
+                },
+                "value" => value = Some(access.next_value()?),
+                _ => {
+                    access.next_value::<de::IgnoredAny>()?;
+                }
+            };
+        }
+
+        let title = title.ok_or_else(|| de::Error::custom("missing title keyword"))?;
+        let value = value.ok_or_else(|| de::Error::custom("missing value keyword"))?;
+
+        Ok(EnumEntry {
+            title: Some(title),
+            value,
+        })
+    }
+}
+
 impl<'de> de::Deserialize<'de> for EnumEntry {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
         D: de::Deserializer<'de>,
     {
-        let v = Value::deserialize(deserializer)?;
-        match v {
-            Value::Bool(_) | Value::Number(_) | Value::String(_) => Ok(EnumEntry { title: None, value: v }),
-            Value::Null | Value::Array(_) => Err(de::Error::custom("title is required for null or sequence value")),
-            Value::Object(mut m) => {
-                let title = m
-                    .remove("title")
-                    .ok_or_else(|| de::Error::custom("missing title keyword"))?;
-
-                let title = match title {
-                    Value::String(s) => s,
-                    _ => return Err(de::Error::custom("title is not a string")),
-                };
-
-                let value = m
-                    .remove("value")
-                    .ok_or_else(|| de::Error::custom("missing value keyword"))?;
+        deserializer.deserialize_any(EnumEntryVisitor)
+    }
+}
 
-                Ok(EnumEntry {
-                    title: Some(title),
-                    value,
-                })
+// Entries without a title are written as plain values
+impl ser::Serialize for EnumEntry {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: ser::Serializer,
+    {
+        match &self.title {
+            Some(title) => {
+                let mut map = serializer.serialize_map(Some(2))?;
+                map.serialize_entry("title", title)?;
+                map.serialize_entry("value", &self.value)?;
+                map.end()
             }
+            None => self.value.serialize(serializer),
         }
     }
 }
@@ -106,4 +216,19 @@ mod tests {
         let x: Result<EnumEntry, _> = serde_yaml::from_str("[1, 2, 3]");
         assert!(x.is_err());
     }
+
+    #[test]
+    fn serialize_value_without_title() {
+        let e: EnumEntry = serde_yaml::from_str("1.5").unwrap();
+        assert_eq!(serde_json::to_value(&e).unwrap(), serde_json::json!(1.5));
+    }
+
+    #[test]
+    fn serialize_custom_title() {
+        let e: EnumEntry = serde_yaml::from_str("title: Foo\nvalue: [1, 2]").unwrap();
+        assert_eq!(
+            serde_json::to_value(&e).unwrap(),
+            serde_json::json!({"title": "Foo", "value": [1, 2]})
+        );
+    }
 }
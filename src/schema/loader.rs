@@ -0,0 +1,944 @@
+//! Multi-file schema loader
+//!
+//! Schemas can be split into multiple files and composed together with:
+//!
+//! * `include` - a file name (or a list of file names) whose top level keys are merged into
+//!   the current mapping, local keys win, lists (`properties` for example) are concatenated
+//!   with the included items first, mappings (`definitions` for example) are merged
+//! * `$ref: other.yaml#/path` - replaced with the value at the `/path` of the `other.yaml` file,
+//!   `other.yaml#/definitions/<name>` references with other keywords next to them (`type: object?`,
+//!   `title`, ...) are kept and their definitions are moved to the root schema `definitions`
+//!
+//! File names are relative to the including file. Local references (`#/definitions/<name>`) of
+//! the root & included files are not touched, they're resolved against the root schema `definitions`
+//! after the schema is loaded. Local references of the referenced files are resolved against the
+//! referenced file, its definitions are moved to the root schema `definitions` (`network.wifi.yaml:ssid`
+//! for example).
+//!
+//! Errors are reported with the file, line, column & path of the failing value.
+//!
+//! ```yaml
+//! # device.yaml
+//! version: 1
+//! include: common.yaml
+//! properties:
+//!   - wifi:
+//!       $ref: network/wifi.yaml#/definitions/credentials
+//! ```
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+
+use serde_yaml::Value;
+
+use crate::{
+    error::Error,
+    schema::{version, ParseOptions, Schema},
+};
+
+/// Provides the content of the schema files
+pub trait Resolver {
+    /// Returns the content of the file
+    ///
+    /// File name is already normalized (no `.` or `..` components) and relative to the
+    /// root of the resolver.
+    fn read(&self, name: &str) -> Result<String, Error>;
+}
+
+/// Resolver reading files from a directory
+#[derive(Debug, Clone)]
+pub struct FileResolver {
+    directory: PathBuf,
+}
+
+impl FileResolver {
+    pub fn new<P: Into<PathBuf>>(directory: P) -> FileResolver {
+        FileResolver {
+            directory: directory.into(),
+        }
+    }
+}
+
+impl Resolver for FileResolver {
+    fn read(&self, name: &str) -> Result<String, Error> {
+        fs::read_to_string(self.directory.join(name))
+            .map_err(|e| Error::message(format!("unable to read '{}': {}", name, e)))
+    }
+}
+
+/// In-memory resolver
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct MemoryResolver {
+    files: HashMap<String, String>,
+}
+
+impl MemoryResolver {
+    pub fn new() -> MemoryResolver {
+        MemoryResolver::default()
+    }
+
+    pub fn insert<N: Into<String>, C: Into<String>>(&mut self, name: N, content: C) {
+        self.files.insert(name.into(), content.into());
+    }
+}
+
+impl From<HashMap<String, String>> for MemoryResolver {
+    fn from(files: HashMap<String, String>) -> MemoryResolver {
+        MemoryResolver { files }
+    }
+}
+
+impl Resolver for MemoryResolver {
+    fn read(&self, name: &str) -> Result<String, Error> {
+        self.files
+            .get(name)
+            .cloned()
+            .ok_or_else(|| Error::message(format!("unable to read '{}': file not found", name)))
+    }
+}
+
+// Joins the file name with the directory of the including file and removes `.` & `..`
+fn join(current: &str, name: &str) -> Result<String, Error> {
+    let directory = match current.rfind('/') {
+        Some(index) if !name.starts_with('/') => &current[..index],
+        _ => "",
+    };
+
+    let mut components: Vec<&str> = vec![];
+
+    for component in directory.split('/').chain(name.split('/')) {
+        match component {
+            "" | "." => {}
+            ".." => {
+                if components.pop().is_none() {
+                    return Err(Error::message(format!(
+                        "{}: '{}' is outside of the schema root",
+                        current, name
+                    )));
+                }
+            }
+            component => components.push(component),
+        };
+    }
+
+    Ok(components.join("/"))
+}
+
+// Path of the mapping value in the format of the error paths (`properties[0].foo`)
+fn key_path(path: &str, key: &Value) -> String {
+    let key = match key {
+        Value::String(key) => key.clone(),
+        Value::Number(key) => key.to_string(),
+        Value::Bool(key) => key.to_string(),
+        _ => "?".to_string(),
+    };
+
+    if path.is_empty() {
+        key
+    } else {
+        format!("{}.{}", path, key)
+    }
+}
+
+// Path of the sequence item in the format of the error paths
+fn index_path(path: &str, index: usize) -> String {
+    format!("{}[{}]", path, index)
+}
+
+// Looks up the value with the JSON pointer like path (`/definitions/foo`), returns the value
+// and its path in the format of the error paths
+fn lookup(document: &Value, pointer: &str) -> Option<(Value, String)> {
+    pointer
+        .split('/')
+        .filter(|x| !x.is_empty())
+        .map(|x| x.replace("~1", "/").replace("~0", "~"))
+        .try_fold((document, String::new()), |(value, path), component| match value {
+            Value::Mapping(mapping) => {
+                let key = Value::String(component);
+                let path = key_path(&path, &key);
+                mapping.get(&key).map(|x| (x, path))
+            }
+            Value::Sequence(sequence) => {
+                let index = component.parse::<usize>().ok()?;
+                sequence.get(index).map(|x| (x, index_path(&path, index)))
+            }
+            _ => None,
+        })
+        .map(|(value, path)| (value.clone(), path))
+}
+
+// `include` value is a file name or a list of file names, anything else is a property
+// named `include` for example
+fn include_names(value: &Value) -> Option<Vec<String>> {
+    match value {
+        Value::String(name) => Some(vec![name.clone()]),
+        Value::Sequence(names) => names.iter().map(|x| x.as_str().map(str::to_string)).collect(),
+        _ => None,
+    }
+}
+
+// Name of the definition referenced with the `#/definitions/<name>` pointer
+fn definition_name(pointer: &str) -> Option<&str> {
+    match pointer.strip_prefix("/definitions/") {
+        Some(name) if !name.is_empty() => Some(name),
+        _ => None,
+    }
+}
+
+// Origin of the expanded value, the file & the path of the value in the file
+#[derive(Debug, Clone)]
+struct Source {
+    file: String,
+    path: String,
+}
+
+// Expanded value with the origins of all nested values
+enum Node {
+    Scalar(Value, Source),
+    Sequence(Vec<Node>, Source),
+    Mapping(Vec<(Value, Node)>, Source),
+}
+
+impl Node {
+    fn source(&self) -> &Source {
+        match self {
+            Node::Scalar(_, source) | Node::Sequence(_, source) | Node::Mapping(_, source) => source,
+        }
+    }
+
+    fn into_value(self) -> Value {
+        match self {
+            Node::Scalar(value, _) => value,
+            Node::Sequence(items, _) => Value::Sequence(items.into_iter().map(Node::into_value).collect()),
+            Node::Mapping(entries, _) => Value::Mapping(
+                entries
+                    .into_iter()
+                    .map(|(key, value)| (key, value.into_value()))
+                    .collect(),
+            ),
+        }
+    }
+
+    // Collects the origins of all values, keys are the paths in the expanded document
+    fn collect_sources(&self, path: String, sources: &mut HashMap<String, Source>) {
+        match self {
+            Node::Scalar(..) => {}
+            Node::Sequence(items, _) => {
+                for (index, item) in items.iter().enumerate() {
+                    item.collect_sources(index_path(&path, index), sources);
+                }
+            }
+            Node::Mapping(entries, _) => {
+                for (key, value) in entries {
+                    value.collect_sources(key_path(&path, key), sources);
+                }
+            }
+        };
+        sources.insert(path, self.source().clone());
+    }
+}
+
+// Merges the `overlay` into the `base`, overlay values win, lists are concatenated
+fn merge(base: &mut Vec<(Value, Node)>, overlay: Vec<(Value, Node)>) {
+    for (key, value) in overlay {
+        let existing = base.iter().position(|(x, _)| *x == key).map(|x| base.remove(x).1);
+
+        let merged = match (existing, value) {
+            (Some(Node::Sequence(mut items, _)), Node::Sequence(overlay_items, source)) => {
+                items.extend(overlay_items);
+                Node::Sequence(items, source)
+            }
+            (Some(Node::Mapping(mut entries, _)), Node::Mapping(overlay_entries, source)) => {
+                for (key, value) in overlay_entries {
+                    match entries.iter_mut().find(|(x, _)| *x == key) {
+                        Some(entry) => entry.1 = value,
+                        None => entries.push((key, value)),
+                    };
+                }
+                Node::Mapping(entries, source)
+            }
+            (_, value) => value,
+        };
+        base.push((key, merged));
+    }
+}
+
+struct Loader<'a, R> {
+    resolver: &'a R,
+    // File contents & parsed documents
+    contents: HashMap<String, String>,
+    documents: HashMap<String, Value>,
+    // Files & references being expanded, used to detect cycles
+    expanding: Vec<String>,
+    // Definitions of the referenced files moved to the root schema definitions
+    hoisted: Vec<(String, Node)>,
+}
+
+impl<'a, R> Loader<'a, R>
+where
+    R: Resolver,
+{
+    fn document(&mut self, name: &str) -> Result<Value, Error> {
+        if !self.documents.contains_key(name) {
+            let content = self.resolver.read(name)?;
+            let document = serde_yaml::from_str(&content).map_err(|e| Error::from_yaml(e, &content).in_file(name))?;
+            self.contents.insert(name.to_string(), content);
+            self.documents.insert(name.to_string(), document);
+        }
+        Ok(self.documents[name].clone())
+    }
+
+    // Expands the value of the `name` file located at the `pointer`
+    //
+    // Local references (`#/...`) of the file are resolved against the `document`,
+    // `None` stands for the root schema.
+    fn expand_file(&mut self, name: &str, pointer: &str, document: Option<&str>) -> Result<Node, Error> {
+        let key = if pointer.is_empty() {
+            name.to_string()
+        } else {
+            format!("{}#{}", name, pointer)
+        };
+
+        if self.expanding.contains(&key) {
+            let mut cycle = self.expanding.clone();
+            cycle.push(key);
+            return Err(Error::message(format!("include cycle: {}", cycle.join(" -> "))));
+        }
+
+        let (value, path) = lookup(&self.document(name)?, pointer)
+            .ok_or_else(|| Error::message(format!("{}: unable to resolve '#{}'", name, pointer)))?;
+
+        self.expanding.push(key);
+        let result = self.expand(name, document, value, path);
+        self.expanding.pop();
+
+        result
+    }
+
+    // Moves the `name` definition of the referenced `document` to the root schema definitions,
+    // returns the root definition name
+    fn hoist(&mut self, document: &str, name: &str) -> Result<String, Error> {
+        let hoisted_name = format!("{}:{}", document.replace('/', "."), name);
+
+        if !self.hoisted.iter().any(|(x, _)| *x == hoisted_name) {
+            // Placeholder, recursive references to the definition are not expanded again
+            let placeholder = Source {
+                file: document.to_string(),
+                path: String::new(),
+            };
+            self.hoisted
+                .push((hoisted_name.clone(), Node::Scalar(Value::Null, placeholder)));
+
+            // The definition can be expanded inline at the same time (`$ref: other.yaml#/definitions/foo`
+            // with a recursive reference), cycles of the hoisted definition are detected separately
+            let expanding = std::mem::take(&mut self.expanding);
+            let definition = self.expand_file(document, &format!("/definitions/{}", name), Some(document));
+            self.expanding = expanding;
+            let definition = definition?;
+
+            if let Some(entry) = self.hoisted.iter_mut().find(|(x, _)| *x == hoisted_name) {
+                entry.1 = definition;
+            }
+        }
+
+        Ok(hoisted_name)
+    }
+
+    fn expand(&mut self, file: &str, document: Option<&str>, value: Value, path: String) -> Result<Node, Error> {
+        let source = Source {
+            file: file.to_string(),
+            path: path.clone(),
+        };
+
+        let mut mapping = match value {
+            Value::Mapping(mapping) => mapping,
+            Value::Sequence(sequence) => {
+                return sequence
+                    .into_iter()
+                    .enumerate()
+                    .map(|(index, x)| self.expand(file, document, x, index_path(&path, index)))
+                    .collect::<Result<_, _>>()
+                    .map(|x| Node::Sequence(x, source));
+            }
+            value => return Ok(Node::Scalar(value, source)),
+        };
+
+        let ref_keyword = Value::String("$ref".to_string());
+
+        if let Some(Value::String(reference)) = mapping.get(&ref_keyword).cloned() {
+            let (name, pointer) = match reference.find('#') {
+                Some(index) => (&reference[..index], &reference[index + 1..]),
+                None => (reference.as_str(), ""),
+            };
+
+            // Keywords next to the `$ref` are merged over the definition when the schema is parsed,
+            // the referenced definition is moved to the root schema definitions to keep them
+            let has_siblings = mapping.len() > 1;
+
+            // Other file, its local references are resolved against it
+            if !name.is_empty() {
+                let name = join(file, name)?;
+
+                match definition_name(pointer) {
+                    Some(definition) if has_siblings => {
+                        let hoisted_name = self.hoist(&name, definition)?;
+                        mapping.insert(ref_keyword, Value::String(format!("#/definitions/{}", hoisted_name)));
+                    }
+                    _ if has_siblings => return Err(self.ref_siblings_error(file, &path, &reference)),
+                    _ => return self.expand_file(&name, pointer, Some(&name)),
+                };
+            }
+            // Local reference of the referenced file
+            else if let Some(document) = document {
+                match definition_name(pointer) {
+                    Some(name) => {
+                        let hoisted_name = self.hoist(document, name)?;
+                        mapping.insert(ref_keyword, Value::String(format!("#/definitions/{}", hoisted_name)));
+                    }
+                    None if has_siblings => return Err(self.ref_siblings_error(file, &path, &reference)),
+                    None => return self.expand_file(document, pointer, Some(document)),
+                };
+            }
+        }
+
+        let include = Value::String("include".to_string());
+        let names = mapping.get(&include).and_then(include_names);
+
+        if names.is_some() {
+            mapping.remove(&include);
+        }
+
+        let mut entries = vec![];
+        for (key, value) in mapping {
+            let value_path = key_path(&path, &key);
+            entries.push((key, self.expand(file, document, value, value_path)?));
+        }
+
+        if let Some(names) = names {
+            let mut merged = vec![];
+
+            for name in names {
+                let name = join(file, &name)?;
+                match self.expand_file(&name, "", document)? {
+                    Node::Mapping(included, _) => merge(&mut merged, included),
+                    _ => return Err(Error::message(format!("{}: included file must be a mapping", name))),
+                };
+            }
+
+            merge(&mut merged, entries);
+            entries = merged;
+        }
+
+        Ok(Node::Mapping(entries, source))
+    }
+
+    // Only definitions can be merged with the keywords next to the `$ref`
+    fn ref_siblings_error(&self, file: &str, path: &str, reference: &str) -> Error {
+        let content = self.contents.get(file).map(String::as_str).unwrap_or_default();
+        Error::at_path(
+            format!(
+                "keywords next to the $ref '{}' are allowed for '#/definitions/<name>' references only",
+                reference
+            ),
+            content,
+            &key_path(path, &Value::String("$ref".to_string())),
+        )
+        .in_file(file)
+    }
+
+    // Adds the hoisted definitions to the root schema definitions
+    fn add_hoisted_definitions(&mut self, root: &mut Node) -> Result<(), Error> {
+        let (entries, source) = match root {
+            Node::Mapping(entries, source) => (entries, source),
+            _ => return Ok(()),
+        };
+
+        let keyword = Value::String("definitions".to_string());
+        if !entries.iter().any(|(x, _)| *x == keyword) {
+            let definitions = Node::Mapping(
+                vec![],
+                Source {
+                    file: source.file.clone(),
+                    path: key_path(&source.path, &keyword),
+                },
+            );
+            entries.push((keyword.clone(), definitions));
+        }
+
+        let definitions = match entries.iter_mut().find(|(x, _)| *x == keyword) {
+            Some((_, Node::Mapping(definitions, _))) => definitions,
+            _ => {
+                return Err(Error::message(format!(
+                    "{}: definitions must be a mapping",
+                    source.file
+                )))
+            }
+        };
+
+        for (name, definition) in self.hoisted.drain(..) {
+            let name = Value::String(name);
+            if definitions.iter().any(|(x, _)| *x == name) {
+                return Err(Error::message(format!(
+                    "{}: definition {:?} clashes with a referenced file definition",
+                    source.file,
+                    name.as_str().unwrap_or_default()
+                )));
+            }
+            definitions.push((name, definition));
+        }
+
+        Ok(())
+    }
+
+    // Locates the error of the expanded document in the file the failing value comes from
+    fn locate(&self, error: Error, root: &str, sources: &HashMap<String, Source>) -> Error {
+        let mut path = match error.path() {
+            Some(path) if !path.is_empty() => path.to_string(),
+            _ => return Error::message(error.msg()).in_file(root),
+        };
+        let mut suffix = String::new();
+
+        // Every value has its origin, ancestors are searched just in case
+        loop {
+            if let Some(source) = sources.get(&path) {
+                let file_path = format!("{}{}", source.path, suffix);
+                let file_path = file_path.trim_start_matches('.');
+                let content = self.contents.get(&source.file).map(String::as_str).unwrap_or_default();
+                return Error::at_path(error.msg(), content, file_path).in_file(source.file.as_str());
+            }
+
+            match path.rfind(['.', '[']) {
+                Some(index) => {
+                    suffix = format!("{}{}", &path[index..], suffix);
+                    path.truncate(index);
+                }
+                None => return Error::message(error.msg()).in_file(root),
+            };
+        }
+    }
+}
+
+/// Loads the schema composed from multiple files
+///
+/// # Arguments
+///
+/// * `resolver` - Provides the content of the schema files
+/// * `name` - Root schema file name
+pub fn load_schema<R>(resolver: &R, name: &str) -> Result<Schema, Error>
+where
+    R: Resolver,
+{
+    load_schema_with_options(resolver, name, &ParseOptions::default())
+}
+
+/// Loads the schema composed from multiple files with the parsing options
+///
+/// # Arguments
+///
+/// * `resolver` - Provides the content of the schema files
+/// * `name` - Root schema file name
+/// * `options` - Schema parsing options
+pub fn load_schema_with_options<R>(resolver: &R, name: &str, options: &ParseOptions) -> Result<Schema, Error>
+where
+    R: Resolver,
+{
+    let name = join("", name)?;
+
+    let mut loader = Loader {
+        resolver,
+        contents: HashMap::new(),
+        documents: HashMap::new(),
+        expanding: vec![],
+        hoisted: vec![],
+    };
+
+    let mut root = loader.expand_file(&name, "", None)?;
+    loader.add_hoisted_definitions(&mut root)?;
+
+    let mut sources = HashMap::new();
+    root.collect_sources(String::new(), &mut sources);
+
+    // The expanded document is parsed from the text to locate errors, locations are
+    // translated to the files the failing values come from
+    let value = root.into_value();
+    let version = version::value_version(&value);
+    let content = serde_yaml::to_string(&value)?;
+
+    options
+        .apply(|| version::with_grammar(Some(version), || serde_yaml::from_str(&content)))
+        .map_err(|e| loader.locate(Error::from_yaml(e, &content), &name, &sources))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::{generator::generate_json_ui_schema, schema::PrimitiveType, validator::validate};
+
+    fn resolver(files: Vec<(&str, &str)>) -> MemoryResolver {
+        let mut resolver = MemoryResolver::new();
+        for (name, content) in files {
+            resolver.insert(name, content);
+        }
+        resolver
+    }
+
+    fn property_names(schema: &Schema) -> Vec<&str> {
+        schema.properties().iter().map(|x| x.name()).collect()
+    }
+
+    #[test]
+    fn include_file() {
+        let resolver = resolver(vec![
+            (
+                "device.yaml",
+                r#"
+                version: 1
+                title: Device
+                include: common/base.yaml
+                properties:
+                  - hostname:
+                      type: hostname
+                "#,
+            ),
+            (
+                "common/base.yaml",
+                r#"
+                title: Base
+                properties:
+                  - uuid:
+                      type: string
+                "#,
+            ),
+        ]);
+
+        let schema = load_schema(&resolver, "device.yaml").unwrap();
+        assert_eq!(schema.title(), Some("Device"));
+        assert_eq!(property_names(&schema), vec!["uuid", "hostname"]);
+    }
+
+    #[test]
+    fn include_multiple_files() {
+        let resolver = resolver(vec![
+            ("device.yaml", "version: 1\ninclude: [a.yaml, b.yaml]"),
+            ("a.yaml", "properties:\n  - a:\n      type: string"),
+            ("b.yaml", "properties:\n  - b:\n      type: string"),
+        ]);
+
+        let schema = load_schema(&resolver, "device.yaml").unwrap();
+        assert_eq!(property_names(&schema), vec!["a", "b"]);
+    }
+
+    #[test]
+    fn reference_other_file() {
+        let resolver = resolver(vec![
+            (
+                "schemas/device.yaml",
+                r#"
+                version: 1
+                properties:
+                  - wifi:
+                      $ref: ../network/wifi.yaml#/definitions/credentials
+                "#,
+            ),
+            (
+                "network/wifi.yaml",
+                r#"
+                definitions:
+                  credentials:
+                    properties:
+                      - ssid:
+                          type: string
+                "#,
+            ),
+        ]);
+
+        let schema = load_schema(&resolver, "schemas/device.yaml").unwrap();
+        let wifi = schema.properties()[0].schema();
+        assert_eq!(wifi.r#ref(), None);
+        assert_eq!(property_names(wifi), vec!["ssid"]);
+    }
+
+    #[test]
+    fn merge_keywords_next_to_reference_other_file() {
+        let resolver = resolver(vec![
+            (
+                "device.yaml",
+                r#"
+                version: 1
+                properties:
+                  - wifi:
+                      type: object?
+                      title: Wifi
+                      $ref: network/wifi.yaml#/definitions/credentials
+                "#,
+            ),
+            (
+                "network/wifi.yaml",
+                r#"
+                definitions:
+                  credentials:
+                    type: object
+                    properties:
+                      - ssid:
+                          type: string
+                "#,
+            ),
+        ]);
+
+        let schema = load_schema(&resolver, "device.yaml").unwrap();
+        let wifi = schema.resolve(schema.properties()[0].schema());
+        assert!(!wifi.r#type().is_required());
+        assert_eq!(wifi.title(), Some("Wifi"));
+        assert_eq!(property_names(wifi), vec!["ssid"]);
+
+        assert!(validate(&schema, &json!({})).is_valid());
+        assert!(!validate(&schema, &json!({"wifi": {}})).is_valid());
+
+        let (json_schema, _) = generate_json_ui_schema(&schema);
+        assert_eq!(json_schema.get("required"), None);
+        assert_eq!(json_schema["properties"]["wifi"]["title"], "Wifi");
+    }
+
+    #[test]
+    fn fail_on_keywords_next_to_reference_other_file_value() {
+        let resolver = resolver(vec![
+            (
+                "device.yaml",
+                "version: 1\nproperties:\n  - wifi:\n      title: Wifi\n      $ref: wifi.yaml#/properties/0/ssid\n",
+            ),
+            ("wifi.yaml", "properties:\n  - ssid:\n      type: string"),
+        ]);
+
+        let error = load_schema(&resolver, "device.yaml").unwrap_err();
+        assert_eq!(
+            error.msg(),
+            "keywords next to the $ref 'wifi.yaml#/properties/0/ssid' are allowed for '#/definitions/<name>' references only"
+        );
+        assert_eq!(error.file(), Some("device.yaml"));
+        assert_eq!(error.path(), Some("properties[0].wifi.$ref"));
+        assert_eq!(error.line(), Some(5));
+    }
+
+    #[test]
+    fn keep_local_references() {
+        let resolver = resolver(vec![(
+            "device.yaml",
+            r##"
+            version: 1
+            definitions:
+              name:
+                type: string
+            properties:
+              - name:
+                  $ref: "#/definitions/name"
+            "##,
+        )]);
+
+        let schema = load_schema(&resolver, "device.yaml").unwrap();
+        assert_eq!(schema.properties()[0].schema().r#ref(), Some("#/definitions/name"));
+    }
+
+    #[test]
+    fn property_named_include() {
+        let resolver = resolver(vec![(
+            "device.yaml",
+            "version: 1\nproperties:\n  - include:\n      type: string",
+        )]);
+
+        let schema = load_schema(&resolver, "device.yaml").unwrap();
+        assert_eq!(property_names(&schema), vec!["include"]);
+    }
+
+    #[test]
+    fn fail_on_include_cycle() {
+        let resolver = resolver(vec![
+            ("a.yaml", "version: 1\ninclude: b.yaml"),
+            ("b.yaml", "include: a.yaml"),
+        ]);
+
+        let error = load_schema(&resolver, "a.yaml").unwrap_err();
+        assert_eq!(error.to_string(), "include cycle: a.yaml -> b.yaml -> a.yaml");
+    }
+
+    #[test]
+    fn fail_on_reference_cycle() {
+        let resolver = resolver(vec![
+            ("a.yaml", "version: 1\nproperties:\n  - foo:\n      $ref: b.yaml#/foo"),
+            ("b.yaml", "foo:\n  $ref: a.yaml#/properties/0/foo"),
+        ]);
+
+        assert!(load_schema(&resolver, "a.yaml")
+            .unwrap_err()
+            .to_string()
+            .starts_with("include cycle:"));
+    }
+
+    #[test]
+    fn error_contains_file_name() {
+        let resolver = resolver(vec![
+            ("a.yaml", "version: 1\ninclude: b.yaml"),
+            ("b.yaml", "properties: ["),
+        ]);
+        assert!(load_schema(&resolver, "a.yaml")
+            .unwrap_err()
+            .to_string()
+            .starts_with("b.yaml:"));
+    }
+
+    #[test]
+    fn fail_on_missing_file() {
+        let resolver = resolver(vec![("a.yaml", "version: 1\ninclude: b.yaml")]);
+        assert_eq!(
+            load_schema(&resolver, "a.yaml").unwrap_err().to_string(),
+            "unable to read 'b.yaml': file not found"
+        );
+    }
+
+    #[test]
+    fn fail_on_unresolved_reference() {
+        let resolver = resolver(vec![
+            ("a.yaml", "version: 1\nproperties:\n  - foo:\n      $ref: b.yaml#/foo"),
+            ("b.yaml", "bar: 1"),
+        ]);
+        assert_eq!(
+            load_schema(&resolver, "a.yaml").unwrap_err().to_string(),
+            "b.yaml: unable to resolve '#/foo'"
+        );
+    }
+
+    #[test]
+    fn fail_outside_of_root() {
+        let resolver = resolver(vec![("a.yaml", "version: 1\ninclude: ../b.yaml")]);
+        assert!(load_schema(&resolver, "a.yaml").is_err());
+    }
+
+    #[test]
+    fn load_from_directory() {
+        let resolver = FileResolver::new(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/loader"));
+        let schema = load_schema(&resolver, "device.yaml").unwrap();
+        assert_eq!(property_names(&schema), vec!["uuid", "hostname", "wifi"]);
+    }
+
+    #[test]
+    fn strict_mode() {
+        let resolver = resolver(vec![
+            ("a.yaml", "version: 1\ninclude: b.yaml\nx-owner: os"),
+            (
+                "b.yaml",
+                "properties:\n  - foo:\n      type: string\n      maxlength: 3",
+            ),
+        ]);
+        let options = ParseOptions::new().extension_prefix("x-");
+
+        let schema = load_schema_with_options(&resolver, "a.yaml", &options).unwrap();
+        assert_eq!(schema.extension("x-owner").unwrap(), "os");
+
+        let error = load_schema_with_options(&resolver, "a.yaml", &options.strict(true)).unwrap_err();
+        assert_eq!(error.msg(), "unknown keyword 'maxlength', did you mean 'maxLength'?");
+        assert_eq!(error.file(), Some("b.yaml"));
+        assert_eq!(error.path(), Some("properties[0].foo"));
+        assert_eq!(error.line(), Some(3));
+        assert_eq!(error.column(), Some(11));
+        assert_eq!(
+            error.to_string(),
+            "b.yaml: properties[0].foo: unknown keyword 'maxlength', did you mean 'maxLength'? at line 3 column 11"
+        );
+    }
+
+    #[test]
+    fn locate_error_in_root_file() {
+        let resolver = resolver(vec![
+            (
+                "a.yaml",
+                "version: 1\ninclude: b.yaml\nproperties:\n  - bar:\n      type: strng",
+            ),
+            ("b.yaml", "properties:\n  - foo:\n      type: string"),
+        ]);
+
+        let error = load_schema(&resolver, "a.yaml").unwrap_err();
+        assert_eq!(error.file(), Some("a.yaml"));
+        assert_eq!(error.path(), Some("properties[0].bar.type"));
+        assert_eq!(error.line(), Some(5));
+        assert_eq!(error.column(), Some(13));
+    }
+
+    #[test]
+    fn locate_error_in_referenced_file() {
+        let resolver = resolver(vec![
+            (
+                "a.yaml",
+                "version: 1\nproperties:\n  - wifi:\n      $ref: network/wifi.yaml#/definitions/credentials",
+            ),
+            (
+                "network/wifi.yaml",
+                "definitions:\n  credentials:\n    properties:\n      - ssid:\n          type: strng",
+            ),
+        ]);
+
+        let error = load_schema(&resolver, "a.yaml").unwrap_err();
+        assert_eq!(error.msg(), "invalid primitive type: \"strng\"");
+        assert_eq!(error.file(), Some("network/wifi.yaml"));
+        assert_eq!(error.path(), Some("definitions.credentials.properties[0].ssid.type"));
+        assert_eq!(error.line(), Some(5));
+        assert_eq!(error.column(), Some(17));
+    }
+
+    #[test]
+    fn resolve_local_references_of_referenced_file() {
+        let resolver = resolver(vec![
+            (
+                "a.yaml",
+                r##"
+                version: 1
+                definitions:
+                  ssid:
+                    type: integer
+                properties:
+                  - wifi:
+                      $ref: network/wifi.yaml#/definitions/credentials
+                "##,
+            ),
+            (
+                "network/wifi.yaml",
+                r##"
+                definitions:
+                  ssid:
+                    type: string
+                  credentials:
+                    properties:
+                      - ssid:
+                          $ref: "#/definitions/ssid"
+                      - fallback:
+                          type: object?
+                          $ref: "#/definitions/credentials"
+                "##,
+            ),
+        ]);
+
+        let schema = load_schema(&resolver, "a.yaml").unwrap();
+        let wifi = schema.properties()[0].schema();
+        assert_eq!(property_names(wifi), vec!["ssid", "fallback"]);
+
+        let ssid = wifi.properties()[0].schema();
+        assert_eq!(ssid.r#ref(), Some("#/definitions/network.wifi.yaml:ssid"));
+        assert_eq!(schema.resolve(ssid).r#type().primitive_type(), &PrimitiveType::String);
+
+        let fallback = wifi.properties()[1].schema();
+        assert_eq!(fallback.r#ref(), Some("#/definitions/network.wifi.yaml:credentials"));
+        assert_eq!(property_names(schema.resolve(fallback)), vec!["ssid", "fallback"]);
+        assert_eq!(
+            schema.resolve(&schema.definitions()["ssid"]).r#type().primitive_type(),
+            &PrimitiveType::Integer
+        );
+    }
+
+    #[test]
+    fn join_names() {
+        assert_eq!(join("a/b.yaml", "c.yaml").unwrap(), "a/c.yaml");
+        assert_eq!(join("a/b.yaml", "./c/../d.yaml").unwrap(), "a/d.yaml");
+        assert_eq!(join("a/b.yaml", "/c.yaml").unwrap(), "c.yaml");
+        assert_eq!(join("b.yaml", "c.yaml").unwrap(), "c.yaml");
+    }
+}
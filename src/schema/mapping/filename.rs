@@ -1,7 +1,12 @@
-use serde::de;
+use std::fmt;
+
+use serde::{
+    de,
+    ser::{self, SerializeMap},
+};
 use serde_json::Value;
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum FileName {
     /// A real file name
     Name(String),
@@ -25,26 +30,63 @@ impl FileName {
     }
 }
 
+struct FileNameVisitor;
+
+impl<'de> de::Visitor<'de> for FileNameVisitor {
+    type Value = FileName;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("filename must be a string")
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<Self::Value, E> {
+        Ok(FileName::Name(v.to_string()))
+    }
+
+    fn visit_map<M>(self, mut access: M) -> Result<Self::Value, M::Error>
+    where
+        M: de::MapAccess<'de>,
+    {
+        let mut formula = None;
+
+        while let Some(key) = access.next_key::<String>()? {
+            if key == "formula" {
+                match access.next_value()? {
+                    Value::String(s) => formula = Some(s),
+                    _ => return Err(de::Error::custom("mapping contains formula, but it's not a string")),
+                };
+            } else {
+                access.next_value::<de::IgnoredAny>()?;
+            }
+        }
+
+        formula
+            .map(FileName::Formula)
+            .ok_or_else(|| de::Error::custom("filename must be a string"))
+    }
+}
+
 impl<'de> de::Deserialize<'de> for FileName {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
         D: de::Deserializer<'de>,
     {
-        let value = Value::deserialize(deserializer)?;
-        match value {
-            Value::Object(ref obj) => {
-                if let Some(value) = obj.get("formula") {
-                    if let Some(s) = value.as_str() {
-                        Ok(FileName::Formula(s.to_string()))
-                    } else {
-                        Err(de::Error::custom("mapping contains eval, but it's not a string"))
-                    }
-                } else {
-                    Err(de::Error::custom("filename must be a string"))
-                }
+        deserializer.deserialize_any(FileNameVisitor)
+    }
+}
+
+impl ser::Serialize for FileName {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: ser::Serializer,
+    {
+        match self {
+            FileName::Name(name) => serializer.serialize_str(name),
+            FileName::Formula(formula) => {
+                let mut map = serializer.serialize_map(Some(1))?;
+                map.serialize_entry("formula", formula)?;
+                map.end()
             }
-            Value::String(s) => Ok(FileName::Name(s)),
-            _ => Err(de::Error::custom("filename must be a string")),
         }
     }
 }
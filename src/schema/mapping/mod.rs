@@ -3,13 +3,12 @@
 //! do it without it. If not, I'll put it back. But less stuff we have, more
 //! better it is.
 //!
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
-use serde_derive::Deserialize;
+use serde::{ser, Serialize};
+use serde_derive::{Deserialize, Serialize};
 use serde_json::Value;
 
-use crate::deref::OptionDeref;
-
 pub use self::{
     filename::FileName,
     target::{LocationPartition, RawTarget, Target, TargetFormat, TargetLocation, TargetType},
@@ -19,9 +18,13 @@ mod filename;
 mod target;
 
 /// Mapping structure
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
 pub struct Mapping {
-    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    #[serde(
+        default,
+        skip_serializing_if = "HashMap::is_empty",
+        serialize_with = "serialize_sorted"
+    )]
     targets: HashMap<String, RawTarget>,
 
     #[serde(default, skip_serializing_if = "Option::is_none")]
@@ -37,6 +40,44 @@ pub struct Mapping {
     template: Option<Value>,
 }
 
+impl Mapping {
+    pub fn new() -> Mapping {
+        Mapping::default()
+    }
+
+    /// Adds a target which can be referenced by name
+    pub fn with_named_target<S>(mut self, name: S, target: RawTarget) -> Mapping
+    where
+        S: Into<String>,
+    {
+        self.targets.insert(name.into(), target);
+        self
+    }
+
+    pub fn with_target(mut self, target: Target) -> Mapping {
+        self.target = Some(target);
+        self
+    }
+
+    pub fn with_filename(mut self, filename: FileName) -> Mapping {
+        self.filename = Some(filename);
+        self
+    }
+
+    pub fn with_path<S>(mut self, path: S) -> Mapping
+    where
+        S: Into<String>,
+    {
+        self.path = Some(path.into());
+        self
+    }
+
+    pub fn with_template(mut self, template: Value) -> Mapping {
+        self.template = Some(template);
+        self
+    }
+}
+
 impl Mapping {
     pub fn targets(&self) -> &HashMap<String, RawTarget> {
         &self.targets
@@ -59,6 +100,14 @@ impl Mapping {
     }
 }
 
+// Targets are serialized in the name order
+fn serialize_sorted<S>(targets: &HashMap<String, RawTarget>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: ser::Serializer,
+{
+    targets.iter().collect::<BTreeMap<_, _>>().serialize(serializer)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
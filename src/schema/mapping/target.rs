@@ -1,13 +1,11 @@
 use std::fmt;
 
-use serde::de;
-use serde_derive::Deserialize;
+use serde::{de, ser};
+use serde_derive::{Deserialize, Serialize};
 use uuid::Uuid;
 
-use crate::deref::OptionDeref;
-
 /// Target type
-#[derive(Debug, Copy, Clone, PartialEq, Deserialize)]
+#[derive(Debug, Copy, Clone, PartialEq, Deserialize, Serialize)]
 pub enum TargetType {
     /// Single file
     #[serde(rename = "file")]
@@ -21,22 +19,16 @@ pub enum TargetType {
 
 impl TargetType {
     pub fn is_file(self) -> bool {
-        match self {
-            TargetType::File => true,
-            _ => false,
-        }
+        matches!(self, TargetType::File)
     }
 
     pub fn is_file_set(self) -> bool {
-        match self {
-            TargetType::FileSet => true,
-            _ => false,
-        }
+        matches!(self, TargetType::FileSet)
     }
 }
 
 /// Target file format
-#[derive(Debug, Copy, Clone, PartialEq, Deserialize)]
+#[derive(Debug, Copy, Clone, PartialEq, Deserialize, Serialize)]
 pub enum TargetFormat {
     #[serde(rename = "ini")]
     Ini,
@@ -52,42 +44,40 @@ pub enum TargetFormat {
 
 impl TargetFormat {
     pub fn is_ini(self) -> bool {
-        match self {
-            TargetFormat::Ini => true,
-            _ => false,
-        }
+        matches!(self, TargetFormat::Ini)
     }
 
     pub fn is_json(self) -> bool {
-        match self {
-            TargetFormat::Json => true,
-            _ => false,
-        }
+        matches!(self, TargetFormat::Json)
     }
 
     pub fn is_binary(self) -> bool {
-        match self {
-            TargetFormat::Binary => true,
-            _ => false,
-        }
+        matches!(self, TargetFormat::Binary)
     }
 
     pub fn is_text(self) -> bool {
-        match self {
-            TargetFormat::Text => true,
-            _ => false,
-        }
+        matches!(self, TargetFormat::Text)
     }
 
     pub fn is_redsocks(self) -> bool {
-        match self {
-            TargetFormat::Redsocks => true,
-            _ => false,
-        }
+        matches!(self, TargetFormat::Redsocks)
     }
 }
 
-#[derive(Debug, Clone, PartialEq)]
+impl fmt::Display for TargetFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            TargetFormat::Ini => "ini",
+            TargetFormat::Json => "json",
+            TargetFormat::Binary => "binary",
+            TargetFormat::Text => "text",
+            TargetFormat::Redsocks => "redsocks",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum LocationPartition {
     Index(u8),
     Uuid(Uuid),
@@ -117,13 +107,33 @@ impl LocationPartition {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Deserialize)]
+impl fmt::Display for LocationPartition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LocationPartition::Index(index) => write!(f, "{}", index),
+            LocationPartition::Uuid(uuid) => write!(f, "{}", uuid),
+            LocationPartition::Label(label) => write!(f, "{}", label),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
 pub struct TargetLocation {
     path: String,
     partition: LocationPartition,
 }
 
 impl TargetLocation {
+    pub fn new<S>(partition: LocationPartition, path: S) -> TargetLocation
+    where
+        S: Into<String>,
+    {
+        TargetLocation {
+            path: path.into(),
+            partition,
+        }
+    }
+
     pub fn path(&self) -> &str {
         &self.path
     }
@@ -133,15 +143,41 @@ impl TargetLocation {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Deserialize)]
+impl fmt::Display for TargetLocation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.partition, self.path)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 pub struct RawTarget {
     #[serde(rename = "type")]
     type_: TargetType,
     format: TargetFormat,
+    #[serde(skip_serializing_if = "Option::is_none")]
     glob: Option<String>,
     location: TargetLocation,
 }
 
+impl RawTarget {
+    pub fn new(type_: TargetType, format: TargetFormat, location: TargetLocation) -> RawTarget {
+        RawTarget {
+            type_,
+            format,
+            glob: None,
+            location,
+        }
+    }
+
+    pub fn with_glob<S>(mut self, glob: S) -> RawTarget
+    where
+        S: Into<String>,
+    {
+        self.glob = Some(glob.into());
+        self
+    }
+}
+
 impl RawTarget {
     pub fn type_(&self) -> &TargetType {
         &self.type_
@@ -160,8 +196,7 @@ impl RawTarget {
     }
 }
 
-#[derive(Debug, PartialEq, Deserialize)]
-#[serde(untagged)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Target {
     Reference(String),
     Raw(RawTarget),
@@ -183,6 +218,48 @@ impl Target {
     }
 }
 
+impl ser::Serialize for Target {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: ser::Serializer,
+    {
+        match self {
+            Target::Reference(name) => serializer.serialize_str(name),
+            Target::Raw(target) => target.serialize(serializer),
+        }
+    }
+}
+
+struct TargetVisitor;
+
+impl<'de> de::Visitor<'de> for TargetVisitor {
+    type Value = Target;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("target name or target")
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<Self::Value, E> {
+        Ok(Target::Reference(v.to_string()))
+    }
+
+    fn visit_map<M>(self, access: M) -> Result<Self::Value, M::Error>
+    where
+        M: de::MapAccess<'de>,
+    {
+        de::Deserialize::deserialize(de::value::MapAccessDeserializer::new(access)).map(Target::Raw)
+    }
+}
+
+impl<'de> de::Deserialize<'de> for Target {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        deserializer.deserialize_any(TargetVisitor)
+    }
+}
+
 struct PartitionVisitor;
 
 impl<'de> de::Visitor<'de> for PartitionVisitor {
@@ -196,7 +273,7 @@ impl<'de> de::Visitor<'de> for PartitionVisitor {
     where
         E: de::Error,
     {
-        if v > u64::from(std::u8::MAX) {
+        if v > u64::from(u8::MAX) {
             return Err(de::Error::custom("partition index out of bounds"));
         }
         Ok(LocationPartition::Index(v as u8))
@@ -223,6 +300,19 @@ impl<'de> de::Deserialize<'de> for LocationPartition {
     }
 }
 
+impl ser::Serialize for LocationPartition {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: ser::Serializer,
+    {
+        match self {
+            LocationPartition::Index(index) => serializer.serialize_u8(*index),
+            LocationPartition::Uuid(uuid) => serializer.collect_str(uuid),
+            LocationPartition::Label(label) => serializer.serialize_str(label),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -317,4 +407,39 @@ mod tests {
         let uuid: Uuid = Uuid::parse_str(UUID).unwrap();
         assert_eq!(p.uuid(), Some(&uuid));
     }
+
+    #[test]
+    fn serialize_target() {
+        let t: Target = serde_yaml::from_str("foo").unwrap();
+        assert_eq!(serde_json::to_value(&t).unwrap(), "foo");
+
+        let schema = r#"
+            type: fileset
+            format: ini
+            location:
+                partition: 2
+                path: /system-connections
+        "#;
+        let t: Target = serde_yaml::from_str(schema).unwrap();
+        assert_eq!(
+            serde_json::to_value(&t).unwrap(),
+            serde_json::json!({
+                "type": "fileset",
+                "format": "ini",
+                "location": {
+                    "path": "/system-connections",
+                    "partition": 2
+                }
+            })
+        );
+    }
+
+    #[test]
+    fn serialize_partition() {
+        for partition in &["0", "resin-boot", "9d7a1f5e-4b28-4c1a-9e3c-7b7d8a1b2c3d"] {
+            let p: LocationPartition = serde_yaml::from_str(partition).unwrap();
+            let serialized = serde_yaml::to_string(&p).unwrap();
+            assert_eq!(serde_yaml::from_str::<LocationPartition>(&serialized).unwrap(), p);
+        }
+    }
 }
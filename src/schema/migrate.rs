@@ -0,0 +1,395 @@
+//! Schema version migration
+//!
+//! Upgrades the version 1 schema document to the version 2. The migration is a pure
+//! document transformation:
+//!
+//! * `datetime` & `stringlist` types are renamed to `date-time` & `string-list`
+//! * unknown keywords, silently ignored in the version 1, are removed (lossy change)
+//! * `version` is set to `2`
+//!
+//! Keywords with the `x-` prefix are kept, they're vendor extensions in the version 2.
+//! The `include` keyword of the [`loader`] is kept as well, every file of the multi-file
+//! schema can be migrated separately. Comments are not preserved.
+//!
+//! # Examples
+//!
+//! ```
+//! use jellyschema::schema::{migrate::migrate, Schema};
+//!
+//! let migration = migrate(r#"
+//!   version: 1
+//!   properties:
+//!     - since:
+//!         type: datetime
+//!         maxlength: 10
+//! "#).unwrap();
+//!
+//! assert_eq!(migration.changes().len(), 3);
+//! assert_eq!(migration.lossy_changes().count(), 1);
+//! assert_eq!(migration.lossy_changes().next().unwrap().path(), "properties[0].since.maxlength");
+//!
+//! let schema: Schema = migration.document().parse().unwrap();
+//! assert_eq!(schema.version(), Some(2));
+//! ```
+//!
+//! [`loader`]: ../loader/index.html
+use std::fmt;
+
+use serde_yaml::{Mapping, Value};
+
+use crate::{
+    error::Error,
+    schema::{
+        options::{suggestion, KEYWORDS, VERSION_2_EXTENSION_PREFIX},
+        r#type::renamed_keyword,
+        version::document_version,
+    },
+};
+
+// Loader keyword, not a schema keyword, but still allowed in the schema documents
+const INCLUDE_KEYWORD: &str = "include";
+
+/// Schema document change made by the migration
+#[derive(Debug, Clone, PartialEq)]
+pub struct Change {
+    path: String,
+    message: String,
+    lossy: bool,
+}
+
+impl Change {
+    /// Path to the changed value (`properties[0].since.type`)
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// `true` if the change lost some information
+    pub fn is_lossy(&self) -> bool {
+        self.lossy
+    }
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.lossy {
+            write!(f, "{}: {} (lossy)", self.path, self.message)
+        } else {
+            write!(f, "{}: {}", self.path, self.message)
+        }
+    }
+}
+
+/// Migrated schema document
+#[derive(Debug, Clone)]
+pub struct Migration {
+    document: String,
+    changes: Vec<Change>,
+}
+
+impl Migration {
+    /// Migrated YAML schema document
+    pub fn document(&self) -> &str {
+        &self.document
+    }
+
+    pub fn changes(&self) -> &[Change] {
+        &self.changes
+    }
+
+    pub fn lossy_changes(&self) -> impl Iterator<Item = &Change> {
+        self.changes.iter().filter(|x| x.lossy)
+    }
+
+    pub fn is_lossy(&self) -> bool {
+        self.changes.iter().any(|x| x.lossy)
+    }
+}
+
+/// Migrates the version 1 YAML schema document to the version 2
+///
+/// The version 2 document is returned untouched.
+///
+/// # Arguments
+///
+/// * `document` - YAML schema document
+pub fn migrate(document: &str) -> Result<Migration, Error> {
+    let value: Value = serde_yaml::from_str(document).map_err(|e| Error::from_yaml(e, document))?;
+
+    let mut mapping = match value {
+        Value::Mapping(x) => x,
+        _ => return Err(Error::message("schema document must be a mapping")),
+    };
+
+    match mapping.get(&key("version")) {
+        None => {}
+        Some(Value::Number(x)) if x.as_u64() == Some(1) => {}
+        Some(_) if document_version(document) == 2 => {
+            return Ok(Migration {
+                document: document.to_string(),
+                changes: vec![],
+            });
+        }
+        Some(x) => return Err(Error::message(format!("unsupported version: {:?}", x))),
+    };
+
+    let mut migrator = Migrator { changes: vec![] };
+    migrator.schema(&mut mapping, "");
+
+    // Keep the version as the first keyword
+    let mut migrated = Mapping::new();
+    migrated.insert(key("version"), Value::Number(2.into()));
+    mapping.remove(&key("version"));
+    migrated.extend(mapping);
+
+    migrator.changes.insert(
+        0,
+        Change {
+            path: "version".to_string(),
+            message: "version set to 2".to_string(),
+            lossy: false,
+        },
+    );
+
+    Ok(Migration {
+        document: serde_yaml::to_string(&Value::Mapping(migrated))?,
+        changes: migrator.changes,
+    })
+}
+
+fn key(keyword: &str) -> Value {
+    Value::String(keyword.to_string())
+}
+
+fn join(path: &str, component: &str) -> String {
+    if path.is_empty() {
+        component.to_string()
+    } else {
+        format!("{}.{}", path, component)
+    }
+}
+
+struct Migrator {
+    changes: Vec<Change>,
+}
+
+impl Migrator {
+    fn push(&mut self, path: String, message: String, lossy: bool) {
+        self.changes.push(Change { path, message, lossy });
+    }
+
+    fn schema(&mut self, mapping: &mut Mapping, path: &str) {
+        self.remove_unknown_keywords(mapping, path);
+        self.rename_type(mapping, path);
+        self.children(mapping, path);
+    }
+
+    fn remove_unknown_keywords(&mut self, mapping: &mut Mapping, path: &str) {
+        let unknown: Vec<Value> = mapping
+            .iter()
+            .map(|(keyword, _)| keyword)
+            .filter(|keyword| match keyword.as_str() {
+                Some(x) => !KEYWORDS.contains(&x) && x != INCLUDE_KEYWORD && !x.starts_with(VERSION_2_EXTENSION_PREFIX),
+                None => true,
+            })
+            .cloned()
+            .collect();
+
+        for keyword in unknown {
+            mapping.remove(&keyword);
+
+            let name = match &keyword {
+                Value::String(x) => x.clone(),
+                x => format!("{:?}", x),
+            };
+            let message = match suggestion(&name) {
+                Some(x) => format!("unknown keyword '{}' removed, did you mean '{}'?", name, x),
+                None => format!("unknown keyword '{}' removed", name),
+            };
+            self.push(join(path, &name), message, true);
+        }
+    }
+
+    fn rename_type(&mut self, mapping: &mut Mapping, path: &str) {
+        let r#type = match mapping.get_mut(&key("type")) {
+            Some(Value::String(x)) => x,
+            _ => return,
+        };
+
+        let (name, suffix) = match r#type.strip_suffix('?') {
+            Some(name) => (name, "?"),
+            None => (r#type.as_str(), ""),
+        };
+
+        if let Some(renamed) = renamed_keyword(name) {
+            let message = format!("type '{}' renamed to '{}'", name, renamed);
+            *r#type = format!("{}{}", renamed, suffix);
+            self.push(join(path, "type"), message, false);
+        }
+    }
+
+    fn children(&mut self, mapping: &mut Mapping, path: &str) {
+        if let Some(Value::Mapping(definitions)) = mapping.get_mut(&key("definitions")) {
+            for (name, definition) in definitions.iter_mut() {
+                if let (Some(name), Value::Mapping(definition)) = (name.as_str(), definition) {
+                    self.schema(definition, &join(path, &format!("definitions.{}", name)));
+                }
+            }
+        }
+
+        if let Some(Value::Sequence(properties)) = mapping.get_mut(&key("properties")) {
+            for (index, property) in properties.iter_mut().enumerate() {
+                if let Value::Mapping(property) = property {
+                    for (name, schema) in property.iter_mut() {
+                        if let (Some(name), Value::Mapping(schema)) = (name.as_str(), schema) {
+                            self.schema(schema, &join(path, &format!("properties[{}].{}", index, name)));
+                        }
+                    }
+                }
+            }
+        }
+
+        for keyword in &["keys", "values"] {
+            if let Some(Value::Mapping(schema)) = mapping.get_mut(&key(keyword)) {
+                self.schema(schema, &join(path, keyword));
+            }
+        }
+
+        match mapping.get_mut(&key("items")) {
+            Some(Value::Mapping(schema)) => self.schema(schema, &join(path, "items")),
+            Some(Value::Sequence(items)) => self.sequence(items, &join(path, "items")),
+            _ => {}
+        };
+
+        for keyword in &["oneOf", "anyOf", "allOf"] {
+            if let Some(Value::Sequence(schemas)) = mapping.get_mut(&key(keyword)) {
+                self.sequence(schemas, &join(path, keyword));
+            }
+        }
+    }
+
+    fn sequence(&mut self, schemas: &mut [Value], path: &str) {
+        for (index, schema) in schemas.iter_mut().enumerate() {
+            if let Value::Mapping(schema) = schema {
+                self.schema(schema, &format!("{}[{}]", path, index));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::{PrimitiveType, Schema};
+
+    fn lossy_paths(migration: &Migration) -> Vec<&str> {
+        migration.lossy_changes().map(Change::path).collect()
+    }
+
+    #[test]
+    fn rename_types() {
+        let migration = migrate(
+            r##"
+            version: 1
+            definitions:
+              tags:
+                type: stringlist?
+            properties:
+              - since:
+                  type: datetime
+              - tags:
+                  $ref: "#/definitions/tags"
+            "##,
+        )
+        .unwrap();
+
+        assert!(!migration.is_lossy());
+        let paths: Vec<&str> = migration.changes().iter().map(Change::path).collect();
+        assert_eq!(
+            paths,
+            vec!["version", "definitions.tags.type", "properties[0].since.type"]
+        );
+        assert_eq!(
+            migration.changes()[2].message(),
+            "type 'datetime' renamed to 'date-time'"
+        );
+
+        let schema: Schema = migration.document().parse().unwrap();
+        assert_eq!(schema.version(), Some(2));
+        assert_eq!(
+            schema.definitions()["tags"].r#type().primitive_type(),
+            &PrimitiveType::StringList
+        );
+        assert!(migration.document().contains("type: string-list?"));
+        assert!(migration.document().contains("type: date-time"));
+    }
+
+    #[test]
+    fn remove_unknown_keywords() {
+        let migration = migrate(
+            r#"
+            properties:
+              - network:
+                  type: array
+                  hiden: true
+                  items:
+                    - type: string
+                      foo: bar
+                      x-widget: ssid
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            lossy_paths(&migration),
+            vec!["properties[0].network.hiden", "properties[0].network.items[0].foo"]
+        );
+        assert_eq!(
+            migration.lossy_changes().next().unwrap().to_string(),
+            "properties[0].network.hiden: unknown keyword 'hiden' removed, did you mean 'hidden'? (lossy)"
+        );
+
+        let schema: Schema = migration.document().parse().unwrap();
+        assert_eq!(
+            schema.properties()[0].schema().items()[0]
+                .extension("x-widget")
+                .unwrap(),
+            "ssid"
+        );
+    }
+
+    #[test]
+    fn keep_mapping_and_include() {
+        let document = r#"
+            include: common.yaml
+            mapping:
+              targets:
+                config:
+                  type: file
+                  format: json
+                  location:
+                    path: config.json
+            "#;
+        let migration = migrate(document).unwrap();
+        assert!(!migration.is_lossy());
+        assert!(migration.document().contains("include: common.yaml"));
+        assert!(migration.document().starts_with("---\nversion: 2\n"));
+    }
+
+    #[test]
+    fn keep_version_2() {
+        let document = "version: 2\ntype: date-time\nfoo: bar";
+        let migration = migrate(document).unwrap();
+        assert_eq!(migration.document(), document);
+        assert!(migration.changes().is_empty());
+    }
+
+    #[test]
+    fn fail_on_unsupported_version() {
+        assert!(migrate("version: 3").is_err());
+        assert!(migrate("- foo").is_err());
+    }
+}
@@ -1,25 +1,37 @@
+use std::cell::Cell;
+use std::collections::{BTreeMap, HashSet};
 use std::fmt;
 use std::marker::PhantomData;
 use std::str::FromStr;
+use std::sync::OnceLock;
 
+use lazy_static::lazy_static;
 use regex::Regex;
-use serde_derive::Deserialize;
+use serde_derive::{Deserialize, Serialize};
 use serde_json::{Number, Value};
 
-// Reexport everything except mapping, which is a public module
+// Reexport everything except data_migrations, loader, mapping, migrate & registry, which are public modules
 pub use self::{
+    builder::SchemaBuilder,
+    options::ParseOptions,
     property::Property,
     r#enum::EnumEntry,
     r#type::{PrimitiveType, Type},
     unique_items::UniqueItems,
-    version::Version,
+    version::{Version, LATEST_VERSION},
 };
 
-use crate::{deref::OptionDeref, error::Error};
+use crate::error::Error;
 
+mod builder;
+pub mod data_migrations;
 mod r#enum;
+pub mod loader;
 pub mod mapping;
+pub mod migrate;
+mod options;
 mod property;
+pub mod registry;
 mod r#type;
 mod unique_items;
 mod version;
@@ -32,20 +44,39 @@ mod version;
 /// `serde_json` structures like `Value`, `Number` or Rust types. The reason is
 /// that we're generating JSON values from the JellySchema. And this allows us
 /// to catch missing JSON features (when compared with YAML) during deserialization.
-#[derive(Debug, Deserialize)]
+///
+/// Serialized schema keys are written in a canonical order (the order of the keywords
+/// below) and keywords with default values are omitted.
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+#[serde(remote = "Self")]
 pub struct Schema {
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     version: Option<Version>,
     //
-    // Mapping extension
+    // Annotation keywords
     //
     #[serde(default, skip_serializing_if = "Option::is_none")]
-    mapping: Option<mapping::Mapping>,
+    title: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    help: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    warning: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    description: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    collapsible: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    collapsed: Option<bool>,
     //
     // Any instance type validation keywords
     //
-    #[serde(default, rename = "type", deserialize_with = "deserialize_from_str")]
-    r#type: Type,
+    #[serde(
+        default,
+        rename = "type",
+        deserialize_with = "deserialize_option_from_str",
+        skip_serializing_if = "Option::is_none"
+    )]
+    r#type: Option<Type>,
     #[serde(default, rename = "const", skip_serializing_if = "Option::is_none")]
     r#const: Option<Value>,
     #[serde(default, rename = "default", skip_serializing_if = "Option::is_none")]
@@ -58,15 +89,54 @@ pub struct Schema {
         skip_serializing_if = "Option::is_none"
     )]
     formula: Option<String>,
-    #[serde(default, rename = "readOnly")]
+    #[serde(
+        default,
+        deserialize_with = "deserialize_as_optional_condition",
+        skip_serializing_if = "Option::is_none"
+    )]
+    when: Option<String>,
+    #[serde(default, rename = "readOnly", skip_serializing_if = "is_false")]
     read_only: bool,
-    #[serde(default, rename = "writeOnly")]
+    #[serde(default, rename = "writeOnly", skip_serializing_if = "is_false")]
     write_only: bool,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     placeholder: Option<String>,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "is_false")]
     hidden: bool,
     //
+    // Reusable definitions
+    //
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    definitions: BTreeMap<String, Schema>,
+    #[serde(default, rename = "$ref", skip_serializing_if = "Option::is_none")]
+    r#ref: Option<String>,
+    // Referenced definition merged with the keywords next to the `$ref`
+    #[serde(skip)]
+    resolved: OnceLock<Box<Schema>>,
+    //
+    // Combinator keywords
+    //
+    #[serde(default, rename = "oneOf", skip_serializing_if = "Vec::is_empty")]
+    one_of: Vec<Schema>,
+    #[serde(default, rename = "anyOf", skip_serializing_if = "Vec::is_empty")]
+    any_of: Vec<Schema>,
+    #[serde(default, rename = "allOf", skip_serializing_if = "Vec::is_empty")]
+    all_of: Vec<Schema>,
+    //
+    // Mapping extension
+    //
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    mapping: Option<mapping::Mapping>,
+    //
+    // Data migrations extension
+    //
+    #[serde(
+        default,
+        skip_serializing_if = "data_migrations::Migrations::is_empty",
+        deserialize_with = "deserialize_root_migrations"
+    )]
+    migrations: data_migrations::Migrations,
+    //
     // Object validation keywords
     //
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
@@ -75,7 +145,7 @@ pub struct Schema {
     keys: Option<Box<Schema>>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     values: Option<Box<Schema>>,
-    #[serde(default, rename = "additionalProperties")]
+    #[serde(default, rename = "additionalProperties", skip_serializing_if = "is_false")]
     additional_properties: bool,
     //
     // StringList keywords
@@ -83,40 +153,31 @@ pub struct Schema {
     #[serde(default, skip_serializing_if = "Option::is_none")]
     separator: Option<String>,
     //
-    // Annotation keywords
+    // IPTablesAddress keywords
     //
-    #[serde(default, skip_serializing_if = "Option::is_none")]
-    title: Option<String>,
-    #[serde(default, skip_serializing_if = "Option::is_none")]
-    help: Option<String>,
-    #[serde(default, skip_serializing_if = "Option::is_none")]
-    warning: Option<String>,
-    #[serde(default, skip_serializing_if = "Option::is_none")]
-    description: Option<String>,
-    #[serde(default, skip_serializing_if = "Option::is_none")]
-    collapsible: Option<bool>,
-    #[serde(default, skip_serializing_if = "Option::is_none")]
-    collapsed: Option<bool>,
+    #[serde(default, rename = "strictMask", skip_serializing_if = "is_false")]
+    strict_mask: bool,
     //
     // Array validation keywords
     //
     #[serde(
         default,
         skip_serializing_if = "Vec::is_empty",
-        deserialize_with = "deserialize_struct_or_vec"
+        deserialize_with = "deserialize_struct_or_vec",
+        serialize_with = "serialize_struct_or_vec"
     )]
     items: Vec<Schema>,
     #[serde(default, rename = "maxItems", skip_serializing_if = "Option::is_none")]
     max_items: Option<usize>,
     #[serde(default, rename = "minItems", skip_serializing_if = "Option::is_none")]
     min_items: Option<usize>,
-    #[serde(default, rename = "uniqueItems")]
+    #[serde(default, rename = "uniqueItems", skip_serializing_if = "is_default")]
     unique_items: UniqueItems,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     orderable: Option<bool>,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     addable: Option<bool>,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     removable: Option<bool>,
     //
     // Number validation keywords
@@ -141,9 +202,15 @@ pub struct Schema {
     #[serde(
         default,
         skip_serializing_if = "Option::is_none",
-        deserialize_with = "deserialize_option_from_str"
+        deserialize_with = "deserialize_option_from_str",
+        serialize_with = "serialize_option_as_str"
     )]
     pattern: Option<Regex>,
+    //
+    // Unknown keywords, only the ones with a registered extension prefix are kept
+    //
+    #[serde(flatten)]
+    extensions: options::Extensions,
 }
 
 impl Schema {
@@ -152,12 +219,336 @@ impl Schema {
     }
 }
 
+//
+// Reusable definitions
+//
+impl Schema {
+    pub fn definitions(&self) -> &BTreeMap<String, Schema> {
+        &self.definitions
+    }
+
+    pub fn definition(&self, name: &str) -> Option<&Schema> {
+        self.definitions.get(name)
+    }
+
+    pub fn r#ref(&self) -> Option<&str> {
+        self.r#ref.as_deref()
+    }
+
+    /// Resolves `$ref` against the definitions of this (root) schema
+    ///
+    /// Annotation keywords, `when` and the `type` optionality next to the `$ref`
+    /// are merged over the referenced definition, other keywords are rejected
+    /// during deserialization. Schemas without `$ref` are returned as they are.
+    pub fn resolve<'a>(&'a self, schema: &'a Schema) -> &'a Schema {
+        // References are checked during deserialization, the limit is here
+        // just to avoid infinite loops for unchecked schemas
+        self.resolve_with_limit(schema, self.definitions.len())
+    }
+
+    fn resolve_with_limit<'a>(&'a self, schema: &'a Schema, limit: usize) -> &'a Schema {
+        let definition = match schema
+            .r#ref()
+            .and_then(definition_name)
+            .and_then(|x| self.definition(x))
+        {
+            Some(definition) if limit > 0 => self.resolve_with_limit(definition, limit - 1),
+            _ => return schema,
+        };
+
+        if !schema.has_ref_siblings() {
+            return definition;
+        }
+
+        schema.resolved.get_or_init(|| Box::new(schema.merge_over(definition)))
+    }
+
+    fn has_ref_siblings(&self) -> bool {
+        self.title.is_some()
+            || self.help.is_some()
+            || self.warning.is_some()
+            || self.description.is_some()
+            || self.collapsible.is_some()
+            || self.collapsed.is_some()
+            || self.r#type.is_some()
+            || self.when.is_some()
+            || self.read_only
+            || self.write_only
+            || self.placeholder.is_some()
+            || self.hidden
+            || !self.extensions.values().is_empty()
+    }
+
+    // Copy of the definition with the keywords next to the `$ref` of this schema
+    fn merge_over(&self, definition: &Schema) -> Schema {
+        let mut result = definition.clone();
+        result.resolved = OnceLock::new();
+
+        if let Some(r#type) = &self.r#type {
+            result.r#type = Some(Type::new(
+                definition.r#type().primitive_type().clone(),
+                r#type.is_optional(),
+            ));
+        }
+
+        macro_rules! merge {
+            ($($field:ident),*) => {
+                $(
+                    if self.$field.is_some() {
+                        result.$field = self.$field.clone();
+                    }
+                )*
+            };
+        }
+        merge!(
+            title,
+            help,
+            warning,
+            description,
+            collapsible,
+            collapsed,
+            when,
+            placeholder
+        );
+
+        result.read_only |= self.read_only;
+        result.write_only |= self.write_only;
+        result.hidden |= self.hidden;
+
+        for (keyword, value) in self.extensions.values() {
+            result.extensions.insert(keyword.clone(), value.clone());
+        }
+
+        result
+    }
+
+    pub(crate) fn has_references(&self) -> bool {
+        let mut references = vec![];
+        self.references(&mut references);
+        !references.is_empty()
+    }
+
+    // Schemas with the `$ref` keyword
+    fn references<'a>(&'a self, references: &mut Vec<&'a Schema>) {
+        if self.r#ref().is_some() {
+            references.push(self);
+        }
+
+        let children = self
+            .definitions
+            .values()
+            .chain(self.properties.iter().map(Property::schema))
+            .chain(self.keys.as_deref())
+            .chain(self.values.as_deref())
+            .chain(self.items.iter())
+            .chain(self.one_of.iter())
+            .chain(self.any_of.iter())
+            .chain(self.all_of.iter());
+
+        for child in children {
+            child.references(references);
+        }
+    }
+
+    // Checks that all references point to existing definitions, that they
+    // do not form a cycle without any schema in between and that there are
+    // only supported keywords next to them
+    fn check_references(&self) -> Result<(), Error> {
+        let mut references = vec![];
+        self.references(&mut references);
+
+        for schema in references {
+            let reference = schema.r#ref().unwrap_or_default();
+            let name = definition_name(reference).ok_or_else(|| {
+                Error::message(format!(
+                    "unsupported $ref '{}', only '#/definitions/<name>' is supported",
+                    reference
+                ))
+            })?;
+
+            let mut visited = HashSet::new();
+            let mut current = name;
+
+            let definition = loop {
+                if !visited.insert(current) {
+                    return Err(Error::message(format!(
+                        "cyclic $ref '{}' never resolves to a schema",
+                        reference
+                    )));
+                }
+
+                let definition = self
+                    .definition(current)
+                    .ok_or_else(|| Error::message(format!("dangling $ref '{}'", reference)))?;
+
+                match definition.r#ref() {
+                    Some(next) => {
+                        current = definition_name(next).ok_or_else(|| {
+                            Error::message(format!(
+                                "unsupported $ref '{}', only '#/definitions/<name>' is supported",
+                                next
+                            ))
+                        })?;
+                    }
+                    None => break definition,
+                }
+            };
+
+            check_ref_siblings(schema, reference, definition)?;
+        }
+
+        Ok(())
+    }
+}
+
+// Keywords allowed next to the `$ref`, vendor extensions are allowed as well
+const REF_SIBLING_KEYWORDS: &[&str] = &[
+    "$ref",
+    "title",
+    "help",
+    "warning",
+    "description",
+    "collapsible",
+    "collapsed",
+    "type",
+    "when",
+    "readOnly",
+    "writeOnly",
+    "placeholder",
+    "hidden",
+];
+
+// Only the keywords merged by `Schema::resolve` are allowed next to the `$ref`,
+// `definition` is the end of the reference chain
+fn check_ref_siblings(schema: &Schema, reference: &str, definition: &Schema) -> Result<(), Error> {
+    let keywords = serde_json::to_value(schema).map_err(|e| Error::message(e.to_string()))?;
+
+    if let Some(keyword) =
+        keywords.as_object().into_iter().flat_map(|x| x.keys()).find(|x| {
+            !REF_SIBLING_KEYWORDS.contains(&x.as_str()) && !schema.extensions.values().contains_key(x.as_str())
+        })
+    {
+        return Err(Error::message(format!(
+            "keyword '{}' is not allowed next to the $ref '{}'",
+            keyword, reference
+        )));
+    }
+
+    if let Some(r#type) = &schema.r#type {
+        if r#type.primitive_type() != definition.r#type().primitive_type() {
+            return Err(Error::message(format!(
+                "type '{}' next to the $ref '{}' does not match the definition type '{}'",
+                r#type,
+                reference,
+                definition.r#type()
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+fn definition_name(reference: &str) -> Option<&str> {
+    if reference.starts_with("#/definitions/") && reference.len() > "#/definitions/".len() {
+        Some(&reference["#/definitions/".len()..])
+    } else {
+        None
+    }
+}
+
+lazy_static! {
+    static ref DEFAULT_TYPE: Type = Type::default();
+}
+
+impl Schema {
+    // Combined schemas (`oneOf`, ...) without the `type` keyword inherit the type
+    // of the combining schema
+    fn inherit_types(&mut self) {
+        let r#type = self.r#type().clone();
+
+        for schema in self
+            .one_of
+            .iter_mut()
+            .chain(self.any_of.iter_mut())
+            .chain(self.all_of.iter_mut())
+        {
+            // Referencing schemas get the type of the definition
+            if schema.r#type.is_none() && schema.r#ref.is_none() {
+                schema.r#type = Some(r#type.clone());
+            }
+        }
+
+        let children = self
+            .definitions
+            .values_mut()
+            .chain(self.properties.iter_mut().map(Property::schema_mut))
+            .chain(self.keys.as_deref_mut())
+            .chain(self.values.as_deref_mut())
+            .chain(self.items.iter_mut())
+            .chain(self.one_of.iter_mut())
+            .chain(self.any_of.iter_mut())
+            .chain(self.all_of.iter_mut());
+
+        for child in children {
+            child.inherit_types();
+        }
+    }
+}
+
+impl Schema {
+    // Root schema only, nested schemas can reference root schema definitions
+    fn finalize(mut self) -> Result<Schema, Error> {
+        self.check_references()?;
+        self.inherit_types();
+        Ok(self)
+    }
+}
+
+thread_local! {
+    // Nesting level of schemas being deserialized, references can be checked
+    // (and types inherited) only when the root schema is deserialized
+    static DEPTH: Cell<usize> = const { Cell::new(0) };
+}
+
+impl<'de> serde::de::Deserialize<'de> for Schema {
+    fn deserialize<D>(deserializer: D) -> Result<Schema, D::Error>
+    where
+        D: serde::de::Deserializer<'de>,
+    {
+        DEPTH.with(|x| x.set(x.get() + 1));
+        let result = Schema::deserialize(deserializer);
+        let depth = DEPTH.with(|x| {
+            x.set(x.get() - 1);
+            x.get()
+        });
+
+        let schema = result?;
+        if depth == 0 {
+            schema.finalize().map_err(serde::de::Error::custom)
+        } else {
+            Ok(schema)
+        }
+    }
+}
+
+impl serde::ser::Serialize for Schema {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::ser::Serializer,
+    {
+        match &self.version {
+            Some(version) => version::with_grammar(Some(version.value()), || Schema::serialize(self, serializer)),
+            None => Schema::serialize(self, serializer),
+        }
+    }
+}
+
 //
 // Any instance type
 //
 impl Schema {
     pub fn r#type(&self) -> &Type {
-        &self.r#type
+        self.r#type.as_ref().unwrap_or(&DEFAULT_TYPE)
     }
 
     pub fn r#const(&self) -> Option<&Value> {
@@ -176,6 +567,19 @@ impl Schema {
         self.formula.as_deref()
     }
 
+    /// Condition under which the property is used
+    ///
+    /// The condition is evaluated against the object containing the property (siblings
+    /// are accessible by their names). The property is optional for the validator and
+    /// ignored by the filler if the condition doesn't evaluate to `true`, the validator
+    /// still validates it if present.
+    ///
+    /// The generated JSON Schema can express `sibling` and `sibling == literal` conditions
+    /// only, properties with any other condition are optional there.
+    pub fn when(&self) -> Option<&str> {
+        self.when.as_deref()
+    }
+
     pub fn placeholder(&self) -> Option<&str> {
         self.placeholder.as_deref()
     }
@@ -193,6 +597,44 @@ impl Schema {
     }
 }
 
+//
+// Combinator keywords
+//
+// Data must be valid against the schema itself and against the combined schemas.
+// Combined schemas without the `type` keyword inherit the type of the combining schema.
+//
+impl Schema {
+    /// Data must be valid against exactly one of these schemas
+    pub fn one_of(&self) -> &[Schema] {
+        self.one_of.as_slice()
+    }
+
+    /// Data must be valid against at least one of these schemas
+    pub fn any_of(&self) -> &[Schema] {
+        self.any_of.as_slice()
+    }
+
+    /// Data must be valid against all of these schemas
+    pub fn all_of(&self) -> &[Schema] {
+        self.all_of.as_slice()
+    }
+}
+
+//
+// Vendor extensions
+//
+// Keywords starting with one of the `ParseOptions::extension_prefix` prefixes.
+//
+impl Schema {
+    pub fn extensions(&self) -> &BTreeMap<String, Value> {
+        self.extensions.values()
+    }
+
+    pub fn extension(&self, keyword: &str) -> Option<&Value> {
+        self.extensions.values().get(keyword)
+    }
+}
+
 //
 // Mapping extension
 //
@@ -202,6 +644,22 @@ impl Schema {
     }
 }
 
+//
+// Data migrations extension
+//
+impl Schema {
+    pub fn migrations(&self) -> &[data_migrations::DataMigration] {
+        self.migrations.migrations()
+    }
+
+    /// Current revision of data documents, see the [`data_migrations`] module
+    ///
+    /// [`data_migrations`]: data_migrations/index.html
+    pub fn revision(&self) -> u32 {
+        self.migrations.revision()
+    }
+}
+
 //
 // Object validation keywords
 //
@@ -230,6 +688,78 @@ impl Schema {
     pub fn separator(&self) -> Option<&str> {
         self.separator.as_deref()
     }
+
+    /// Unescaped `separator` (`\n`, `\r`, `\t`, `\\`), `\n` if not provided
+    pub fn unescaped_separator(&self) -> String {
+        let separator = match self.separator() {
+            Some(separator) if !separator.is_empty() => separator,
+            _ => "\\n",
+        };
+        let mut result = String::with_capacity(separator.len());
+        let mut chars = separator.chars();
+
+        while let Some(c) = chars.next() {
+            match (c, chars.clone().next()) {
+                ('\\', Some('n')) => result.push('\n'),
+                ('\\', Some('r')) => result.push('\r'),
+                ('\\', Some('t')) => result.push('\t'),
+                ('\\', Some('\\')) => result.push('\\'),
+                _ => {
+                    result.push(c);
+                    continue;
+                }
+            };
+            chars.next();
+        }
+
+        result
+    }
+
+    /// Splits the separator-joined `stringlist` value, empty items are skipped
+    pub fn split_stringlist(&self, s: &str) -> Vec<String> {
+        self.split_stringlist_indexed(s).into_iter().map(|(_, x)| x).collect()
+    }
+
+    /// Splits the separator-joined `stringlist` value, empty items are skipped, the other
+    /// ones are returned with their index in the string (`a,,b` -> `[(0, a), (2, b)]`)
+    pub fn split_stringlist_indexed(&self, s: &str) -> Vec<(usize, String)> {
+        let separator = self.unescaped_separator();
+
+        s.split(separator.as_str())
+            .enumerate()
+            .filter(|(_, x)| !x.is_empty())
+            .map(|(index, x)| (index, x.to_string()))
+            .collect()
+    }
+
+    /// Joins `stringlist` items with the separator
+    pub fn join_stringlist<I, S>(&self, items: I) -> String
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let separator = self.unescaped_separator();
+        let mut result = String::new();
+
+        for (index, item) in items.into_iter().enumerate() {
+            if index > 0 {
+                result.push_str(&separator);
+            }
+            result.push_str(item.as_ref());
+        }
+
+        result
+    }
+}
+
+//
+// IPTablesAddress keywords
+//
+impl Schema {
+    /// `true` if addresses with host bits set (`10.0.0.1/8`) are invalid
+    pub fn strict_mask(&self) -> bool {
+        self.strict_mask
+    }
 }
 
 //
@@ -340,33 +870,103 @@ impl FromStr for Schema {
     type Err = Error;
 
     fn from_str(s: &str) -> Result<Schema, Error> {
-        let schema: Schema = serde_yaml::from_str(s)?;
-        Ok(schema)
+        version::with_grammar(Some(version::document_version(s)), || serde_yaml::from_str(s))
+            .map_err(|e| Error::from_yaml(e, s))
     }
 }
 
-fn deserialize_from_str<'de, S, D>(deserializer: D) -> Result<S, D::Error>
+// Strings are parsed inside the visitor, parse errors are reported at the string position
+fn deserialize_parsed_str<'de, D, T, F>(deserializer: D, parse: F) -> Result<T, D::Error>
 where
-    S: FromStr,
-    S::Err: std::fmt::Display,
     D: serde::de::Deserializer<'de>,
+    F: FnOnce(&str) -> Result<T, String>,
 {
-    let s: String = serde::de::Deserialize::deserialize(deserializer)?;
-    S::from_str(&s).map_err(serde::de::Error::custom)
+    struct ParsedStr<T, F>(F, PhantomData<T>);
+
+    impl<'de, T, F> serde::de::Visitor<'de> for ParsedStr<T, F>
+    where
+        F: FnOnce(&str) -> Result<T, String>,
+    {
+        type Value = T;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str("string")
+        }
+
+        fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+        where
+            E: serde::de::Error,
+        {
+            (self.0)(v).map_err(E::custom)
+        }
+    }
+
+    deserializer.deserialize_str(ParsedStr(parse, PhantomData))
+}
+
+fn deserialize_root_migrations<'de, D>(deserializer: D) -> Result<data_migrations::Migrations, D::Error>
+where
+    D: serde::de::Deserializer<'de>,
+{
+    if DEPTH.with(Cell::get) > 1 {
+        return Err(serde::de::Error::custom(
+            "migrations are allowed in the root schema only",
+        ));
+    }
+    serde::de::Deserialize::deserialize(deserializer)
+}
+
+fn deserialize_as_optional_condition<'de, D>(deserializer: D) -> Result<Option<String>, D::Error>
+where
+    D: serde::de::Deserializer<'de>,
+{
+    deserialize_parsed_str(deserializer, |condition| {
+        crate::formula::parse(condition)
+            .map(|_| Some(condition.to_string()))
+            .map_err(|e| format!("invalid condition '{}': {}", condition, e))
+    })
 }
 
 fn deserialize_as_optional_string<'de, D>(deserializer: D) -> Result<Option<String>, D::Error>
 where
     D: serde::de::Deserializer<'de>,
 {
-    let s: String = match serde::de::Deserialize::deserialize(deserializer)? {
-        Value::Bool(x) => format!("{}", x),
-        Value::Number(x) => format!("{}", x),
-        Value::String(x) => x.clone(),
-        _ => return Err(serde::de::Error::custom("unable to deserialize as string")),
-    };
+    struct PrimitiveAsString;
+
+    impl<'de> serde::de::Visitor<'de> for PrimitiveAsString {
+        type Value = Option<String>;
 
-    Ok(Some(s))
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str("string, number or boolean")
+        }
+
+        fn visit_bool<E>(self, v: bool) -> Result<Self::Value, E> {
+            Ok(Some(format!("{}", v)))
+        }
+
+        fn visit_i64<E>(self, v: i64) -> Result<Self::Value, E> {
+            Ok(Some(format!("{}", v)))
+        }
+
+        fn visit_u64<E>(self, v: u64) -> Result<Self::Value, E> {
+            Ok(Some(format!("{}", v)))
+        }
+
+        fn visit_f64<E>(self, v: f64) -> Result<Self::Value, E>
+        where
+            E: serde::de::Error,
+        {
+            Number::from_f64(v)
+                .map(|x| Some(format!("{}", x)))
+                .ok_or_else(|| E::custom("unable to deserialize as string"))
+        }
+
+        fn visit_str<E>(self, v: &str) -> Result<Self::Value, E> {
+            Ok(Some(v.to_string()))
+        }
+    }
+
+    deserializer.deserialize_any(PrimitiveAsString)
 }
 
 fn deserialize_option_from_str<'de, S, D>(deserializer: D) -> Result<Option<S>, D::Error>
@@ -375,8 +975,7 @@ where
     S::Err: std::fmt::Display,
     D: serde::de::Deserializer<'de>,
 {
-    let s: String = serde::de::Deserialize::deserialize(deserializer)?;
-    Ok(Some(S::from_str(&s).map_err(serde::de::Error::custom)?))
+    deserialize_parsed_str(deserializer, |s| S::from_str(s).map(Some).map_err(|e| e.to_string()))
 }
 
 fn deserialize_struct_or_vec<'de, D, T>(deserializer: D) -> Result<Vec<T>, D::Error>
@@ -413,3 +1012,94 @@ where
 
     deserializer.deserialize_any(StructOrVec(PhantomData))
 }
+
+fn is_false(value: &bool) -> bool {
+    !value
+}
+
+fn is_default<T>(value: &T) -> bool
+where
+    T: Default + PartialEq,
+{
+    value == &T::default()
+}
+
+fn serialize_option_as_str<T, S>(value: &Option<T>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    T: fmt::Display,
+    S: serde::ser::Serializer,
+{
+    match value {
+        Some(value) => serializer.collect_str(value),
+        None => serializer.serialize_none(),
+    }
+}
+
+// Single item is serialized as a struct, which is the way how it's usually written
+fn serialize_struct_or_vec<T, S>(value: &[T], serializer: S) -> Result<S::Ok, S::Error>
+where
+    T: serde::ser::Serialize,
+    S: serde::ser::Serializer,
+{
+    match value {
+        [item] => serde::ser::Serialize::serialize(item, serializer),
+        items => serde::ser::Serialize::serialize(items, serializer),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn serialize_in_canonical_order() {
+        let schema: Schema = r#"
+            properties:
+              - ssid:
+                  minLength: 1
+                  type: string?
+                  hidden: false
+                  title: SSID
+            version: 1
+            title: Network
+        "#
+        .parse()
+        .unwrap();
+
+        assert_eq!(
+            serde_yaml::to_string(&schema).unwrap(),
+            "---\nversion: 1\ntitle: Network\nproperties:\n  - ssid:\n      title: SSID\n      type: string?\n      minLength: 1\n"
+        );
+    }
+
+    #[test]
+    fn serialize_extensions() {
+        let schema = ParseOptions::new()
+            .extension_prefix("x-")
+            .parse("x-widget: color\ntype: string")
+            .unwrap();
+        assert_eq!(
+            serde_json::to_string(&schema).unwrap(),
+            r#"{"type":"string","x-widget":"color"}"#
+        );
+    }
+
+    #[test]
+    fn stringlist_helpers() {
+        let schema: Schema = "type: stringlist\nseparator: ','".parse().unwrap();
+        assert_eq!(schema.split_stringlist("a,b,,c,"), vec!["a", "b", "c"]);
+        assert_eq!(
+            schema.split_stringlist_indexed("a,,foo"),
+            vec![(0, "a".to_string()), (2, "foo".to_string())]
+        );
+        assert_eq!(schema.join_stringlist(["a", "b"]), "a,b");
+        assert!(schema.split_stringlist("").is_empty());
+
+        let schema: Schema = "type: stringlist".parse().unwrap();
+        assert_eq!(schema.unescaped_separator(), "\n");
+        assert_eq!(schema.split_stringlist("a\nb\n"), vec!["a", "b"]);
+
+        let schema: Schema = r"{type: stringlist, separator: '\t\\'}".parse().unwrap();
+        assert_eq!(schema.unescaped_separator(), "\t\\");
+    }
+}
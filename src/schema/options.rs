@@ -0,0 +1,443 @@
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::Arc;
+
+use serde::{de, ser};
+use serde_json::Value;
+
+use crate::{
+    error::Error,
+    schema::{
+        registry::{CustomType, Registry},
+        version, Schema,
+    },
+};
+
+/// All keywords of the schema, unknown keywords are rejected in the strict mode
+pub(crate) const KEYWORDS: &[&str] = &[
+    "version",
+    "definitions",
+    "$ref",
+    "oneOf",
+    "anyOf",
+    "allOf",
+    "mapping",
+    "migrations",
+    "type",
+    "const",
+    "default",
+    "enum",
+    "formula",
+    "when",
+    "readOnly",
+    "writeOnly",
+    "placeholder",
+    "hidden",
+    "properties",
+    "keys",
+    "values",
+    "additionalProperties",
+    "separator",
+    "strictMask",
+    "title",
+    "help",
+    "warning",
+    "description",
+    "collapsible",
+    "collapsed",
+    "items",
+    "maxItems",
+    "minItems",
+    "uniqueItems",
+    "orderable",
+    "addable",
+    "removable",
+    "multipleOf",
+    "max",
+    "exclusiveMax",
+    "min",
+    "exclusiveMin",
+    "maxLength",
+    "minLength",
+    "pattern",
+];
+
+/// Schema parsing options
+///
+/// Unknown keywords are ignored by default, which means that a misspelled keyword
+/// (`maxlength` for example) is silently lost. The strict mode rejects them.
+///
+/// Keywords starting with a registered extension prefix are always accepted and
+/// available via the [`Schema::extensions`] method.
+///
+/// Version 2 schemas are always strict and the `x-` prefix is always registered.
+///
+/// Custom types of the [`Registry`] are available to the parsed schema only.
+///
+/// # Examples
+///
+/// ```
+/// use jellyschema::schema::ParseOptions;
+///
+/// let options = ParseOptions::new().strict(true).extension_prefix("x-");
+///
+/// let schema = options.parse("type: string\nx-widget: color").unwrap();
+/// assert_eq!(schema.extension("x-widget").unwrap(), "color");
+///
+/// let error = options.parse("type: string\nmaxlength: 10").unwrap_err();
+/// assert_eq!(error.msg(), "unknown keyword 'maxlength', did you mean 'maxLength'?");
+/// ```
+///
+/// [`Schema::extensions`]: struct.Schema.html#method.extensions
+/// [`Registry`]: registry/struct.Registry.html
+#[derive(Debug, Clone, Default)]
+pub struct ParseOptions {
+    strict: bool,
+    extension_prefixes: Vec<String>,
+    registry: Registry,
+}
+
+impl ParseOptions {
+    pub fn new() -> ParseOptions {
+        ParseOptions::default()
+    }
+
+    /// Rejects unknown keywords if `true`
+    pub fn strict(mut self, strict: bool) -> ParseOptions {
+        self.strict = strict;
+        self
+    }
+
+    /// Registers vendor extension prefix (`x-` for example)
+    pub fn extension_prefix<S>(mut self, prefix: S) -> ParseOptions
+    where
+        S: Into<String>,
+    {
+        self.extension_prefixes.push(prefix.into());
+        self
+    }
+
+    /// Custom types available to the schema
+    pub fn registry(mut self, registry: Registry) -> ParseOptions {
+        self.registry = registry;
+        self
+    }
+
+    pub fn is_strict(&self) -> bool {
+        self.strict
+    }
+
+    pub fn extension_prefixes(&self) -> &[String] {
+        &self.extension_prefixes
+    }
+
+    pub fn is_extension(&self, keyword: &str) -> bool {
+        self.extension_prefixes.iter().any(|x| keyword.starts_with(x.as_str()))
+    }
+
+    /// Parses the schema with these options
+    pub fn parse(&self, s: &str) -> Result<Schema, Error> {
+        self.apply(|| s.parse())
+    }
+
+    // Deserializers do not have access to any context, options are passed via
+    // the thread local storage
+    pub(crate) fn apply<T, F>(&self, f: F) -> T
+    where
+        F: FnOnce() -> T,
+    {
+        let previous = OPTIONS.with(|x| x.replace(self.clone()));
+        let result = f();
+        OPTIONS.with(|x| x.replace(previous));
+        result
+    }
+}
+
+/// Vendor extension prefix of the version 2 schema
+pub(crate) const VERSION_2_EXTENSION_PREFIX: &str = "x-";
+
+thread_local! {
+    // Options of the schema being deserialized
+    static OPTIONS: RefCell<ParseOptions> = RefCell::new(ParseOptions::default());
+}
+
+// Custom type of the registry of the schema being deserialized
+pub(crate) fn registered_type(name: &str) -> Option<Arc<CustomType>> {
+    OPTIONS.with(|x| x.borrow().registry.get(name).cloned())
+}
+
+fn distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, x) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+
+        for (j, y) in b.iter().enumerate() {
+            let substitution = previous[j] + (x != *y) as usize;
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+
+        previous = current;
+    }
+
+    previous[b.len()]
+}
+
+// The most similar known keyword, if there's any similar enough
+pub(crate) fn suggestion(keyword: &str) -> Option<&'static str> {
+    if let Some(x) = KEYWORDS.iter().find(|x| x.eq_ignore_ascii_case(keyword)) {
+        return Some(x);
+    }
+
+    KEYWORDS
+        .iter()
+        .map(|x| (distance(&keyword.to_lowercase(), &x.to_lowercase()), *x))
+        .filter(|(distance, _)| *distance <= 2 && distance * 3 <= keyword.len())
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, x)| x)
+}
+
+fn unknown_keyword_message(keyword: &str) -> String {
+    match suggestion(keyword) {
+        Some(x) => format!("unknown keyword '{}', did you mean '{}'?", keyword, x),
+        None => format!("unknown keyword '{}'", keyword),
+    }
+}
+
+/// Keywords of the schema not known to the JellySchema
+#[derive(Debug, Default, Clone)]
+pub(crate) struct Extensions(BTreeMap<String, Value>);
+
+impl Extensions {
+    pub(crate) fn values(&self) -> &BTreeMap<String, Value> {
+        &self.0
+    }
+
+    pub(crate) fn insert(&mut self, keyword: String, value: Value) {
+        self.0.insert(keyword, value);
+    }
+}
+
+struct ExtensionsVisitor;
+
+impl<'de> de::Visitor<'de> for ExtensionsVisitor {
+    type Value = Extensions;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("schema keywords")
+    }
+
+    fn visit_map<M>(self, mut access: M) -> Result<Self::Value, M::Error>
+    where
+        M: de::MapAccess<'de>,
+    {
+        let mut options = OPTIONS.with(|x| x.borrow().clone());
+        match version::grammar() {
+            Some(2) => options = options.strict(true).extension_prefix(VERSION_2_EXTENSION_PREFIX),
+            // Unknown version (generic deserializer), do not lose possible version 2 extensions
+            None => options = options.extension_prefix(VERSION_2_EXTENSION_PREFIX),
+            _ => {}
+        };
+        let mut extensions = BTreeMap::new();
+
+        while let Some(keyword) = access.next_key::<String>()? {
+            if options.is_extension(&keyword) {
+                let value = access.next_value()?;
+                extensions.insert(keyword, value);
+            } else if options.is_strict() {
+                return Err(de::Error::custom(unknown_keyword_message(&keyword)));
+            } else {
+                access.next_value::<de::IgnoredAny>()?;
+            }
+        }
+
+        Ok(Extensions(extensions))
+    }
+}
+
+impl ser::Serialize for Extensions {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: ser::Serializer,
+    {
+        ser::Serialize::serialize(&self.0, serializer)
+    }
+}
+
+impl<'de> de::Deserialize<'de> for Extensions {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        deserializer.deserialize_map(ExtensionsVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strict() -> ParseOptions {
+        ParseOptions::new().strict(true)
+    }
+
+    #[test]
+    fn ignore_unknown_keywords_by_default() {
+        let schema: Schema = "type: string\nmaxlength: 10\nx-foo: bar".parse().unwrap();
+        assert_eq!(schema.max_length(), None);
+        assert!(schema.extensions().is_empty());
+    }
+
+    #[test]
+    fn reject_unknown_keywords() {
+        let error = strict().parse("type: string\nreadonly: true").unwrap_err();
+        assert_eq!(error.msg(), "unknown keyword 'readonly', did you mean 'readOnly'?");
+
+        let error = strict().parse("type: string\nmaxLenght: 10").unwrap_err();
+        assert_eq!(error.msg(), "unknown keyword 'maxLenght', did you mean 'maxLength'?");
+
+        let error = strict().parse("type: string\nfoo: 10").unwrap_err();
+        assert_eq!(error.msg(), "unknown keyword 'foo'");
+    }
+
+    #[test]
+    fn reject_nested_unknown_keywords() {
+        let schema = r#"
+            properties:
+              - network:
+                  type: array
+                  items:
+                    properties:
+                      - ssid:
+                          type: string
+                          hiden: true
+        "#;
+        let error = strict().parse(schema).unwrap_err();
+        assert_eq!(error.msg(), "unknown keyword 'hiden', did you mean 'hidden'?");
+        assert_eq!(error.path(), Some("properties[0].network.items.properties[0].ssid"));
+    }
+
+    #[test]
+    fn keywords_match_schema_fields() {
+        use std::sync::OnceLock;
+
+        use crate::schema::{mapping::Mapping, EnumEntry, PrimitiveType, Property, Type, UniqueItems, Version};
+
+        let child = <Schema as Default>::default;
+        let number = || serde_json::Number::from(1);
+        let migrations = "migrations:\n  - revision: 2\n".parse::<Schema>().unwrap().migrations;
+
+        // No `..Default::default()`, new fields must be populated here
+        let schema = Schema {
+            version: Some(Version::new(2).unwrap()),
+            title: Some("title".to_string()),
+            help: Some("help".to_string()),
+            warning: Some("warning".to_string()),
+            description: Some("description".to_string()),
+            collapsible: Some(true),
+            collapsed: Some(true),
+            r#type: Some(Type::new_required(PrimitiveType::String)),
+            r#const: Some(Value::from("const")),
+            r#default: Some(Value::from("default")),
+            r#enum: vec![EnumEntry::new("enum").unwrap()],
+            formula: Some("1".to_string()),
+            when: Some("true".to_string()),
+            read_only: true,
+            write_only: true,
+            placeholder: Some("placeholder".to_string()),
+            hidden: true,
+            definitions: vec![("foo".to_string(), child())].into_iter().collect(),
+            r#ref: Some("#/definitions/foo".to_string()),
+            resolved: OnceLock::new(),
+            one_of: vec![child()],
+            any_of: vec![child()],
+            all_of: vec![child()],
+            mapping: Some(Mapping::new()),
+            migrations,
+            properties: vec![Property::new("foo", child())],
+            keys: Some(Box::new(child())),
+            values: Some(Box::new(child())),
+            additional_properties: true,
+            separator: Some(",".to_string()),
+            strict_mask: true,
+            items: vec![child()],
+            max_items: Some(1),
+            min_items: Some(1),
+            unique_items: UniqueItems::Boolean(true),
+            orderable: Some(true),
+            addable: Some(true),
+            removable: Some(true),
+            multiple_of: Some(number()),
+            max: Some(number()),
+            exclusive_max: Some(number()),
+            min: Some(number()),
+            exclusive_min: Some(number()),
+            max_length: Some(1),
+            min_length: Some(1),
+            pattern: Some(regex::Regex::new("^a$").unwrap()),
+            extensions: Extensions::default(),
+        };
+
+        let value = serde_json::to_value(&schema).unwrap();
+        let keys: Vec<&String> = value.as_object().unwrap().keys().collect();
+        for key in &keys {
+            assert!(KEYWORDS.contains(&key.as_str()), "'{}' missing in KEYWORDS", key);
+        }
+        for keyword in KEYWORDS {
+            assert!(
+                keys.iter().any(|x| x == keyword),
+                "'{}' is not a schema keyword",
+                keyword
+            );
+        }
+    }
+
+    #[test]
+    fn accept_all_keywords() {
+        for keyword in KEYWORDS {
+            if let Err(e) = strict().parse(&format!("{}: ~", keyword)) {
+                assert!(!e.msg().contains("unknown keyword"), "{}", e);
+            }
+        }
+    }
+
+    #[test]
+    fn extensions() {
+        let options = ParseOptions::new().extension_prefix("x-").extension_prefix("balena-");
+        let schema = options
+            .parse("type: string\nx-widget: color\nbalena-since: [2, 29]\nfoo: bar")
+            .unwrap();
+        assert_eq!(schema.extensions().len(), 2);
+        assert_eq!(schema.extension("x-widget").unwrap(), "color");
+        assert_eq!(schema.extension("balena-since").unwrap(), &serde_json::json!([2, 29]));
+
+        let error = options.strict(true).parse("type: string\nx-widget: color\nfoo: bar");
+        assert_eq!(error.unwrap_err().msg(), "unknown keyword 'foo'");
+    }
+
+    #[test]
+    fn version_2_is_strict() {
+        let schema: Schema = "version: 2\ntype: string\nx-widget: color".parse().unwrap();
+        assert_eq!(schema.extension("x-widget").unwrap(), "color");
+
+        let error = "version: 2\ntype: string\nmaxlength: 10".parse::<Schema>().unwrap_err();
+        assert_eq!(error.msg(), "unknown keyword 'maxlength', did you mean 'maxLength'?");
+    }
+
+    #[test]
+    fn restore_options() {
+        assert!(strict().parse("type: string\nfoo: 10").is_err());
+        assert!("type: string\nfoo: 10".parse::<Schema>().is_ok());
+    }
+
+    #[test]
+    fn suggestions() {
+        assert_eq!(suggestion("maxlength"), Some("maxLength"));
+        assert_eq!(suggestion("defualt"), Some("default"));
+        assert_eq!(suggestion("propertes"), Some("properties"));
+        assert_eq!(suggestion("ab"), None);
+        assert_eq!(suggestion("something"), None);
+    }
+}
@@ -1,16 +1,29 @@
 use std::fmt;
 
-use serde::de;
+use serde::{
+    de,
+    ser::{self, SerializeMap},
+};
 
 use crate::schema::Schema;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Property {
     name: String,
     schema: Schema,
 }
 
 impl Property {
+    pub fn new<S>(name: S, schema: Schema) -> Property
+    where
+        S: Into<String>,
+    {
+        Property {
+            name: name.into(),
+            schema,
+        }
+    }
+
     pub fn name(&self) -> &str {
         &self.name
     }
@@ -18,6 +31,10 @@ impl Property {
     pub fn schema(&self) -> &Schema {
         &self.schema
     }
+
+    pub(crate) fn schema_mut(&mut self) -> &mut Schema {
+        &mut self.schema
+    }
 }
 
 struct PropertyVisitor;
@@ -49,3 +66,14 @@ impl<'de> de::Deserialize<'de> for Property {
         deserializer.deserialize_map(PropertyVisitor)
     }
 }
+
+impl ser::Serialize for Property {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: ser::Serializer,
+    {
+        let mut map = serializer.serialize_map(Some(1))?;
+        map.serialize_entry(&self.name, &self.schema)?;
+        map.end()
+    }
+}
@@ -0,0 +1,490 @@
+//! Custom type registry
+//!
+//! Applications can extend the set of primitive types with their own types (`udev-rule`, ...)
+//! without forking the crate. A custom type is based on a built-in type, data are validated
+//! against the base type first and then against the optional pattern & validation function.
+//! The JSON Schema `format` and the UI Schema `ui:widget` hints are used by the generator.
+//!
+//! Custom types are registered in a [`Registry`] passed to the parser with the [`ParseOptions`],
+//! the parsed schema keeps the types it uses. Registries of different schemas are independent,
+//! a type name can be registered only once in a registry.
+//!
+//! ```
+//! use jellyschema::schema::{registry::{CustomType, Registry}, ParseOptions, PrimitiveType};
+//! use jellyschema::validator::validate;
+//! use serde_json::json;
+//!
+//! let mut registry = Registry::new();
+//! registry
+//!     .register(
+//!         CustomType::new("udev-rule", PrimitiveType::Text)
+//!             .with_format("udev-rule")
+//!             .with_validator(|scope, data| {
+//!                 match data.as_str() {
+//!                     Some(rule) if !rule.contains("==") => scope.error("type", "missing match key").into(),
+//!                     _ => Default::default(),
+//!                 }
+//!             }),
+//!     )
+//!     .unwrap();
+//!
+//! let schema = ParseOptions::new()
+//!     .registry(registry)
+//!     .parse(
+//!         r#"
+//!   version: 1
+//!   properties:
+//!     - rule:
+//!         type: udev-rule
+//! "#,
+//!     )
+//!     .unwrap();
+//!
+//! assert!(validate(&schema, &json!({"rule": "KERNEL==\"sda\""})).is_valid());
+//! assert!(!validate(&schema, &json!({"rule": "foo"})).is_valid());
+//! ```
+//!
+//! [`Registry`]: struct.Registry.html
+//! [`ParseOptions`]: ../struct.ParseOptions.html
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::Arc;
+
+use regex::Regex;
+use serde_derive::Deserialize;
+use serde_json::Value;
+
+use crate::{
+    error::Error,
+    schema::PrimitiveType,
+    validator::{ScopedSchema, ValidationState},
+};
+
+/// Custom type validation function, called with data already valid against the base type
+pub type ValidateFn = dyn Fn(&ScopedSchema, &Value) -> ValidationState + Send + Sync;
+
+/// Application type definition
+pub struct CustomType {
+    name: String,
+    base: PrimitiveType,
+    pattern: Option<Regex>,
+    format: Option<String>,
+    widget: Option<String>,
+    validator: Option<Box<ValidateFn>>,
+}
+
+impl CustomType {
+    /// Creates new custom type
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - Type name used in schemas
+    /// * `base` - Built-in type data must be valid against
+    pub fn new<S>(name: S, base: PrimitiveType) -> CustomType
+    where
+        S: Into<String>,
+    {
+        CustomType {
+            name: name.into(),
+            base,
+            pattern: None,
+            format: None,
+            widget: None,
+            validator: None,
+        }
+    }
+
+    /// Creates new custom type from the definition
+    pub fn from_definition<S>(name: S, definition: &TypeDefinition) -> Result<CustomType, Error>
+    where
+        S: Into<String>,
+    {
+        let name = name.into();
+        let base = match definition.r#type.parse() {
+            Ok(PrimitiveType::Custom(_)) | Err(_) => {
+                return Err(Error::message(format!(
+                    "invalid base type of '{}': \"{}\"",
+                    name, definition.r#type
+                )));
+            }
+            Ok(base) => base,
+        };
+
+        let mut custom_type = CustomType::new(name, base);
+
+        if let Some(pattern) = &definition.pattern {
+            let regex = Regex::new(pattern)
+                .map_err(|e| Error::message(format!("invalid pattern of '{}': {}", custom_type.name, e)))?;
+            custom_type = custom_type.with_pattern(regex);
+        }
+        if let Some(format) = &definition.format {
+            custom_type = custom_type.with_format(format.as_str());
+        }
+        if let Some(widget) = &definition.widget {
+            custom_type = custom_type.with_widget(widget.as_str());
+        }
+
+        Ok(custom_type)
+    }
+
+    /// String data must match the pattern
+    pub fn with_pattern(self, pattern: Regex) -> CustomType {
+        CustomType {
+            pattern: Some(pattern),
+            ..self
+        }
+    }
+
+    /// JSON Schema `format` keyword value
+    pub fn with_format<S>(self, format: S) -> CustomType
+    where
+        S: Into<String>,
+    {
+        CustomType {
+            format: Some(format.into()),
+            ..self
+        }
+    }
+
+    /// UI Schema `ui:widget` keyword value
+    pub fn with_widget<S>(self, widget: S) -> CustomType
+    where
+        S: Into<String>,
+    {
+        CustomType {
+            widget: Some(widget.into()),
+            ..self
+        }
+    }
+
+    pub fn with_validator<F>(self, validator: F) -> CustomType
+    where
+        F: Fn(&ScopedSchema, &Value) -> ValidationState + Send + Sync + 'static,
+    {
+        CustomType {
+            validator: Some(Box::new(validator)),
+            ..self
+        }
+    }
+}
+
+// The validation function can't be compared, types are equal if they're the same registered type
+impl PartialEq for CustomType {
+    fn eq(&self, other: &CustomType) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+impl fmt::Debug for CustomType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CustomType")
+            .field("name", &self.name)
+            .field("base", &self.base)
+            .field("pattern", &self.pattern)
+            .field("format", &self.format)
+            .field("widget", &self.widget)
+            .finish()
+    }
+}
+
+//
+// Accessors
+//
+
+impl CustomType {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn base(&self) -> &PrimitiveType {
+        &self.base
+    }
+
+    pub fn pattern(&self) -> Option<&Regex> {
+        self.pattern.as_ref()
+    }
+
+    pub fn format(&self) -> Option<&str> {
+        self.format.as_deref()
+    }
+
+    pub fn widget(&self) -> Option<&str> {
+        self.widget.as_deref()
+    }
+}
+
+impl CustomType {
+    /// Validates data already valid against the base type
+    pub fn validate(&self, scope: &ScopedSchema, data: &Value) -> ValidationState {
+        if let (Some(pattern), Some(s)) = (&self.pattern, data.as_str()) {
+            if !pattern.is_match(s) {
+                return scope.error("type", format!("expected '{}'", self.name)).into();
+            }
+        }
+
+        match &self.validator {
+            Some(validator) => validator(scope, data),
+            None => ValidationState::new(),
+        }
+    }
+}
+
+/// Declarative custom type definition (without the validation function)
+///
+/// ```yaml
+/// type: string
+/// pattern: ^[A-Z]+$
+/// format: upper-case
+/// widget: textarea
+/// ```
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TypeDefinition {
+    /// Built-in base type name
+    r#type: String,
+    #[serde(default)]
+    pattern: Option<String>,
+    #[serde(default)]
+    format: Option<String>,
+    #[serde(default)]
+    widget: Option<String>,
+}
+
+/// Set of custom types
+///
+/// Schemas using custom types are parsed with the registry passed via the
+/// [`ParseOptions::registry`] method, the parsed schema keeps the types it uses.
+///
+/// [`ParseOptions::registry`]: ../struct.ParseOptions.html#method.registry
+#[derive(Clone, Default)]
+pub struct Registry {
+    types: BTreeMap<String, Arc<CustomType>>,
+}
+
+impl Registry {
+    pub fn new() -> Registry {
+        Registry::default()
+    }
+
+    /// Registers the custom type
+    ///
+    /// Fails if the name is empty, contains whitespace or `?`, clashes with a built-in
+    /// type name or with an already registered type. The base type must be a built-in type.
+    pub fn register(&mut self, custom_type: CustomType) -> Result<(), Error> {
+        let name = custom_type.name.as_str();
+
+        if name.is_empty() || name.ends_with('?') || name.contains(char::is_whitespace) {
+            return Err(Error::message(format!("invalid custom type name: \"{}\"", name)));
+        }
+
+        if PrimitiveType::is_builtin_name(name) {
+            return Err(Error::message(format!(
+                "custom type \"{}\" clashes with a built-in type",
+                name
+            )));
+        }
+
+        if let PrimitiveType::Custom(base) = &custom_type.base {
+            return Err(Error::message(format!(
+                "custom type \"{}\" must be based on a built-in type, not \"{}\"",
+                name,
+                base.name()
+            )));
+        }
+
+        if self.types.contains_key(name) {
+            return Err(Error::message(format!(
+                "custom type \"{}\" is already registered",
+                name
+            )));
+        }
+
+        self.types.insert(name.to_string(), Arc::new(custom_type));
+        Ok(())
+    }
+
+    /// Registered custom type
+    pub fn get(&self, name: &str) -> Option<&Arc<CustomType>> {
+        self.types.get(name)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.types.is_empty()
+    }
+}
+
+impl fmt::Debug for Registry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_set().entries(self.types.keys()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::{
+        generator::generate_json_ui_schema,
+        schema::{ParseOptions, Schema, Type},
+        validator::validate,
+    };
+
+    fn parse(registry: &Registry, schema: &str) -> Result<Schema, Error> {
+        ParseOptions::new().registry(registry.clone()).parse(schema)
+    }
+
+    #[test]
+    fn reject_invalid_types() {
+        let mut registry = Registry::new();
+        let nested = PrimitiveType::Custom(Arc::new(CustomType::new("foo", PrimitiveType::String)));
+
+        assert!(registry.register(CustomType::new("", PrimitiveType::String)).is_err());
+        assert!(registry
+            .register(CustomType::new("foo bar", PrimitiveType::String))
+            .is_err());
+        assert!(registry
+            .register(CustomType::new("hostname", PrimitiveType::String))
+            .is_err());
+        assert!(registry
+            .register(CustomType::new("date-time", PrimitiveType::String))
+            .is_err());
+        assert!(registry.register(CustomType::new("nested", nested)).is_err());
+        assert!(registry.is_empty());
+        assert!("unregistered-type".parse::<PrimitiveType>().is_err());
+    }
+
+    #[test]
+    fn reject_registered_name() {
+        let mut registry = Registry::new();
+        registry
+            .register(CustomType::new("foo", PrimitiveType::String))
+            .unwrap();
+
+        let error = registry
+            .register(CustomType::new("foo", PrimitiveType::Boolean))
+            .unwrap_err();
+        assert_eq!(error.msg(), "custom type \"foo\" is already registered");
+        assert_eq!(registry.get("foo").unwrap().base(), &PrimitiveType::String);
+    }
+
+    #[test]
+    fn parse_custom_type() {
+        let mut registry = Registry::new();
+        registry
+            .register(CustomType::new("foo", PrimitiveType::Integer))
+            .unwrap();
+
+        let schema = parse(&registry, "type: foo?").unwrap();
+        let t: &Type = schema.r#type();
+        assert_eq!(
+            t,
+            &Type::new_optional(PrimitiveType::Custom(registry.get("foo").unwrap().clone()))
+        );
+        assert_eq!(t.to_string(), "foo?");
+        assert_eq!(t.primitive_type().base_type(), PrimitiveType::Integer);
+
+        // Types are available to the schema parsed with the registry only
+        assert!("type: foo".parse::<Schema>().is_err());
+    }
+
+    #[test]
+    fn independent_registries() {
+        let mut a = Registry::new();
+        a.register(CustomType::new("foo", PrimitiveType::String)).unwrap();
+        let mut b = Registry::new();
+        b.register(CustomType::new("foo", PrimitiveType::Boolean)).unwrap();
+
+        let schema_a = parse(&a, "type: foo").unwrap();
+        let schema_b = parse(&b, "type: foo").unwrap();
+
+        assert_eq!(schema_a.r#type().primitive_type().base_type(), PrimitiveType::String);
+        assert_eq!(schema_b.r#type().primitive_type().base_type(), PrimitiveType::Boolean);
+        assert!(validate(&schema_a, &json!("bar")).is_valid());
+        assert!(!validate(&schema_b, &json!("bar")).is_valid());
+    }
+
+    #[test]
+    fn validate_custom_type() {
+        let mut registry = Registry::new();
+        registry
+            .register(
+                CustomType::new("even", PrimitiveType::Integer).with_validator(|scope, data| {
+                    if data.as_i64().unwrap() % 2 == 0 {
+                        ValidationState::new()
+                    } else {
+                        scope.error("type", "expected an even number").into()
+                    }
+                }),
+            )
+            .unwrap();
+
+        let schema = parse(&registry, "properties:\n  - a:\n      type: even\n      max: 10").unwrap();
+
+        assert!(validate(&schema, &json!({"a": 2})).is_valid());
+
+        // Base type is validated first, including its keywords
+        let state = validate(&schema, &json!({"a": "2"}));
+        assert_eq!(state.errors()[0].message(), "expected 'even'");
+        let state = validate(&schema, &json!({"a": 12}));
+        assert_eq!(state.errors()[0].keyword(), "max");
+
+        let state = validate(&schema, &json!({"a": 3}));
+        assert_eq!(state.errors()[0].message(), "expected an even number");
+        assert_eq!(state.errors()[0].data_path(), "a");
+    }
+
+    #[test]
+    fn validate_pattern() {
+        let definition: TypeDefinition = serde_yaml::from_str("type: string\npattern: ^[A-Z]+$").unwrap();
+        let mut registry = Registry::new();
+        registry
+            .register(CustomType::from_definition("upper-case", &definition).unwrap())
+            .unwrap();
+
+        let schema = parse(&registry, "type: upper-case").unwrap();
+        assert!(validate(&schema, &json!("ABC")).is_valid());
+        assert!(!validate(&schema, &json!("abc")).is_valid());
+    }
+
+    #[test]
+    fn invalid_definitions() {
+        let definition = |s: &str| serde_yaml::from_str::<TypeDefinition>(s).unwrap();
+
+        let error = CustomType::from_definition("foo", &definition("type: bar"))
+            .err()
+            .unwrap();
+        assert_eq!(error.msg(), "invalid base type of 'foo': \"bar\"");
+        assert!(CustomType::from_definition("foo", &definition("type: string\npattern: '['")).is_err());
+        assert!(serde_yaml::from_str::<TypeDefinition>("type: string\nfoo: bar").is_err());
+    }
+
+    #[test]
+    fn generate_hints() {
+        let mut registry = Registry::new();
+        registry
+            .register(
+                CustomType::new("hints", PrimitiveType::Text)
+                    .with_format("hints")
+                    .with_widget("code"),
+            )
+            .unwrap();
+        registry
+            .register(CustomType::new("base-hints", PrimitiveType::Password))
+            .unwrap();
+
+        let schema = parse(
+            &registry,
+            "properties:\n  - a:\n      type: hints\n  - b:\n      type: base-hints",
+        )
+        .unwrap();
+        let (json_schema, ui_schema) = generate_json_ui_schema(&schema);
+
+        assert_eq!(json_schema["properties"]["a"]["type"], "string");
+        assert_eq!(json_schema["properties"]["a"]["format"], "hints");
+        assert_eq!(ui_schema["a"]["ui:widget"], "code");
+
+        assert_eq!(json_schema["properties"]["b"]["writeOnly"], true);
+        assert_eq!(ui_schema["b"]["ui:widget"], "password");
+    }
+}
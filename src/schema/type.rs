@@ -1,6 +1,11 @@
-use std::{fmt, str::FromStr};
+use std::{fmt, str::FromStr, sync::Arc};
 
-use crate::error::Error;
+use serde::ser;
+
+use crate::{
+    error::Error,
+    schema::{options, registry::CustomType, version},
+};
 
 const OBJECT_KEYWORD: &str = "object";
 const BOOLEAN_KEYWORD: &str = "boolean";
@@ -25,7 +30,44 @@ const DNSMASQ_ADDRESS_KEYWORD: &str = "dnsmasq-address"; // TODO: Update spec
 const CHRONY_ADDRESS_KEYWORD: &str = "chrony-address"; // TODO: Update spec
 const IPTABLES_ADDRESS_KEYWORD: &str = "iptables-address"; // TODO: Update spec
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+// Type names renamed in the version 2 (version 1 name, version 2 name)
+const RENAMED_KEYWORDS: &[(&str, &str)] = &[(DATE_TIME_KEYWORD, "date-time"), (STRINGLIST_KEYWORD, "string-list")];
+
+/// Version 2 name of the version 1 type name, `None` if it wasn't renamed
+pub(crate) fn renamed_keyword(keyword: &str) -> Option<&'static str> {
+    RENAMED_KEYWORDS
+        .iter()
+        .find(|(v1, _)| *v1 == keyword)
+        .map(|(_, v2)| *v2)
+}
+
+// Translates the type name to the version 1 name according to the current grammar
+fn version_1_keyword(keyword: &str) -> Result<&str, Error> {
+    let grammar = version::grammar();
+
+    if let Some((_, v2)) = RENAMED_KEYWORDS.iter().find(|(v1, _)| *v1 == keyword) {
+        if grammar == Some(2) {
+            return Err(Error::message(format!(
+                "invalid primitive type: \"{}\", renamed to \"{}\" in version 2",
+                keyword, v2
+            )));
+        }
+    }
+
+    if let Some((v1, _)) = RENAMED_KEYWORDS.iter().find(|(_, v2)| *v2 == keyword) {
+        if grammar == Some(1) {
+            return Err(Error::message(format!(
+                "invalid primitive type: \"{}\", requires version 2",
+                keyword
+            )));
+        }
+        return Ok(v1);
+    }
+
+    Ok(keyword)
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub enum PrimitiveType {
     Object,
     Boolean,
@@ -49,6 +91,8 @@ pub enum PrimitiveType {
     DNSMasqAddress,
     ChronyAddress,
     IPTablesAddress,
+    /// Application type of the [`Registry`](registry/struct.Registry.html) the schema was parsed with
+    Custom(Arc<CustomType>),
 }
 
 impl AsRef<str> for PrimitiveType {
@@ -76,6 +120,7 @@ impl AsRef<str> for PrimitiveType {
             PrimitiveType::DNSMasqAddress => DNSMASQ_ADDRESS_KEYWORD,
             PrimitiveType::ChronyAddress => CHRONY_ADDRESS_KEYWORD,
             PrimitiveType::IPTablesAddress => IPTABLES_ADDRESS_KEYWORD,
+            PrimitiveType::Custom(custom_type) => custom_type.name(),
         }
     }
 }
@@ -86,39 +131,65 @@ impl fmt::Display for PrimitiveType {
     }
 }
 
+impl PrimitiveType {
+    // Built-in type with the version 1 name
+    fn builtin(keyword: &str) -> Option<PrimitiveType> {
+        match keyword {
+            OBJECT_KEYWORD => Some(PrimitiveType::Object),
+            BOOLEAN_KEYWORD => Some(PrimitiveType::Boolean),
+            STRING_KEYWORD => Some(PrimitiveType::String),
+            PASSWORD_KEYWORD => Some(PrimitiveType::Password),
+            HOSTNAME_KEYWORD => Some(PrimitiveType::Hostname),
+            INTEGER_KEYWORD => Some(PrimitiveType::Integer),
+            ARRAY_KEYWORD => Some(PrimitiveType::Array),
+            NUMBER_KEYWORD => Some(PrimitiveType::Number),
+            DATE_TIME_KEYWORD => Some(PrimitiveType::DateTime),
+            DATE_KEYWORD => Some(PrimitiveType::Date),
+            TIME_KEYWORD => Some(PrimitiveType::Time),
+            EMAIL_KEYWORD => Some(PrimitiveType::Email),
+            IPV4_KEYWORD => Some(PrimitiveType::IPv4),
+            IPV6_KEYWORD => Some(PrimitiveType::IPv6),
+            URI_KEYWORD => Some(PrimitiveType::Uri),
+            FILE_KEYWORD => Some(PrimitiveType::File),
+            PORT_KEYWORD => Some(PrimitiveType::Port),
+            TEXT_KEYWORD => Some(PrimitiveType::Text),
+            STRINGLIST_KEYWORD => Some(PrimitiveType::StringList),
+            DNSMASQ_ADDRESS_KEYWORD => Some(PrimitiveType::DNSMasqAddress),
+            CHRONY_ADDRESS_KEYWORD => Some(PrimitiveType::ChronyAddress),
+            IPTABLES_ADDRESS_KEYWORD => Some(PrimitiveType::IPTablesAddress),
+            _ => None,
+        }
+    }
+
+    /// `true` if the name is reserved for a built-in type in any schema version
+    pub(crate) fn is_builtin_name(name: &str) -> bool {
+        PrimitiveType::builtin(name).is_some() || RENAMED_KEYWORDS.iter().any(|(_, v2)| *v2 == name)
+    }
+
+    /// Built-in type the custom type is based on, built-in types are returned as they are
+    pub fn base_type(&self) -> PrimitiveType {
+        match self {
+            PrimitiveType::Custom(custom_type) => custom_type.base().clone(),
+            _ => self.clone(),
+        }
+    }
+}
+
 impl FromStr for PrimitiveType {
     type Err = Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s {
-            OBJECT_KEYWORD => Ok(PrimitiveType::Object),
-            BOOLEAN_KEYWORD => Ok(PrimitiveType::Boolean),
-            STRING_KEYWORD => Ok(PrimitiveType::String),
-            PASSWORD_KEYWORD => Ok(PrimitiveType::Password),
-            HOSTNAME_KEYWORD => Ok(PrimitiveType::Hostname),
-            INTEGER_KEYWORD => Ok(PrimitiveType::Integer),
-            ARRAY_KEYWORD => Ok(PrimitiveType::Array),
-            NUMBER_KEYWORD => Ok(PrimitiveType::Number),
-            DATE_TIME_KEYWORD => Ok(PrimitiveType::DateTime),
-            DATE_KEYWORD => Ok(PrimitiveType::Date),
-            TIME_KEYWORD => Ok(PrimitiveType::Time),
-            EMAIL_KEYWORD => Ok(PrimitiveType::Email),
-            IPV4_KEYWORD => Ok(PrimitiveType::IPv4),
-            IPV6_KEYWORD => Ok(PrimitiveType::IPv6),
-            URI_KEYWORD => Ok(PrimitiveType::Uri),
-            FILE_KEYWORD => Ok(PrimitiveType::File),
-            PORT_KEYWORD => Ok(PrimitiveType::Port),
-            TEXT_KEYWORD => Ok(PrimitiveType::Text),
-            STRINGLIST_KEYWORD => Ok(PrimitiveType::StringList),
-            DNSMASQ_ADDRESS_KEYWORD => Ok(PrimitiveType::DNSMasqAddress),
-            CHRONY_ADDRESS_KEYWORD => Ok(PrimitiveType::ChronyAddress),
-            IPTABLES_ADDRESS_KEYWORD => Ok(PrimitiveType::IPTablesAddress),
-            _ => Err(Error::message(format!("invalid primitive type: \"{}\"", s))),
+        if let Some(primitive_type) = PrimitiveType::builtin(version_1_keyword(s)?) {
+            return Ok(primitive_type);
         }
+
+        options::registered_type(s)
+            .map(PrimitiveType::Custom)
+            .ok_or_else(|| Error::message(format!("invalid primitive type: \"{}\"", s)))
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Type {
     primitive_type: PrimitiveType,
     optional: bool,
@@ -165,10 +236,9 @@ impl FromStr for Type {
     type Err = Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let (optional, s) = if s.ends_with('?') {
-            (true, &s[..s.len() - 1])
-        } else {
-            (false, s)
+        let (optional, s) = match s.strip_suffix('?') {
+            Some(s) => (true, s),
+            None => (false, s),
         };
         let primitive_type = s.parse::<PrimitiveType>()?;
         Ok(Type::new(primitive_type, optional))
@@ -181,6 +251,25 @@ impl Default for Type {
     }
 }
 
+impl ser::Serialize for Type {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: ser::Serializer,
+    {
+        let name = self.primitive_type().as_ref();
+        let name = match renamed_keyword(name) {
+            Some(v2) if version::grammar() == Some(2) => v2,
+            _ => name,
+        };
+
+        if self.is_optional() {
+            serializer.collect_str(&format_args!("{}?", name))
+        } else {
+            serializer.serialize_str(name)
+        }
+    }
+}
+
 impl fmt::Display for Type {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         if self.is_optional() {
@@ -233,6 +322,38 @@ mod tests {
         );
     }
 
+    #[test]
+    fn version_2_type_names() {
+        let parse = |version, s: &str| version::with_grammar(version, || s.parse::<Type>());
+
+        assert_eq!(
+            parse(Some(2), "date-time?").unwrap(),
+            Type::new_optional(PrimitiveType::DateTime)
+        );
+        assert_eq!(
+            parse(Some(2), "string-list").unwrap(),
+            Type::new_required(PrimitiveType::StringList)
+        );
+        assert_eq!(
+            parse(Some(2), "datetime").unwrap_err().msg(),
+            "invalid primitive type: \"datetime\", renamed to \"date-time\" in version 2"
+        );
+        assert_eq!(
+            parse(Some(1), "string-list").unwrap_err().msg(),
+            "invalid primitive type: \"string-list\", requires version 2"
+        );
+        assert!(parse(None, "date-time").is_ok());
+        assert!(parse(None, "datetime").is_ok());
+    }
+
+    #[test]
+    fn serialize_version_2_type_names() {
+        let t = Type::new_optional(PrimitiveType::DateTime);
+        assert_eq!(serde_json::to_value(&t).unwrap(), "datetime?");
+        let value = version::with_grammar(Some(2), || serde_json::to_value(&t).unwrap());
+        assert_eq!(value, "date-time?");
+    }
+
     #[test]
     fn required_object_type() {
         assert!(!"string".parse::<Type>().unwrap().optional);
@@ -242,4 +363,12 @@ mod tests {
     fn optional_object_type() {
         assert!("string?".parse::<Type>().unwrap().optional);
     }
+
+    #[test]
+    fn serialize_with_optional_suffix() {
+        let t = Type::new_optional(PrimitiveType::DNSMasqAddress);
+        assert_eq!(serde_json::to_value(&t).unwrap(), "dnsmasq-address?");
+        let t = Type::new_required(PrimitiveType::String);
+        assert_eq!(serde_json::to_value(&t).unwrap(), "string");
+    }
 }
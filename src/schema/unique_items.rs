@@ -5,7 +5,7 @@ use serde::{
     ser::{self, SerializeSeq},
 };
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum UniqueItems {
     Boolean(bool),
     Paths(Vec<String>),
@@ -1,12 +1,36 @@
+use std::{cell::Cell, fmt};
+
 use serde::{de, ser};
-use serde_json::Number;
+use serde_derive::Deserialize;
+
+use crate::error::Error;
 
-#[derive(Debug, PartialEq)]
+/// Latest supported schema version
+pub const LATEST_VERSION: u8 = 2;
+
+/// Schema version
+///
+/// Version 2 differs from the version 1 in:
+///
+/// * `date-time` and `string-list` type names (`datetime` and `stringlist` in the version 1)
+/// * unknown keywords are rejected, keywords with the `x-` prefix are vendor extensions
+///
+/// Use the [`migrate`] function to upgrade the version 1 schema.
+///
+/// [`migrate`]: migrate/fn.migrate.html
+#[derive(Debug, Clone, PartialEq)]
 pub struct Version {
     value: u8,
 }
 
 impl Version {
+    pub fn new(value: u8) -> Result<Version, Error> {
+        match value {
+            1..=LATEST_VERSION => Ok(Version { value }),
+            _ => Err(Error::message(format!("unsupported version number: {}", value))),
+        }
+    }
+
     /// Returns schema version value
     pub fn value(&self) -> u8 {
         self.value
@@ -19,18 +43,47 @@ impl Default for Version {
     }
 }
 
+struct VersionVisitor;
+
+impl<'de> de::Visitor<'de> for VersionVisitor {
+    type Value = Version;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("version number")
+    }
+
+    fn visit_u64<E>(self, v: u64) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        match v {
+            1 => Ok(Version { value: 1 }),
+            2 => Ok(Version { value: 2 }),
+            _ => Err(de::Error::custom("unsuppored version number")),
+        }
+    }
+
+    fn visit_i64<E>(self, _v: i64) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        Err(de::Error::custom("unsuppored version number"))
+    }
+
+    fn visit_f64<E>(self, _v: f64) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        Err(de::Error::custom("unsuppored version number"))
+    }
+}
+
 impl<'de> de::Deserialize<'de> for Version {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
         D: de::Deserializer<'de>,
     {
-        let v = Number::deserialize(deserializer)?;
-
-        if v.as_u64() != Some(1) {
-            Err(de::Error::custom("unsuppored version number"))
-        } else {
-            Ok(Version { value: 1 })
-        }
+        deserializer.deserialize_any(VersionVisitor)
     }
 }
 
@@ -43,6 +96,52 @@ impl ser::Serialize for Version {
     }
 }
 
+thread_local! {
+    // Grammar version of the schema being deserialized or serialized, `None` if
+    // unknown (generic deserializer) and both grammars are accepted then
+    static GRAMMAR: Cell<Option<u8>> = const { Cell::new(None) };
+}
+
+/// Grammar version of the schema being deserialized or serialized
+pub(crate) fn grammar() -> Option<u8> {
+    GRAMMAR.with(Cell::get)
+}
+
+// Deserializers & serializers do not have access to any context, the grammar
+// version is passed via the thread local storage
+pub(crate) fn with_grammar<T, F>(version: Option<u8>, f: F) -> T
+where
+    F: FnOnce() -> T,
+{
+    let previous = GRAMMAR.with(|x| x.replace(version));
+    let result = f();
+    GRAMMAR.with(|x| x.set(previous));
+    result
+}
+
+/// Version of the YAML schema document, `1` if missing or invalid
+///
+/// Invalid versions are reported by the schema deserialization.
+pub(crate) fn document_version(document: &str) -> u8 {
+    #[derive(Deserialize)]
+    struct Probe {
+        version: Option<u64>,
+    }
+
+    match serde_yaml::from_str::<Probe>(document) {
+        Ok(Probe { version: Some(2) }) => 2,
+        _ => 1,
+    }
+}
+
+/// Version of the schema value, `1` if missing or invalid
+pub(crate) fn value_version(value: &serde_yaml::Value) -> u8 {
+    match value.get("version").and_then(serde_yaml::Value::as_u64) {
+        Some(2) => 2,
+        _ => 1,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -58,14 +157,37 @@ mod tests {
         assert_eq!(v.value(), 1);
     }
 
+    #[test]
+    fn two() {
+        let v: Version = serde_yaml::from_str("2").unwrap();
+        assert_eq!(v.value(), 2);
+        assert_eq!(Version::new(2).unwrap().value(), 2);
+    }
+
     #[test]
     fn fail_on_unsupported_version() {
-        let v: Result<Version, _> = serde_yaml::from_str("2");
+        let v: Result<Version, _> = serde_yaml::from_str("3");
         assert!(v.is_err());
+        assert!(Version::new(3).is_err());
         let v: Result<Version, _> = serde_yaml::from_str("0");
         assert!(v.is_err());
     }
 
+    #[test]
+    fn detect_document_version() {
+        assert_eq!(document_version("version: 2\ntype: string"), 2);
+        assert_eq!(document_version("version: 1"), 1);
+        assert_eq!(document_version("type: string"), 1);
+        assert_eq!(document_version("version: foo"), 1);
+        assert_eq!(document_version("- foo"), 1);
+    }
+
+    #[test]
+    fn restore_grammar() {
+        assert_eq!(with_grammar(Some(2), grammar), Some(2));
+        assert_eq!(grammar(), None);
+    }
+
     #[test]
     fn fail_on_string() {
         let v: Result<Version, _> = serde_yaml::from_str("'1'");
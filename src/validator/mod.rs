@@ -3,7 +3,7 @@ use serde_json::{self, Value};
 use crate::schema::{PrimitiveType, Schema};
 
 pub use error::ValidationError;
-use scope::ScopedSchema;
+pub use scope::ScopedSchema;
 pub use state::ValidationState;
 
 mod error;
@@ -26,18 +26,11 @@ macro_rules! bail_if_invalid {
 }
 
 fn validate_optional(scope: &ScopedSchema, data: Option<&Value>) -> ValidationState {
-    let value_exists = match data {
-        Some(Value::Null) => false,
-        None => false,
-        _ => true,
-    };
+    let value_exists = !matches!(data, Some(Value::Null) | None);
 
     if !value_exists && scope.schema().r#type().is_required() {
         return scope
-            .error(
-                "type",
-                format!("'{}' is not an optional type", scope.schema().r#type().to_string()),
-            )
+            .error("type", format!("'{}' is not an optional type", scope.schema().r#type()))
             .into();
     }
 
@@ -69,6 +62,56 @@ fn validate_enum(scope: &ScopedSchema, data: &Value) -> ValidationState {
     }
 }
 
+// Validates data against all combined schemas, returns number of valid schemas and errors
+// of the invalid ones
+fn validate_against(scope: &ScopedSchema, keyword: &str, schemas: &[Schema], data: &Value) -> (usize, ValidationState) {
+    let scope = scope.scope_with_schema_keyword(keyword);
+
+    let mut valid_count = 0;
+    let mut state = ValidationState::new();
+
+    for (index, schema) in schemas.iter().enumerate() {
+        let nested_state = scope.scope_with_schema_index(index, schema).validate(Some(data));
+
+        if nested_state.is_valid() {
+            valid_count += 1;
+        } else {
+            state.extend(nested_state);
+        }
+    }
+
+    (valid_count, state)
+}
+
+fn validate_combinators(scope: &ScopedSchema, data: &Value) -> ValidationState {
+    let schema = scope.schema();
+    let mut state = ValidationState::new();
+
+    if !schema.one_of().is_empty() {
+        match validate_against(scope, "oneOf", schema.one_of(), data) {
+            (0, errors) => {
+                state.push_error(scope.error("oneOf", "not valid against any schema"));
+                state.extend(errors);
+            }
+            (1, _) => {}
+            _ => state.push_error(scope.error("oneOf", "valid against multiple schemas")),
+        };
+    }
+
+    if !schema.any_of().is_empty() {
+        if let (0, errors) = validate_against(scope, "anyOf", schema.any_of(), data) {
+            state.push_error(scope.error("anyOf", "not valid against any schema"));
+            state.extend(errors);
+        }
+    }
+
+    if !schema.all_of().is_empty() {
+        state.extend(validate_against(scope, "allOf", schema.all_of(), data).1);
+    }
+
+    state
+}
+
 impl<'a> Validator for ScopedSchema<'a> {
     fn validate(&self, data: Option<&Value>) -> ValidationState {
         bail_if_invalid!(validate_optional(self, data));
@@ -80,8 +123,19 @@ impl<'a> Validator for ScopedSchema<'a> {
 
         bail_if_invalid!(validate_const(self, data));
         bail_if_invalid!(validate_enum(self, data));
+        bail_if_invalid!(self.validate_type(data));
+
+        validate_combinators(self, data)
+    }
+}
+
+impl<'a> ScopedSchema<'a> {
+    fn validate_type(&self, data: &Value) -> ValidationState {
+        self.validate_as_type(self.schema().r#type().primitive_type(), data)
+    }
 
-        match self.schema().r#type().primitive_type() {
+    fn validate_as_type(&self, primitive_type: &PrimitiveType, data: &Value) -> ValidationState {
+        match primitive_type {
             PrimitiveType::String => types::validate_as_string(self, data),
             PrimitiveType::Array => types::validate_as_array(self, data),
             PrimitiveType::Boolean => types::validate_as_boolean(self, data),
@@ -104,6 +158,7 @@ impl<'a> Validator for ScopedSchema<'a> {
             PrimitiveType::DNSMasqAddress => types::validate_as_dnsmasq_address(self, data),
             PrimitiveType::IPTablesAddress => types::validate_as_iptables_address(self, data),
             PrimitiveType::StringList => types::validate_as_stringlist(self, data),
+            PrimitiveType::Custom(custom_type) => types::validate_as_custom(self, custom_type, data),
         }
     }
 }
@@ -117,3 +172,8 @@ impl Validator for Schema {
 pub fn validate(schema: &Schema, data: &Value) -> ValidationState {
     schema.validate(Some(data))
 }
+
+// Validates data against a nested schema of the `root` schema
+pub(crate) fn validate_with_root(root: &Schema, schema: &Schema, data: &Value) -> ValidationState {
+    ScopedSchema::new_with_root(root, schema).validate(Some(data))
+}
@@ -5,22 +5,35 @@ use crate::{
 
 #[derive(Debug, Clone)]
 pub struct ScopedSchema<'a> {
+    // Root schema, used to resolve references
+    root: &'a Schema,
     schema: &'a Schema,
     schema_path: PathBuf,
     data_path: PathBuf,
 }
 
 impl<'a> ScopedSchema<'a> {
-    pub fn new(schema: &Schema) -> ScopedSchema {
+    pub fn new(schema: &Schema) -> ScopedSchema<'_> {
         ScopedSchema {
-            schema,
+            root: schema,
+            schema: schema.resolve(schema),
             schema_path: PathBuf::new(),
             data_path: PathBuf::new(),
         }
     }
 
-    pub fn schema(&self) -> &Schema {
-        &self.schema
+    // Nested schema of the `root` schema, references are resolved against the `root`
+    pub fn new_with_root(root: &'a Schema, schema: &'a Schema) -> ScopedSchema<'a> {
+        ScopedSchema {
+            root,
+            schema: root.resolve(schema),
+            schema_path: PathBuf::new(),
+            data_path: PathBuf::new(),
+        }
+    }
+
+    pub fn schema(&self) -> &'a Schema {
+        self.schema
     }
 
     pub fn schema_path(&self) -> &PathBuf {
@@ -33,18 +46,29 @@ impl<'a> ScopedSchema<'a> {
 }
 
 impl<'a> ScopedSchema<'a> {
-    pub fn scope_with_data_index(&self, index: usize) -> ScopedSchema {
+    // Paths are not changed, used for `keys` & `values` schemas
+    pub fn scope_with_schema(&self, schema: &'a Schema) -> ScopedSchema<'a> {
+        ScopedSchema {
+            root: self.root,
+            schema: self.root.resolve(schema),
+            schema_path: self.schema_path.clone(),
+            data_path: self.data_path.clone(),
+        }
+    }
+
+    pub fn scope_with_data_index(&self, index: usize) -> ScopedSchema<'a> {
         let mut data_path = self.data_path.clone();
         data_path.push_index(index);
 
         ScopedSchema {
+            root: self.root,
             schema: self.schema,
             schema_path: self.schema_path.clone(),
             data_path,
         }
     }
 
-    pub fn scope_with_property<'b>(&self, index: usize, property: &'b Property) -> ScopedSchema<'b> {
+    pub fn scope_with_property(&self, index: usize, property: &'a Property) -> ScopedSchema<'a> {
         let mut data_path = self.data_path.clone();
         data_path.push_property(property.name());
 
@@ -54,28 +78,31 @@ impl<'a> ScopedSchema<'a> {
         schema_path.push_property(property.name());
 
         ScopedSchema {
-            schema: property.schema(),
+            root: self.root,
+            schema: self.root.resolve(property.schema()),
             schema_path,
             data_path,
         }
     }
 
-    pub fn scope_with_schema_index<'b>(&self, index: usize, schema: &'b Schema) -> ScopedSchema<'b> {
+    pub fn scope_with_schema_index(&self, index: usize, schema: &'a Schema) -> ScopedSchema<'a> {
         let mut schema_path = self.schema_path.clone();
         schema_path.push_index(index);
 
         ScopedSchema {
-            schema,
+            root: self.root,
+            schema: self.root.resolve(schema),
             schema_path,
             data_path: self.data_path.clone(),
         }
     }
 
-    pub fn scope_with_schema_keyword<S: Into<String>>(&self, keyword: S) -> ScopedSchema {
+    pub fn scope_with_schema_keyword<S: Into<String>>(&self, keyword: S) -> ScopedSchema<'a> {
         let mut schema_path = self.schema_path.clone();
         schema_path.push_property(keyword);
 
         ScopedSchema {
+            root: self.root,
             schema: self.schema,
             schema_path,
             data_path: self.data_path.clone(),
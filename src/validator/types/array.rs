@@ -103,6 +103,13 @@ pub fn validate_as_array(scope: &ScopedSchema, data: &Value) -> ValidationState
         None => return scope.error("type", "expected 'array'").into(),
     };
 
+    let indexes: Vec<usize> = (0..data_array.len()).collect();
+    validate_array_items(scope, data_array, &indexes)
+}
+
+// Items are reported with the data path indexes from `indexes`, they differ from the `data_array`
+// indexes if some items were skipped (empty items of the `stringlist` string)
+pub fn validate_array_items(scope: &ScopedSchema, data_array: &[Value], indexes: &[usize]) -> ValidationState {
     let schema = scope.schema();
 
     let mut state = ValidationState::new();
@@ -126,10 +133,10 @@ pub fn validate_as_array(scope: &ScopedSchema, data: &Value) -> ValidationState
     // Validate items keyword
     let scope = scope.scope_with_schema_keyword("items");
 
-    for (idx, item) in data_array.iter().enumerate() {
+    for (idx, item) in indexes.iter().zip(data_array) {
         let mut valid_count = 0;
 
-        let data_scope = scope.scope_with_data_index(idx);
+        let data_scope = scope.scope_with_data_index(*idx);
 
         let mut data_item_state = ValidationState::new();
 
@@ -0,0 +1,15 @@
+use serde_json::Value;
+
+use crate::{
+    schema::registry::CustomType,
+    validator::{scope::ScopedSchema, state::ValidationState},
+};
+
+pub fn validate_as_custom(scope: &ScopedSchema, custom_type: &CustomType, data: &Value) -> ValidationState {
+    let state = scope.validate_as_type(custom_type.base(), data);
+    if !state.is_valid() {
+        return state;
+    }
+
+    custom_type.validate(scope, data)
+}
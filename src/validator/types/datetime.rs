@@ -31,7 +31,7 @@ pub fn validate_as_datetime(scope: &ScopedSchema, data: &Value) -> ValidationSta
 
 fn is_leap_year(year: usize) -> bool {
     // https://tools.ietf.org/html/rfc3339#appendix-C
-    year % 4 == 0 && (year % 100 != 0 || year % 400 == 0)
+    year.is_multiple_of(4) && (!year.is_multiple_of(100) || year.is_multiple_of(400))
 }
 
 fn days(year: usize, month: usize) -> usize {
@@ -55,11 +55,11 @@ pub fn validate_as_date(scope: &ScopedSchema, data: &Value) -> ValidationState {
         _ => return scope.error("type", "expected `date`").into(),
     };
 
-    let year: usize = (&captures[1]).parse().expect("invalid regex");
-    let month: usize = (&captures[2]).parse().expect("invalid regex");
-    let day: usize = (&captures[3]).parse().expect("invalid regex");
+    let year: usize = captures[1].parse().expect("invalid regex");
+    let month: usize = captures[2].parse().expect("invalid regex");
+    let day: usize = captures[3].parse().expect("invalid regex");
 
-    if month >= 1 && month <= 12 && day >= 1 && day <= days(year, month) {
+    if (1..=12).contains(&month) && day >= 1 && day <= days(year, month) {
         ValidationState::new()
     } else {
         scope.error("type", "invalid `date` range").into()
@@ -77,9 +77,9 @@ pub fn validate_as_time(scope: &ScopedSchema, data: &Value) -> ValidationState {
         _ => return scope.error("type", "expected `time`").into(),
     };
 
-    let hour: usize = (&captures[1]).parse().expect("invalid regex");
-    let min: usize = (&captures[2]).parse().expect("invalid regex");
-    let sec: usize = (&captures[3]).parse().expect("invalid regex");
+    let hour: usize = captures[1].parse().expect("invalid regex");
+    let min: usize = captures[2].parse().expect("invalid regex");
+    let sec: usize = captures[3].parse().expect("invalid regex");
 
     if (hour <= 23 && min <= 59 && sec <= 59) || (hour == 23 && min == 59 && sec == 60) {
         ValidationState::new()
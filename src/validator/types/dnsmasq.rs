@@ -1,24 +1,25 @@
 use serde_json::Value;
 
-use crate::validator::{
-    scope::ScopedSchema,
-    state::ValidationState,
-    types::{validate_as_ipv4, validate_as_ipv6},
+use crate::{
+    address::DnsmasqAddress,
+    validator::{scope::ScopedSchema, state::ValidationState, types::validate_as_string},
 };
 
-// https://github.com/balena-os/meta-balena/blob/v2.29.2/meta-resin-common/recipes-connectivity/resin-net-config/resin-net-config/resin-net-config#L34-L39
-//
-// This dnsmasq server address can be pretty complex:
-//
-// --server=[/[<domain>]/[domain/]][<ipaddr>[#<port>][@<source-ip>|<interface>[#<port>]]
-//
-// For now, we're going to validate it as either ipv4 or ipv6.
+// Full `dnsmasq --server` syntax, see the `address` module
 
 pub fn validate_as_dnsmasq_address(scope: &ScopedSchema, data: &Value) -> ValidationState {
-    let state = validate_as_ipv4(scope, data);
-    if state.is_valid() {
+    let mut state = validate_as_string(scope, data);
+    if !state.is_valid() {
         return state;
     }
 
-    validate_as_ipv6(scope, data)
+    if let Err(e) = data
+        .as_str()
+        .expect("invalid validate_as_string")
+        .parse::<DnsmasqAddress>()
+    {
+        state.push_error(scope.error("type", format!("invalid 'dnsmasq-address': {}", e.msg())));
+    }
+
+    state
 }
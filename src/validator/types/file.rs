@@ -1,3 +1,4 @@
+use base64::Engine;
 use lazy_static::lazy_static;
 use regex::Regex;
 use serde_json::Value;
@@ -21,7 +22,7 @@ pub fn validate_as_file(scope: &ScopedSchema, data: &Value) -> ValidationState {
         _ => return scope.error("type", "expected `file`").into(),
     };
 
-    if (&captures[1]).is_empty() {
+    if captures[1].is_empty() {
         return scope.error("type", "file name is missing").into();
     }
 
@@ -29,7 +30,7 @@ pub fn validate_as_file(scope: &ScopedSchema, data: &Value) -> ValidationState {
         return scope.error("type", "only base64 is supported").into();
     }
 
-    match base64::decode(&captures[3]) {
+    match base64::engine::general_purpose::STANDARD.decode(&captures[3]) {
         Ok(_) => ValidationState::new(),
         Err(_) => scope.error("type", "unable to decode file data").into(),
     }
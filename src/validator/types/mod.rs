@@ -1,6 +1,7 @@
-pub use array::validate_as_array;
+pub use array::{validate_array_items, validate_as_array};
 pub use boolean::validate_as_boolean;
 pub use chrony::validate_as_chrony_address;
+pub use custom::validate_as_custom;
 pub use datetime::{validate_as_date, validate_as_datetime, validate_as_time};
 pub use dnsmasq::validate_as_dnsmasq_address;
 pub use email::validate_as_email;
@@ -20,6 +21,7 @@ pub use uri::validate_as_uri;
 mod array;
 mod boolean;
 mod chrony;
+mod custom;
 mod datetime;
 mod dnsmasq;
 mod email;
@@ -2,7 +2,10 @@ use std::collections::HashSet;
 
 use serde_json::Value;
 
-use crate::validator::{scope::ScopedSchema, state::ValidationState, Validator};
+use crate::{
+    formula::evaluate_condition,
+    validator::{scope::ScopedSchema, state::ValidationState, Validator},
+};
 
 pub fn validate_as_object(scope: &ScopedSchema, data: &Value) -> ValidationState {
     let object = match data.as_object() {
@@ -16,6 +19,14 @@ pub fn validate_as_object(scope: &ScopedSchema, data: &Value) -> ValidationState
 
     // Validate .properties first
     for (index, property) in scope.schema().properties().iter().enumerate() {
+        // Conditional property is optional if the condition is not met, but it's still
+        // validated if present, the same way as the generated JSON Schema does
+        if let Some(condition) = property.schema().when() {
+            if !object.contains_key(property.name()) && !evaluate_condition(condition, data) {
+                continue;
+            }
+        }
+
         let nested_scope = scope.scope_with_property(index, property);
         let nested_state = nested_scope.validate(object.get(property.name()));
         state.extend(nested_state);
@@ -27,8 +38,12 @@ pub fn validate_as_object(scope: &ScopedSchema, data: &Value) -> ValidationState
         (Some(schema_keys), Some(schema_values)) => {
             for key in remaining_keys {
                 let value = object.get(key);
-                state.extend(schema_keys.validate(Some(&Value::String(key.to_string()))));
-                state.extend(schema_values.validate(value));
+                state.extend(
+                    scope
+                        .scope_with_schema(schema_keys)
+                        .validate(Some(&Value::String(key.to_string()))),
+                );
+                state.extend(scope.scope_with_schema(schema_values).validate(value));
             }
         }
         // Schema doesn't contain keys & values, just check for additional properties
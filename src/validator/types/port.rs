@@ -8,7 +8,7 @@ pub fn validate_as_port(scope: &ScopedSchema, data: &Value) -> ValidationState {
     if state.is_valid() {
         let value = data.as_i64().expect("invalid validate_as_integer");
 
-        if value < 0 || value > 65535 {
+        if !(0..=65535).contains(&value) {
             state.push_error(scope.error("type", format!("expected '{} in 0..65535'", value)));
         }
     }
@@ -1,7 +1,24 @@
 use serde_json::Value;
 
-use crate::validator::{scope::ScopedSchema, state::ValidationState, types::validate_as_array};
+use crate::validator::{
+    scope::ScopedSchema,
+    state::ValidationState,
+    types::{validate_array_items, validate_as_array},
+};
 
+// Array or a string joined with the `separator`, string items are validated as array items,
+// data paths of the string items are indexes in the string (including the skipped empty items)
 pub fn validate_as_stringlist(scope: &ScopedSchema, data: &Value) -> ValidationState {
-    validate_as_array(scope, data)
+    match data.as_str() {
+        Some(s) => {
+            let (indexes, items): (Vec<usize>, Vec<Value>) = scope
+                .schema()
+                .split_stringlist_indexed(s)
+                .into_iter()
+                .map(|(index, x)| (index, Value::String(x)))
+                .unzip();
+            validate_array_items(scope, &items, &indexes)
+        }
+        None => validate_as_array(scope, data),
+    }
 }
@@ -1,12 +1,16 @@
 use console_error_panic_hook::set_once as set_panic_hook_once;
 use serde_json::{json, Value};
-use std::str::FromStr;
+use std::collections::{BTreeMap, HashMap};
 use wasm_bindgen::prelude::*;
 
 use crate::{
     filler::fill_default_values,
     generator::generate_json_ui_schema,
-    schema::Schema,
+    schema::{
+        loader::{load_schema, MemoryResolver},
+        registry::{CustomType, Registry, TypeDefinition},
+        ParseOptions, Schema,
+    },
     validator::{ValidationError, ValidationState, Validator},
 };
 
@@ -25,18 +29,50 @@ impl JellySchema {
     /// # Arguments
     ///
     /// * `schema` - JellySchema as a string or an object
+    /// * `customTypes` - Optional object with custom type names as keys and definitions as values
+    ///
+    /// ```js
+    /// new JellySchema(schema, {
+    ///     "udev-rule": {
+    ///         "type": "text",
+    ///         "pattern": "==",
+    ///         "format": "udev-rule",
+    ///         "widget": "textarea"
+    ///     }
+    /// });
+    /// ```
+    ///
+    /// Custom types are available to this JellySchema object only.
     ///
     /// # Throws
     ///
-    /// Constructor throws in case of invalid `schema` argument value.
+    /// Constructor throws in case of invalid `schema` or `customTypes` argument value.
     #[wasm_bindgen(constructor)]
-    pub fn constructor(schema: &JsValue) -> Result<JellySchema, JsValue> {
+    pub fn constructor(schema: &JsValue, custom_types: &JsValue) -> Result<JellySchema, JsValue> {
         set_panic_hook_once();
 
+        let mut registry = Registry::new();
+
+        if !custom_types.is_undefined() && !custom_types.is_null() {
+            let definitions: BTreeMap<String, TypeDefinition> =
+                custom_types.into_serde().map_err(|e| JsValue::from(format!("{}", e)))?;
+
+            for (name, definition) in definitions {
+                CustomType::from_definition(name, &definition)
+                    .and_then(|x| registry.register(x))
+                    .map_err(|e| JsValue::from(format!("{}", e)))?;
+            }
+        }
+
+        let options = ParseOptions::new().registry(registry);
         let schema: Schema = if schema.is_string() {
-            Schema::from_str(&schema.as_string().unwrap()).map_err(|e| JsValue::from(format!("{}", e)))?
+            options
+                .parse(&schema.as_string().unwrap())
+                .map_err(|e| JsValue::from(format!("{}", e)))?
         } else {
-            schema.into_serde().map_err(|e| JsValue::from(format!("{}", e)))?
+            options
+                .apply(|| schema.into_serde())
+                .map_err(|e| JsValue::from(format!("{}", e)))?
         };
 
         Ok(JellySchema {
@@ -46,6 +82,29 @@ impl JellySchema {
         })
     }
 
+    /// Instantiates new JellySchema object composed from multiple files
+    ///
+    /// # Arguments
+    ///
+    /// * `files` - An object with file names as keys and file contents as values
+    /// * `name` - Root schema file name
+    ///
+    /// # Throws
+    ///
+    /// In case of invalid `files` argument value or if the schema can't be loaded.
+    pub fn load(files: &JsValue, name: &str) -> Result<JellySchema, JsValue> {
+        set_panic_hook_once();
+
+        let files: HashMap<String, String> = files.into_serde().map_err(|e| JsValue::from(format!("{}", e)))?;
+        let schema = load_schema(&MemoryResolver::from(files), name).map_err(|e| JsValue::from(format!("{}", e)))?;
+
+        Ok(JellySchema {
+            schema,
+            json_ui_schema: None,
+            last_validation_state: ValidationState::new(),
+        })
+    }
+
     /// Fills missing `default` values
     ///
     /// # Arguments
@@ -137,11 +196,11 @@ pub fn generateJsonAndUiSchema(schema: &JsValue) -> Result<JsValue, JsValue> {
     // This is okay for now, but in the future, when the `constructor`
     // will be much more expensive (doing lot of other things), we will have to
     // replace it with direct calls to `generate_json_ui_schema`, etc.
-    JellySchema::constructor(schema)?.jsonAndUiSchema()
+    JellySchema::constructor(schema, &JsValue::UNDEFINED)?.jsonAndUiSchema()
 }
 
 #[wasm_bindgen]
 #[allow(non_snake_case)]
 pub fn fillDefaultValues(schema: &JsValue, data: &JsValue, include_optional: bool) -> Result<JsValue, JsValue> {
-    JellySchema::constructor(schema)?.fillDefaultValues(data, include_optional)
+    JellySchema::constructor(schema, &JsValue::UNDEFINED)?.fillDefaultValues(data, include_optional)
 }
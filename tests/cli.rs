@@ -0,0 +1,132 @@
+use std::process::{Command, Output};
+
+use serde_json::{json, Value};
+
+const SCHEMA: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/cli/schema.yaml");
+const VALID_DATA: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/cli/valid-data.json");
+const INVALID_DATA: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/cli/invalid-data.json");
+const BALENA_OS: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/fuzz/seeds/balena-os.yml");
+
+fn run(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_jellyschema"))
+        .args(args)
+        .output()
+        .expect("unable to run jellyschema")
+}
+
+fn stdout_json(output: &Output) -> Value {
+    serde_json::from_slice(&output.stdout).expect("invalid JSON output")
+}
+
+fn stderr_json(output: &Output) -> Value {
+    serde_json::from_slice(&output.stderr).expect("invalid JSON error output")
+}
+
+#[test]
+fn generate() {
+    let output = run(&["generate", SCHEMA]);
+    assert_eq!(output.status.code(), Some(0));
+    let value = stdout_json(&output);
+    assert_eq!(value["jsonSchema"]["$$version"], 1);
+    assert_eq!(value["uiSchema"]["ui:order"], json!(["hostname", "retries"]));
+}
+
+#[test]
+fn validate() {
+    let output = run(&["validate", SCHEMA, VALID_DATA]);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "valid\n");
+
+    let output = run(&["validate", SCHEMA, INVALID_DATA]);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "hostname: expected 'hostname' (keyword: type)\n"
+    );
+
+    let output = run(&["--format", "json", "validate", SCHEMA, INVALID_DATA]);
+    assert_eq!(output.status.code(), Some(1));
+    let value = stdout_json(&output);
+    assert_eq!(value["valid"], false);
+    assert_eq!(value["errors"][0]["dataPath"], "hostname");
+}
+
+#[test]
+fn fill() {
+    let output = run(&["fill", SCHEMA]);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout_json(&output), json!({"hostname": "balena"}));
+}
+
+#[test]
+fn lint() {
+    let output = run(&["--format", "json", "lint", SCHEMA]);
+    assert_eq!(output.status.code(), Some(1));
+    let value = stdout_json(&output);
+    assert_eq!(value[0]["code"], "inverted-range");
+    assert_eq!(value[0]["schemaPath"], "properties[1].retries.min");
+}
+
+#[test]
+fn lint_balena_os() {
+    let output = run(&["lint", "--deny-warnings", BALENA_OS]);
+    assert_eq!(
+        output.status.code(),
+        Some(0),
+        "{}",
+        String::from_utf8_lossy(&output.stdout)
+    );
+}
+
+#[test]
+fn render() {
+    let directory = std::env::temp_dir().join(format!("jellyschema-cli-{}", std::process::id()));
+    let output = run(&[
+        "--format",
+        "json",
+        "render",
+        SCHEMA,
+        VALID_DATA,
+        directory.to_str().unwrap(),
+    ]);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout_json(&output)["removed"], json!([]));
+
+    let content = std::fs::read_to_string(directory.join("boot/config.json")).unwrap();
+    assert_eq!(
+        serde_json::from_str::<Value>(&content).unwrap(),
+        json!({"hostname": "foo"})
+    );
+    std::fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn usage_errors() {
+    assert_eq!(run(&[]).status.code(), Some(2));
+    assert_eq!(run(&["foo"]).status.code(), Some(2));
+    assert_eq!(run(&["validate", SCHEMA]).status.code(), Some(2));
+    assert_eq!(run(&["lint", SCHEMA, "--foo"]).status.code(), Some(2));
+    assert_eq!(run(&["lint", "missing.yaml"]).status.code(), Some(2));
+    assert_eq!(run(&["--help"]).status.code(), Some(0));
+}
+
+#[test]
+fn json_errors() {
+    let output = run(&["--format", "json", "lint", "missing.yaml"]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(output.stdout.is_empty());
+    let value = stderr_json(&output);
+    assert!(value["error"]["message"].as_str().unwrap().contains("missing.yaml"));
+    assert_eq!(value["error"]["line"], Value::Null);
+
+    let output = run(&["--format", "json", "validate", SCHEMA]);
+    assert_eq!(output.status.code(), Some(2));
+    assert_eq!(
+        stderr_json(&output)["error"]["message"],
+        "invalid number of arguments for 'validate'"
+    );
+
+    let output = run(&["--format", "json"]);
+    assert_eq!(output.status.code(), Some(2));
+    assert_eq!(stderr_json(&output)["error"]["message"], "missing command");
+}